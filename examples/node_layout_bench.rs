@@ -0,0 +1,85 @@
+// Times BTreeMap's insert, find and remove, for comparing node layouts. This is the benchmark
+// behind the numbers in the commit that kept children stored inline in their parent.
+//
+// Run it with `cargo run --release --example node_layout_bench`. It inserts KEYS random u64 keys
+// in random order, looks each one up, then removes them all in another random order, for each B
+// and value type below, and prints the best of RUNS runs of each phase in milliseconds.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use tiny_collections_rs::btreemap::map::BTreeMap;
+
+const KEYS: usize = 1_000_000;
+const RUNS: usize = 3;
+const SEED: u64 = 1808;
+
+// xorshift64*, so every run uses the same keys without needing a crate for it.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, (self.next() % (i as u64 + 1)) as usize);
+        }
+    }
+}
+
+// The three phases' times for one run.
+fn run<V: Copy>(b: usize, keys: &[u64], removal: &[u64], value: V) -> [Duration; 3] {
+    let mut map = BTreeMap::with_b(b);
+
+    let start = Instant::now();
+    for &key in keys {
+        map.insert(key, value);
+    }
+    let insert = start.elapsed();
+
+    let start = Instant::now();
+    for key in keys {
+        black_box(map.find(key));
+    }
+    let find = start.elapsed();
+
+    let start = Instant::now();
+    for key in removal {
+        black_box(map.remove(key));
+    }
+    let remove = start.elapsed();
+
+    assert!(map.is_empty());
+    [insert, find, remove]
+}
+
+fn bench<V: Copy>(label: &str, b: usize, keys: &[u64], removal: &[u64], value: V) {
+    let mut best = [Duration::MAX; 3];
+    for _ in 0..RUNS {
+        for (best, time) in best.iter_mut().zip(run(b, keys, removal, value)) {
+            *best = (*best).min(time);
+        }
+    }
+    let [insert, find, remove] = best.map(|time| time.as_millis());
+    println!("b={b:<2} {label:<9} {insert}/{find}/{remove}ms");
+}
+
+fn main() {
+    let mut rng = Rng(SEED);
+    let mut keys: Vec<u64> = (0..KEYS).map(|_| rng.next()).collect();
+    keys.sort_unstable();
+    keys.dedup();
+    rng.shuffle(&mut keys);
+    let mut removal = keys.clone();
+    rng.shuffle(&mut removal);
+
+    println!("{} keys, best of {RUNS}, insert/find/remove", keys.len());
+    for b in [3, 6, 16] {
+        bench("u64", b, &keys, &removal, 0u64);
+    }
+    bench("[u8; 64]", 16, &keys, &removal, [0u8; 64]);
+}
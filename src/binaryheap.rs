@@ -0,0 +1,397 @@
+use crate::myvec::{MyVec, MyVecIntoIter};
+use std::{
+    fmt,
+    iter::FusedIterator,
+    ops::{Deref, DerefMut},
+    slice,
+};
+
+/// A priority queue kept as a max-heap in a `MyVec`: every element is at least as big as the two
+/// at `2i + 1` and `2i + 2` below it, so the biggest is always at the front.
+///
+/// `push` adds to the end and sifts the new element up past smaller parents, and `pop` swaps
+/// the last element into the front and sifts it down past bigger children, both in O(log n).
+/// Building a heap from a whole vector with `From` sifts down from the bottom up instead of
+/// pushing one at a time, which is O(n).
+///
+/// If an element's ordering changes while it's in the heap, through `Cell` or the like, the
+/// heap won't crash or leak, but what it hands back in what order is unspecified.
+pub struct BinaryHeap<T> {
+    data: MyVec<T>,
+}
+
+impl<T: Ord> BinaryHeap<T> {
+    pub fn new() -> Self {
+        BinaryHeap { data: MyVec::new() }
+    }
+
+    pub fn push(&mut self, elem: T) {
+        self.data.push(elem);
+        self.sift_up(self.data.len() - 1);
+    }
+
+    /// Removes the biggest element and returns it.
+    pub fn pop(&mut self) -> Option<T> {
+        let last = self.data.len().checked_sub(1)?;
+        self.data.swap(0, last);
+        let top = self.data.pop();
+        self.sift_down(0, last);
+        top
+    }
+
+    /// Returns a guard giving mutable access to the biggest element. Once the guard is dropped,
+    /// the element is sifted back down to wherever its new value belongs.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T>> {
+        (!self.is_empty()).then_some(PeekMut {
+            heap: self,
+            changed: false,
+        })
+    }
+
+    /// Turns the heap into a vector sorted from smallest to biggest, in place, by heapsort.
+    pub fn into_sorted_vec(mut self) -> MyVec<T> {
+        for end in (1..self.data.len()).rev() {
+            self.data.swap(0, end);
+            self.sift_down(0, end);
+        }
+        self.data
+    }
+
+    /// Returns an iterator that pops the elements off the heap, biggest first. Whatever the
+    /// iterator doesn't get to before it's dropped is removed anyway.
+    pub fn drain_sorted(&mut self) -> BinaryHeapDrainSorted<'_, T> {
+        BinaryHeapDrainSorted { heap: self }
+    }
+
+    // Moves the element at `index` up until its parent is no smaller.
+    fn sift_up(&mut self, mut index: usize) {
+        while index > 0 {
+            let parent = (index - 1) / 2;
+            if self.data[index] <= self.data[parent] {
+                break;
+            }
+            self.data.swap(index, parent);
+            index = parent;
+        }
+    }
+
+    // Moves the element at `index` down until neither child is bigger, looking only at the
+    // first `end` elements.
+    fn sift_down(&mut self, mut index: usize, end: usize) {
+        loop {
+            let left = 2 * index + 1;
+            if left >= end {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < end && self.data[right] > self.data[left] {
+                right
+            } else {
+                left
+            };
+            if self.data[index] >= self.data[child] {
+                break;
+            }
+            self.data.swap(index, child);
+            index = child;
+        }
+    }
+}
+
+impl<T> BinaryHeap<T> {
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+    }
+
+    /// Returns the biggest element, the one `pop` would return.
+    pub fn peek(&self) -> Option<&T> {
+        self.data.first()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.truncate(0);
+    }
+
+    /// Returns the elements in heap order: the biggest first, and the rest in no useful order.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    /// Hands back the underlying vector, in heap order.
+    pub fn into_vec(self) -> MyVec<T> {
+        self.data
+    }
+
+    /// Returns an iterator over the elements in heap order.
+    pub fn iter(&self) -> BinaryHeapIter<'_, T> {
+        BinaryHeapIter(self.data.iter())
+    }
+}
+
+/// Builds a heap out of a vector in O(n), by sifting down every element that has children,
+/// from the last one back to the root.
+impl<T: Ord> From<MyVec<T>> for BinaryHeap<T> {
+    fn from(data: MyVec<T>) -> Self {
+        let mut heap = BinaryHeap { data };
+        let len = heap.len();
+        for index in (0..len / 2).rev() {
+            heap.sift_down(index, len);
+        }
+        heap
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for BinaryHeap<T> {
+    fn from(vec: Vec<T>) -> Self {
+        BinaryHeap::from(MyVec::from(vec))
+    }
+}
+
+impl<T> From<BinaryHeap<T>> for MyVec<T> {
+    fn from(heap: BinaryHeap<T>) -> Self {
+        heap.data
+    }
+}
+
+impl<T: Ord> Default for BinaryHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> Clone for BinaryHeap<T> {
+    fn clone(&self) -> Self {
+        BinaryHeap {
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<T: Ord> Extend<T> for BinaryHeap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|elem| self.push(elem));
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: Ord + arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for BinaryHeap<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BinaryHeap<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        BinaryHeap::from(iter.into_iter().collect::<MyVec<T>>())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BinaryHeap<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Mutable access to the biggest element of a BinaryHeap, made by `peek_mut`.
+pub struct PeekMut<'a, T: Ord> {
+    heap: &'a mut BinaryHeap<T>,
+    // Only sift on drop if the element might have changed.
+    changed: bool,
+}
+
+impl<T: Ord> PeekMut<'_, T> {
+    /// Takes the element out of the heap.
+    pub fn pop(mut this: Self) -> T {
+        this.changed = false;
+        this.heap.pop().unwrap()
+    }
+}
+
+impl<T: Ord> Deref for PeekMut<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.heap.data[0]
+    }
+}
+
+impl<T: Ord> DerefMut for PeekMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.changed = true;
+        &mut self.heap.data[0]
+    }
+}
+
+impl<T: Ord> Drop for PeekMut<'_, T> {
+    fn drop(&mut self) {
+        if self.changed {
+            let len = self.heap.len();
+            self.heap.sift_down(0, len);
+        }
+    }
+}
+
+/// An iterator over the elements of a BinaryHeap, in heap order.
+pub struct BinaryHeapIter<'a, T>(slice::Iter<'a, T>);
+
+impl<'a, T> Iterator for BinaryHeapIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for BinaryHeapIter<'_, T> {}
+
+impl<T> FusedIterator for BinaryHeapIter<'_, T> {}
+
+/// An iterator that moves the elements out of a BinaryHeap in heap order, made by `into_iter`.
+pub struct BinaryHeapIntoIter<T>(MyVecIntoIter<T>);
+
+impl<T> Iterator for BinaryHeapIntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for BinaryHeapIntoIter<T> {}
+
+impl<T> FusedIterator for BinaryHeapIntoIter<T> {}
+
+impl<T> IntoIterator for BinaryHeap<T> {
+    type Item = T;
+    type IntoIter = BinaryHeapIntoIter<T>;
+    fn into_iter(self) -> BinaryHeapIntoIter<T> {
+        BinaryHeapIntoIter(self.data.into_iter())
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BinaryHeap<T> {
+    type Item = &'a T;
+    type IntoIter = BinaryHeapIter<'a, T>;
+    fn into_iter(self) -> BinaryHeapIter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator that pops the elements off a BinaryHeap, biggest first, made by `drain_sorted`.
+pub struct BinaryHeapDrainSorted<'a, T: Ord> {
+    heap: &'a mut BinaryHeap<T>,
+}
+
+impl<T: Ord> Iterator for BinaryHeapDrainSorted<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.heap.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
+    }
+}
+
+impl<T: Ord> ExactSizeIterator for BinaryHeapDrainSorted<'_, T> {}
+
+impl<T: Ord> FusedIterator for BinaryHeapDrainSorted<'_, T> {}
+
+// Dropping the rest in sorted order, rather than just clearing, is what the name promises, and
+// matters if the elements' destructors can observe each other.
+impl<T: Ord> Drop for BinaryHeapDrainSorted<'_, T> {
+    fn drop(&mut self) {
+        while self.heap.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BinaryHeap, PeekMut};
+    use crate::myvec::MyVec;
+
+    #[test]
+    fn test_push_pop() {
+        let mut heap = BinaryHeap::new();
+        for x in [5, 1, 8, 3, 9, 2, 8] {
+            heap.push(x);
+        }
+        assert_eq!(heap.peek(), Some(&9));
+        assert_eq!(heap.len(), 7);
+
+        // Lowering the top sifts it back down once the guard goes.
+        *heap.peek_mut().unwrap() = 0;
+        assert_eq!(heap.peek(), Some(&8));
+        assert_eq!(PeekMut::pop(heap.peek_mut().unwrap()), 8);
+
+        let popped: Vec<_> = std::iter::from_fn(|| heap.pop()).collect();
+        assert_eq!(popped, [8, 5, 3, 2, 1, 0]);
+        assert_eq!(heap.pop(), None);
+        assert!(heap.peek_mut().is_none());
+    }
+
+    #[test]
+    fn test_heapify_and_sort() {
+        let data: MyVec<u32> = (0..1000u32)
+            .map(|i| i.wrapping_mul(2_654_435_761) % 997)
+            .collect();
+        let mut expected: Vec<_> = data.iter().copied().collect();
+        expected.sort();
+
+        assert_eq!(BinaryHeap::from(vec![2, 7, 1]).peek(), Some(&7));
+        let heap = BinaryHeap::from(data);
+        let slice = heap.as_slice();
+        for i in 1..slice.len() {
+            assert!(slice[(i - 1) / 2] >= slice[i], "{i}");
+        }
+        assert_eq!(&heap.clone().into_sorted_vec()[..], &expected[..]);
+
+        let mut heap = heap;
+        let top: Vec<_> = heap.drain_sorted().take(3).collect();
+        assert_eq!(
+            top,
+            expected.iter().rev().take(3).copied().collect::<Vec<_>>()
+        );
+        assert!(heap.is_empty());
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        // For each element, an odd byte to keep going and then the element.
+        let bytes: Vec<u8> = (0..200u32)
+            .flat_map(|i| [1, (i * 37 % 251) as u8])
+            .collect();
+        let heap = BinaryHeap::<u8>::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap();
+        let slice = heap.as_slice();
+        assert_eq!(slice.len(), 200);
+        for i in 1..slice.len() {
+            assert!(slice[(i - 1) / 2] >= slice[i], "{i}");
+        }
+    }
+}
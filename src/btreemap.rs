@@ -1,3 +1,10 @@
-pub mod map;
-mod node;
-mod set;
+pub mod compare;
+pub mod concurrent;
+mod inline_stack;
+pub mod map;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod multimap;
+mod node;
+pub mod persistent;
+mod set;
@@ -0,0 +1,181 @@
+#[cfg(feature = "simd")]
+mod simd;
+
+#[cfg(feature = "simd")]
+pub use self::simd::SimdNatural;
+
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+// Runs up to this many keys are scanned linearly, which beats binary search's unpredictable
+// branches on short runs of keys.
+const LINEAR_SEARCH_MAX_LEN: usize = 12;
+
+/// An ordering on keys, for maps whose keys aren't `Ord` or should sort some other way than their
+/// `Ord` impl. Any `Fn(&T, &T) -> Ordering` closure is a comparator.
+///
+/// A comparator must be a total order, and must not change while a map is using it.
+pub trait Comparator<T: ?Sized> {
+    fn compare(&self, a: &T, b: &T) -> Ordering;
+
+    /// Finds `key` in `keys`, which are sorted by this comparator: `Ok` with its index, or `Err`
+    /// with the index it would be inserted at.
+    ///
+    /// Maps search every node with this, so a comparator that knows a faster way to search its
+    /// keys can override it. The default calls `compare` on one key at a time.
+    fn search<K: Borrow<T>>(&self, keys: &[K], key: &T) -> Result<usize, usize> {
+        if keys.len() > LINEAR_SEARCH_MAX_LEN {
+            return keys.binary_search_by(|k| self.compare(k.borrow(), key));
+        }
+        for (i, k) in keys.iter().enumerate() {
+            match self.compare(k.borrow(), key) {
+                Ordering::Less => continue,
+                Ordering::Equal => return Ok(i),
+                Ordering::Greater => return Err(i),
+            }
+        }
+        Err(keys.len())
+    }
+}
+
+/// The comparator maps use by default, which orders keys by their `Ord` impl.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Natural;
+
+impl<T: ?Sized + Ord> Comparator<T> for Natural {
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+impl<T: ?Sized, F> Comparator<T> for F
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn compare(&self, a: &T, b: &T) -> Ordering {
+        self(a, b)
+    }
+}
+
+macro_rules! ordered_float {
+    ($(#[$doc:meta])* $name:ident, $float:ty, $bits:ty) => {
+        $(#[$doc])*
+        ///
+        /// Values are ordered by `total_cmp`, so every value, NaN included, has a place:
+        /// negative NaNs sort first, then negative infinity, the negative numbers, `-0.0`, `0.0`,
+        /// the positive numbers, positive infinity, and positive NaNs last. Equality and hashing
+        /// go by the same order, so `-0.0` and `0.0` are different keys, while two NaNs with the
+        /// same bits are the same key.
+        #[derive(Clone, Copy, Debug, Default)]
+        pub struct $name(pub $float);
+
+        impl $name {
+            pub fn into_inner(self) -> $float {
+                self.0
+            }
+        }
+
+        impl From<$float> for $name {
+            fn from(value: $float) -> $name {
+                $name(value)
+            }
+        }
+
+        impl From<$name> for $float {
+            fn from(value: $name) -> $float {
+                value.0
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &$name) -> bool {
+                self.cmp(other) == Ordering::Equal
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl PartialOrd for $name {
+            fn partial_cmp(&self, other: &$name) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for $name {
+            fn cmp(&self, other: &$name) -> Ordering {
+                self.0.total_cmp(&other.0)
+            }
+        }
+
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.0.to_bits().hash(state)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        #[cfg(feature = "arbitrary")]
+        impl<'a> arbitrary::Arbitrary<'a> for $name {
+            fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+                Ok($name(<$float>::from_bits(u.arbitrary::<$bits>()?)))
+            }
+
+            fn size_hint(depth: usize) -> (usize, Option<usize>) {
+                <$bits as arbitrary::Arbitrary>::size_hint(depth)
+            }
+        }
+    };
+}
+
+ordered_float!(
+    /// An `f64` that is `Ord`, so floats can be used as map keys.
+    OrderedF64,
+    f64,
+    u64
+);
+ordered_float!(
+    /// An `f32` that is `Ord`, so floats can be used as map keys.
+    OrderedF32,
+    f32,
+    u32
+);
+
+#[cfg(test)]
+mod test {
+    use super::super::map::BTreeMap;
+    use super::OrderedF64;
+
+    #[test]
+    fn test_ordered_float() {
+        let values = [
+            -f64::NAN,
+            f64::NEG_INFINITY,
+            -1.5,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            2.0,
+            f64::INFINITY,
+            f64::NAN,
+        ];
+        let mut map = BTreeMap::new();
+        for (i, &value) in values.iter().enumerate().rev() {
+            map.insert(OrderedF64(value), i);
+        }
+        assert_eq!(map.len(), values.len());
+        assert!(map.iter().map(|(_, &i)| i).eq(0..values.len()));
+        assert_eq!(map.find(&OrderedF64(f64::NAN)), Some(&8));
+        assert_eq!(map.find(&OrderedF64(0.0)), Some(&4));
+        assert_eq!(map.find(&OrderedF64(1.0)), None);
+        assert_ne!(OrderedF64(0.0), OrderedF64(-0.0));
+    }
+}
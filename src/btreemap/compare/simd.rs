@@ -0,0 +1,220 @@
+// A comparator for integer keys that searches a node a cache line at a time. The keys are copied
+// into a 64-byte line of signed lanes, and with SSE2 each line is compared against the search key
+// in four registers; anywhere else it's a plain loop over the line, which the compiler can
+// vectorize as it likes.
+
+use super::Comparator;
+use std::{borrow::Borrow, cmp::Ordering, mem};
+
+/// Orders keys the same way as `Natural`, but searches each node by comparing a whole cache line
+/// of keys at once instead of one key at a time. It's a comparator for `u32`, `i32`, `u64` and
+/// `i64` keys, for use with `BTreeMap::with_comparator`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SimdNatural;
+
+const LINE_BYTES: usize = 64;
+
+// The signed type keys are compared as. Unsigned keys have their sign bit flipped first, which
+// orders them the same way.
+trait Lane: Copy + Ord {
+    const MAX: Self;
+
+    // Returns how many lanes of `line`, which is LINE_BYTES long, are less than `needle`.
+    fn count_less(line: &[Self], needle: Self) -> usize;
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+mod imp {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    const REGS: usize = super::LINE_BYTES / 16;
+
+    impl super::Lane for i32 {
+        const MAX: i32 = i32::MAX;
+
+        fn count_less(line: &[i32], needle: i32) -> usize {
+            assert_eq!(line.len(), REGS * 4);
+            let mut count = 0;
+            unsafe {
+                let needle = _mm_set1_epi32(needle);
+                let regs = line.as_ptr().cast::<__m128i>();
+                for r in 0..REGS {
+                    let less = _mm_cmpgt_epi32(needle, _mm_loadu_si128(regs.add(r)));
+                    count += _mm_movemask_epi8(less).count_ones() / 4;
+                }
+            }
+            count as usize
+        }
+    }
+
+    impl super::Lane for i64 {
+        const MAX: i64 = i64::MAX;
+
+        fn count_less(line: &[i64], needle: i64) -> usize {
+            assert_eq!(line.len(), REGS * 2);
+            let mut count = 0;
+            unsafe {
+                let needle = _mm_set1_epi64x(needle);
+                // SSE2 only compares 32-bit lanes, so a 64-bit lane is less if its high half is
+                // less, or the high halves are equal and its low half is less unsigned.
+                let flip_low = _mm_set1_epi64x(1 << 31);
+                let regs = line.as_ptr().cast::<__m128i>();
+                for r in 0..REGS {
+                    let keys = _mm_loadu_si128(regs.add(r));
+                    let gt = _mm_cmpgt_epi32(needle, keys);
+                    let eq = _mm_cmpeq_epi32(needle, keys);
+                    let gt_low = _mm_cmpgt_epi32(
+                        _mm_xor_si128(needle, flip_low),
+                        _mm_xor_si128(keys, flip_low),
+                    );
+                    let less = _mm_or_si128(gt, _mm_and_si128(eq, _mm_slli_epi64(gt_low, 32)));
+                    // Only the high half of each lane has the full answer.
+                    count += (_mm_movemask_epi8(less) & 0xf0f0).count_ones() / 4;
+                }
+            }
+            count as usize
+        }
+    }
+}
+
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+)))]
+mod imp {
+    macro_rules! impl_lane {
+        ($($t:ty),*) => {$(
+            impl super::Lane for $t {
+                const MAX: $t = <$t>::MAX;
+
+                fn count_less(line: &[$t], needle: $t) -> usize {
+                    line.iter().filter(|&&lane| lane < needle).count()
+                }
+            }
+        )*};
+    }
+
+    impl_lane!(i32, i64);
+}
+
+// Returns the index of the first key that isn't less than `needle`. Keys are copied a line at a
+// time, with the end of the last line padded out with keys that are never less.
+fn lower_bound<K, L: Lane>(keys: &[K], needle: L, lane: impl Fn(&K) -> L) -> usize {
+    let per_line = LINE_BYTES / mem::size_of::<L>();
+    let mut buf = [L::MAX; LINE_BYTES / 4];
+    let line = &mut buf[..per_line];
+    let mut count = 0;
+    for chunk in keys.chunks(per_line) {
+        for (slot, key) in line.iter_mut().zip(chunk) {
+            *slot = lane(key);
+        }
+        line[chunk.len()..].fill(L::MAX);
+        let less = L::count_less(line, needle);
+        count += less;
+        if less < chunk.len() {
+            break;
+        }
+    }
+    count
+}
+
+macro_rules! impl_simd_natural {
+    ($($t:ty => $lane:ty, $flip:expr);*) => {$(
+        impl Comparator<$t> for SimdNatural {
+            fn compare(&self, a: &$t, b: &$t) -> Ordering {
+                a.cmp(b)
+            }
+
+            fn search<K: Borrow<$t>>(&self, keys: &[K], key: &$t) -> Result<usize, usize> {
+                let lane = |key: &$t| (key ^ $flip) as $lane;
+                let i = lower_bound(keys, lane(key), |k| lane(k.borrow()));
+                match keys.get(i) {
+                    Some(k) if k.borrow() == key => Ok(i),
+                    _ => Err(i),
+                }
+            }
+        }
+    )*};
+}
+
+impl_simd_natural!(
+    u32 => i32, 1 << 31;
+    i32 => i32, 0;
+    u64 => i64, 1 << 63;
+    i64 => i64, 0
+);
+
+#[cfg(test)]
+mod test {
+    use super::super::{super::map::BTreeMap, Comparator, Natural};
+    use super::SimdNatural;
+    use crate::test_util::Lcg;
+
+    #[test]
+    fn test_search() {
+        fn check<T: Copy + Ord + std::fmt::Debug>(keys: &mut Vec<T>, probes: &[T])
+        where
+            SimdNatural: Comparator<T>,
+        {
+            keys.sort();
+            keys.dedup();
+            for len in 0..=keys.len() {
+                let keys = &keys[..len];
+                for probe in probes.iter().chain(keys) {
+                    let expected = Natural.search(keys, probe);
+                    assert_eq!(
+                        SimdNatural.search(keys, probe),
+                        expected,
+                        "{probe:?} in {keys:?}"
+                    );
+                }
+            }
+        }
+
+        let mut rng = Lcg::new(1780);
+        let mut wide = || (rng.below(1 << 16) as u64) << 48 | rng.below(1 << 16) as u64;
+        let mut keys: Vec<u64> = (0..40).map(|_| wide()).collect();
+        keys.extend([0, u64::MAX, 1 << 63, (1 << 63) - 1, 1 << 31, (1 << 32) - 1]);
+        let probes: Vec<u64> = (0..40).map(|_| wide()).chain([0, u64::MAX]).collect();
+        check(&mut keys.clone(), &probes);
+
+        // Keys that only differ in their low halves, where the low half's top bit matters.
+        let low = |k: &u64| 5 << 32 | k >> 32;
+        check(
+            &mut keys.iter().map(low).collect(),
+            &probes.iter().map(low).collect::<Vec<_>>(),
+        );
+
+        let signed = |k: &u64| *k as i64;
+        check(
+            &mut keys.iter().map(signed).collect(),
+            &probes.iter().map(signed).collect::<Vec<_>>(),
+        );
+        let narrow = |k: &u64| (k >> 32) as u32;
+        check(
+            &mut keys.iter().map(narrow).collect(),
+            &probes.iter().map(narrow).collect::<Vec<_>>(),
+        );
+        let narrow_signed = |k: &u64| (k >> 32) as i32;
+        check(
+            &mut keys.iter().map(narrow_signed).collect(),
+            &probes.iter().map(narrow_signed).collect::<Vec<_>>(),
+        );
+
+        let mut map = BTreeMap::with_comparator(SimdNatural);
+        for (i, &key) in keys.iter().enumerate() {
+            map.insert(key, i);
+        }
+        assert!(map.iter().map(|(k, _)| k).is_sorted());
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(map.find(key), Some(&i));
+        }
+        assert_eq!(map.find(&1), None);
+    }
+}
@@ -0,0 +1,503 @@
+use std::{
+    borrow::Borrow,
+    cell::RefCell,
+    cmp::Ordering::*,
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering::Relaxed},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+/// An ordered map that many threads can read and write at once through a shared reference.
+///
+/// Every node has its own lock, and operations walk down the tree by lock coupling: the lock on a
+/// child is taken before the one on its parent is let go, so a thread only ever holds the locks on
+/// a node and the few nodes right below it. Writers split full nodes and top up underfull ones on
+/// the way down, like the top-down B-tree of the textbooks, so nothing they do ever has to go back
+/// up the tree. Threads working in different parts of the tree don't wait for each other.
+///
+/// Values can't be borrowed out past a node's lock, so `get` hands back a clone.
+///
+/// If a thread panics while it holds a lock, the map is poisoned and later operations panic too.
+pub struct ConcurrentBTreeMap<K, V> {
+    // The root changes when the tree grows or shrinks, so the pointer to it has a lock of its own.
+    root: RwLock<Link<K, V>>,
+    length: AtomicUsize,
+    b: usize,
+}
+
+type Link<K, V> = Arc<RwLock<Node<K, V>>>;
+
+struct Node<K, V> {
+    keys: Vec<K>,
+    vals: Vec<V>,
+    edges: Vec<Link<K, V>>,
+}
+
+// The nodes an operation has locked, kept alive until the operation is done. A lock guard borrows
+// the lock it came from, so the `Arc` owning that lock has to outlive the guard somewhere other
+// than the guard's own value: a node merged away while it's locked loses its place in the tree,
+// and with it every other owner, before the lock on it is let go.
+struct Pins<K, V>(RefCell<Vec<Link<K, V>>>);
+
+type ReadLatch<'a, K, V> = RwLockReadGuard<'a, Node<K, V>>;
+type WriteLatch<'a, K, V> = RwLockWriteGuard<'a, Node<K, V>>;
+
+impl<K, V> Pins<K, V> {
+    fn new() -> Pins<K, V> {
+        Pins(RefCell::new(Vec::new()))
+    }
+
+    fn pin(&self, link: &Link<K, V>) -> &RwLock<Node<K, V>> {
+        let link = Arc::clone(link);
+        let lock = Arc::as_ptr(&link);
+        self.0.borrow_mut().push(link);
+        // SAFETY: nothing is ever taken out of the list before `self` is dropped, so the Arc just
+        // put in it keeps the lock alive for as long as `self` is borrowed.
+        unsafe { &*lock }
+    }
+
+    fn read(&self, link: &Link<K, V>) -> ReadLatch<'_, K, V> {
+        self.pin(link).read().expect("ConcurrentBTreeMap poisoned")
+    }
+
+    fn write(&self, link: &Link<K, V>) -> WriteLatch<'_, K, V> {
+        self.pin(link).write().expect("ConcurrentBTreeMap poisoned")
+    }
+}
+
+impl<K: Ord, V> Default for ConcurrentBTreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> ConcurrentBTreeMap<K, V> {
+    /// Makes a new empty ConcurrentBTreeMap with a reasonable choice for B.
+    pub fn new() -> ConcurrentBTreeMap<K, V> {
+        ConcurrentBTreeMap::with_b(6)
+    }
+
+    /// Makes a new empty ConcurrentBTreeMap with the given B.
+    pub fn with_b(b: usize) -> ConcurrentBTreeMap<K, V> {
+        assert!(b > 1, "B must be greater than 1");
+        ConcurrentBTreeMap {
+            root: RwLock::new(Arc::new(RwLock::new(Node::new()))),
+            length: AtomicUsize::new(0),
+            b,
+        }
+    }
+
+    /// The number of entries. With other threads writing, it may be out of date by the time it's
+    /// looked at.
+    pub fn len(&self) -> usize {
+        self.length.load(Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a clone of the value for `key`.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+        V: Clone,
+    {
+        let pins = Pins::new();
+        let mut node = {
+            let root = self.root.read().expect("ConcurrentBTreeMap poisoned");
+            pins.read(&root)
+        };
+        loop {
+            let child = match node.search(key) {
+                Ok(i) => return Some(node.vals[i].clone()),
+                Err(i) => pins.read(node.edges.get(i)?),
+            };
+            // The child is locked before the parent is let go.
+            node = child;
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let pins = Pins::new();
+        let mut node = {
+            let root = self.root.read().expect("ConcurrentBTreeMap poisoned");
+            pins.read(&root)
+        };
+        loop {
+            let child = match node.search(key) {
+                Ok(_) => return true,
+                Err(i) => match node.edges.get(i) {
+                    Some(child) => pins.read(child),
+                    None => return false,
+                },
+            };
+            node = child;
+        }
+    }
+
+    /// Inserts a key-value pair into the map, and returns the old value if the key was already
+    /// there.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let capacity = self.capacity();
+        let b = self.b;
+        let pins = Pins::new();
+        // A full root is split before anything else, which is the only way the tree grows.
+        let mut node = self.lock_root(
+            &pins,
+            |root| root.keys.len() == capacity,
+            |root_link, mut root| {
+                let mut new_root = Node::new();
+                new_root.edges.push(Arc::clone(root_link));
+                new_root.split_child(0, &mut *root, b);
+                *root_link = Arc::new(RwLock::new(new_root));
+                drop(root);
+                pins.write(root_link)
+            },
+        );
+
+        loop {
+            let index = match node.search(&key) {
+                Ok(i) => return Some(mem::replace(&mut node.vals[i], value)),
+                Err(i) if node.is_leaf() => {
+                    node.keys.insert(i, key);
+                    node.vals.insert(i, value);
+                    self.length.fetch_add(1, Relaxed);
+                    return None;
+                }
+                Err(i) => i,
+            };
+
+            let mut child = pins.write(&node.edges[index]);
+            if child.keys.len() == capacity {
+                // Split the child now, so that the parent has room if the insertion below needs
+                // to split again.
+                node.split_child(index, &mut child, b);
+                match key.cmp(&node.keys[index]) {
+                    Equal => {
+                        return Some(mem::replace(&mut node.vals[index], value));
+                    }
+                    Greater => {
+                        child = pins.write(&node.edges[index + 1]);
+                    }
+                    Less => {}
+                }
+            }
+            node = child;
+        }
+    }
+
+    /// Removes `key` from the map, and returns its value.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let min_load = self.b - 1;
+        let pins = Pins::new();
+        // The tree only shrinks when the root's last key is merged down into its two children.
+        let mut node = self.lock_root(
+            &pins,
+            |root| root.keys.len() == 1 && !root.is_leaf(),
+            |root_link, mut root| {
+                let mut left = pins.write(&root.edges[0]);
+                let mut right = pins.write(&root.edges[1]);
+                if left.keys.len() > min_load || right.keys.len() > min_load {
+                    return root;
+                }
+                root.merge_children(0, &mut left, &mut right);
+                drop(right);
+                *root_link = Arc::clone(&root.edges[0]);
+                left
+            },
+        );
+
+        loop {
+            let (index, found) = match node.search(key) {
+                Ok(i) if node.is_leaf() => {
+                    node.keys.remove(i);
+                    self.length.fetch_sub(1, Relaxed);
+                    return Some(node.vals.remove(i));
+                }
+                Err(_) if node.is_leaf() => return None,
+                Ok(i) => (i, true),
+                Err(i) => (i, false),
+            };
+
+            if found {
+                // Swap in a neighbouring entry from a child that can spare one, or else merge the
+                // key down between its children and carry on from there.
+                let left = pins.write(&node.edges[index]);
+                if left.keys.len() > min_load {
+                    let (key, val) = Node::pop_last(left, min_load, &pins);
+                    node.keys[index] = key;
+                    self.length.fetch_sub(1, Relaxed);
+                    return Some(mem::replace(&mut node.vals[index], val));
+                }
+                let right = pins.write(&node.edges[index + 1]);
+                if right.keys.len() > min_load {
+                    drop(left);
+                    let (key, val) = Node::pop_first(right, min_load, &pins);
+                    node.keys[index] = key;
+                    self.length.fetch_sub(1, Relaxed);
+                    return Some(mem::replace(&mut node.vals[index], val));
+                }
+                let (mut left, mut right) = (left, right);
+                node.merge_children(index, &mut left, &mut right);
+                drop(right);
+                node = left;
+            } else {
+                let child = pins.write(&node.edges[index]);
+                node = node.fix_child(index, child, min_load, &pins);
+            }
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        2 * self.b - 1
+    }
+
+    // Write-lock the root node. Only if `reshape_needed` says so is the lock on the root pointer
+    // kept while `reshape` runs, which may point it at a new root and returns the lock to carry
+    // on with; otherwise the pointer is let go as soon as the root node is locked.
+    fn lock_root<'a, N, R>(
+        &self,
+        pins: &'a Pins<K, V>,
+        reshape_needed: N,
+        reshape: R,
+    ) -> WriteLatch<'a, K, V>
+    where
+        N: Fn(&Node<K, V>) -> bool,
+        R: FnOnce(&mut Link<K, V>, WriteLatch<'a, K, V>) -> WriteLatch<'a, K, V>,
+    {
+        {
+            let root_link = self.root.read().expect("ConcurrentBTreeMap poisoned");
+            let root = pins.write(&root_link);
+            if !reshape_needed(&root) {
+                return root;
+            }
+        }
+        let mut root_link = self.root.write().expect("ConcurrentBTreeMap poisoned");
+        let root = pins.write(&root_link);
+        if reshape_needed(&root) {
+            reshape(&mut root_link, root)
+        } else {
+            root
+        }
+    }
+}
+
+impl<K, V> Node<K, V> {
+    fn new() -> Node<K, V> {
+        Node {
+            keys: Vec::new(),
+            vals: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.keys.binary_search_by(|k| k.borrow().cmp(key))
+    }
+
+    // Split the full child at `index`, which the caller has locked, around its middle key.
+    fn split_child(&mut self, index: usize, child: &mut Node<K, V>, b: usize) {
+        let right = Node {
+            keys: child.keys.split_off(b),
+            vals: child.vals.split_off(b),
+            edges: if child.is_leaf() {
+                Vec::new()
+            } else {
+                child.edges.split_off(b)
+            },
+        };
+        self.keys.insert(index, child.keys.pop().unwrap());
+        self.vals.insert(index, child.vals.pop().unwrap());
+        self.edges.insert(index + 1, Arc::new(RwLock::new(right)));
+    }
+
+    // Move the key at `index` and everything in the right child into the left one.
+    fn merge_children(&mut self, index: usize, left: &mut Node<K, V>, right: &mut Node<K, V>) {
+        left.keys.push(self.keys.remove(index));
+        left.vals.push(self.vals.remove(index));
+        left.keys.append(&mut right.keys);
+        left.vals.append(&mut right.vals);
+        left.edges.append(&mut right.edges);
+        self.edges.remove(index + 1);
+    }
+
+    // Make sure the child at `index` can lose a key, by taking one from a sibling or merging with
+    // one, and return the lock on wherever the child's entries ended up.
+    fn fix_child<'a>(
+        &mut self,
+        index: usize,
+        mut child: WriteLatch<'a, K, V>,
+        min_load: usize,
+        pins: &'a Pins<K, V>,
+    ) -> WriteLatch<'a, K, V> {
+        if child.keys.len() > min_load {
+            return child;
+        }
+        if index > 0 {
+            let mut left = pins.write(&self.edges[index - 1]);
+            if left.keys.len() > min_load {
+                let key = mem::replace(&mut self.keys[index - 1], left.keys.pop().unwrap());
+                let val = mem::replace(&mut self.vals[index - 1], left.vals.pop().unwrap());
+                child.keys.insert(0, key);
+                child.vals.insert(0, val);
+                if let Some(edge) = left.edges.pop() {
+                    child.edges.insert(0, edge);
+                }
+                return child;
+            }
+            if index == self.keys.len() {
+                self.merge_children(index - 1, &mut left, &mut child);
+                return left;
+            }
+        }
+        let mut right = pins.write(&self.edges[index + 1]);
+        if right.keys.len() > min_load {
+            let key = mem::replace(&mut self.keys[index], right.keys.remove(0));
+            let val = mem::replace(&mut self.vals[index], right.vals.remove(0));
+            child.keys.push(key);
+            child.vals.push(val);
+            if !right.is_leaf() {
+                child.edges.push(right.edges.remove(0));
+            }
+            return child;
+        }
+        self.merge_children(index, &mut child, &mut right);
+        child
+    }
+
+    // Take the greatest entry out of the subtree under `node`, which can spare a key.
+    fn pop_last<'a>(
+        mut node: WriteLatch<'a, K, V>,
+        min_load: usize,
+        pins: &'a Pins<K, V>,
+    ) -> (K, V) {
+        loop {
+            if node.is_leaf() {
+                return (node.keys.pop().unwrap(), node.vals.pop().unwrap());
+            }
+            let last = node.keys.len();
+            let child = pins.write(&node.edges[last]);
+            node = node.fix_child(last, child, min_load, pins);
+        }
+    }
+
+    // Take the smallest entry out of the subtree under `node`, which can spare a key.
+    fn pop_first<'a>(
+        mut node: WriteLatch<'a, K, V>,
+        min_load: usize,
+        pins: &'a Pins<K, V>,
+    ) -> (K, V) {
+        loop {
+            if node.is_leaf() {
+                return (node.keys.remove(0), node.vals.remove(0));
+            }
+            let child = pins.write(&node.edges[0]);
+            node = node.fix_child(0, child, min_load, pins);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ConcurrentBTreeMap, Node};
+    use crate::test_util::Lcg;
+    use std::{collections::BTreeMap, thread};
+
+    // Check the tree's shape and collect its entries in order.
+    fn check<K: Ord + Copy, V: Copy>(map: &ConcurrentBTreeMap<K, V>) -> Vec<(K, V)> {
+        fn walk<K: Ord + Copy, V: Copy>(
+            node: &Node<K, V>,
+            b: usize,
+            is_root: bool,
+            depth: usize,
+            leaf_depth: &mut Option<usize>,
+            out: &mut Vec<(K, V)>,
+        ) {
+            assert!(node.keys.len() < 2 * b);
+            assert!(is_root || node.keys.len() >= b - 1);
+            if node.is_leaf() {
+                assert_eq!(*leaf_depth.get_or_insert(depth), depth);
+                out.extend(node.keys.iter().copied().zip(node.vals.iter().copied()));
+                return;
+            }
+            assert_eq!(node.edges.len(), node.keys.len() + 1);
+            for (i, edge) in node.edges.iter().enumerate() {
+                walk(&edge.read().unwrap(), b, false, depth + 1, leaf_depth, out);
+                if let (Some(&key), Some(&val)) = (node.keys.get(i), node.vals.get(i)) {
+                    out.push((key, val));
+                }
+            }
+        }
+        let mut out = Vec::new();
+        let root = map.root.read().unwrap();
+        walk(&root.read().unwrap(), map.b, true, 0, &mut None, &mut out);
+        assert!(out.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(out.len(), map.len());
+        out
+    }
+
+    #[test]
+    fn test_single_thread() {
+        for b in [2, 3, 6] {
+            let map = ConcurrentBTreeMap::with_b(b);
+            let mut expected = BTreeMap::new();
+            let mut rng = Lcg::new(5);
+            for i in 0..3000 {
+                let key = rng.below(500);
+                if i % 3 == 0 {
+                    assert_eq!(map.remove(&key), expected.remove(&key));
+                } else {
+                    assert_eq!(map.insert(key, i), expected.insert(key, i));
+                }
+                assert_eq!(map.get(&key), expected.get(&key).copied());
+            }
+            assert!(check(&map).into_iter().eq(expected.into_iter()));
+        }
+    }
+
+    #[test]
+    fn test_threads() {
+        let map = ConcurrentBTreeMap::with_b(3);
+        thread::scope(|s| {
+            for t in 0..8u32 {
+                let map = &map;
+                s.spawn(move || {
+                    // Each thread owns the keys that are `t` mod 8, so it knows what to expect.
+                    for i in 0..2000 {
+                        let key = i * 8 + t;
+                        assert_eq!(map.insert(key, t), None);
+                        if i % 2 == 0 {
+                            assert_eq!(map.remove(&key), Some(t));
+                        }
+                        assert_eq!(map.get(&key), (i % 2 == 1).then_some(t));
+                    }
+                });
+            }
+        });
+        let entries = check(&map);
+        assert_eq!(entries.len(), 8000);
+        assert!(entries
+            .iter()
+            .all(|&(key, t)| key % 8 == t && (key / 8) % 2 == 1));
+    }
+}
@@ -0,0 +1,112 @@
+use std::{
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    slice,
+};
+
+// A tree this deep has at least 6^10 entries at the default B, so only very large maps, or ones
+// with a tiny B, ever need to go to the heap.
+const INLINE_LEN: usize = 12;
+
+/// A stack for the paths iterators keep through a tree. The first few items are stored inline, so
+/// walking a map of any ordinary depth never allocates. A stack that outgrows that moves to the
+/// heap for good.
+pub struct InlineStack<T: Copy> {
+    len: usize,
+    inline: [MaybeUninit<T>; INLINE_LEN],
+    // Empty until the stack spills, and from then on holds every item.
+    heap: Vec<T>,
+}
+
+impl<T: Copy> InlineStack<T> {
+    pub fn new() -> InlineStack<T> {
+        InlineStack {
+            len: 0,
+            inline: [MaybeUninit::uninit(); INLINE_LEN],
+            heap: Vec::new(),
+        }
+    }
+
+    fn spilled(&self) -> bool {
+        self.heap.capacity() > 0
+    }
+
+    pub fn push(&mut self, item: T) {
+        if self.spilled() {
+            self.heap.push(item);
+        } else if self.len < INLINE_LEN {
+            self.inline[self.len].write(item);
+            self.len += 1;
+        } else {
+            let mut heap = Vec::with_capacity(INLINE_LEN * 2);
+            heap.extend_from_slice(self);
+            heap.push(item);
+            self.heap = heap;
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.spilled() {
+            self.heap.pop()
+        } else if self.len > 0 {
+            self.len -= 1;
+            Some(unsafe { self.inline[self.len].assume_init() })
+        } else {
+            None
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.heap.clear();
+    }
+}
+
+impl<T: Copy> Deref for InlineStack<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        if self.spilled() {
+            &self.heap
+        } else {
+            // The first `len` inline slots are always initialized.
+            unsafe { slice::from_raw_parts(self.inline.as_ptr() as *const T, self.len) }
+        }
+    }
+}
+
+impl<T: Copy> DerefMut for InlineStack<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        if self.spilled() {
+            &mut self.heap
+        } else {
+            unsafe { slice::from_raw_parts_mut(self.inline.as_mut_ptr() as *mut T, self.len) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InlineStack, INLINE_LEN};
+
+    #[test]
+    fn test_spill() {
+        let mut stack = InlineStack::new();
+        for i in 0..INLINE_LEN * 3 {
+            stack.push(i);
+            assert_eq!(stack.last(), Some(&i));
+            assert_eq!(stack.len(), i + 1);
+        }
+        assert!(stack.iter().copied().eq(0..INLINE_LEN * 3));
+        stack[0] = 100;
+        for i in (1..INLINE_LEN * 3).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        assert_eq!(stack.pop(), Some(100));
+        assert_eq!(stack.pop(), None);
+
+        stack.push(1);
+        stack.clear();
+        assert!(stack.is_empty());
+    }
+}
@@ -1,239 +1,2248 @@
-mod stack;
-
-use super::node::{Node, SearchResult::*};
-use stack::{PartialSearchStack, PushResult::*};
-use std::mem;
-// use std::collections::VecDeque;
-
-pub struct BTreeMap<K: Ord, V> {
-    root: Node<K, V>,
-    length: usize,
-    depth: usize,
-    b: usize,
-}
-
-impl<K: Ord, V> Default for BTreeMap<K, V> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl<K: Ord, V> BTreeMap<K, V> {
-    /// Makes a new empty BTreeMap with a reasonable choice for B.
-    pub fn new() -> BTreeMap<K, V> {
-        BTreeMap::with_b(6)
-    }
-
-    pub fn len(&self) -> usize {
-        self.length
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.length == 0
-    }
-
-    /// Makes a new empty BTreeMap with the given B.
-    pub fn with_b(b: usize) -> BTreeMap<K, V> {
-        assert!(b > 1, "B must be greater than 1");
-        BTreeMap {
-            length: 0,
-            depth: 1,
-            root: Node::make_leaf_root(b),
-            b,
-        }
-    }
-    pub fn find(&self, key: &K) -> Option<&V> {
-        let mut cur_node = &self.root;
-        loop {
-            match cur_node.search(key) {
-                Found(i) => return cur_node.val(i),
-                GoDown(i) => match cur_node.edge(i) {
-                    None => return None,
-                    Some(next_node) => {
-                        cur_node = next_node;
-                        continue;
-                    }
-                },
-            }
-        }
-    }
-
-    pub fn insert(&mut self, key: K, mut value: V) -> Option<V> {
-        // Insertion in a B-Tree is a bit complicated.
-        //
-        // First we do the same kind of search described in `find`. But we need to maintain a stack of
-        // all the nodes/edges in our search path. If we find a match for the key we're trying to
-        // insert, just swap the vals and return the old ones. However, when we bottom out in a leaf,
-        // we attempt to insert our key-value pair at the same location we would want to follow another
-        // edge.
-        //
-        // If the node has room, then this is done in the obvious way by shifting elements. However,
-        // if the node itself is full, we split node into two, and give its median key-value
-        // pair to its parent to insert the new node with. Of course, the parent may also be
-        // full, and insertion can propagate until we reach the root. If we reach the root, and
-        // it is *also* full, then we split the root and place the two nodes under a newly made root.
-        //
-        // Note that we subtly deviate from Open Data Structures in our implementation of split.
-        // ODS describes inserting into the node *regardless* of its capacity, and then
-        // splitting *afterwards* if it happens to be overfull. However, this is inefficient.
-        // Instead, we split beforehand, and then insert the key-value pair into the appropriate
-        // result node. This has two consequences:
-        //
-        // 1) While ODS produces a left node of size B-1, and a right node of size B,
-        // we may potentially reverse this. However, this shouldn't effect the analysis.
-        //
-        // 2) While ODS may potentially return the pair we *just* inserted after
-        // the split, we will never do this. Again, this shouldn't effect the analysis.
-        // let stack = VecDeque::new();
-        let mut stack = PartialSearchStack::new(self);
-        loop {
-            match stack.next().search(&key) {
-                Found(i) => unsafe {
-                    let next = stack.into_next();
-                    mem::swap(next.unsafe_val_mut(i), &mut value);
-                    return Some(value);
-                    // return Some(mem::replace(next.unsafe_val_mut(i), value));
-                },
-                GoDown(i) => {
-                    stack = match stack.push(i) {
-                        Done(new_stack) => {
-                            new_stack.insert(key, value);
-                            return None;
-                        }
-                        Grew(new_stack) => new_stack,
-                    };
-                }
-            }
-        }
-    }
-
-    // Deletion is the most complicated operation for a B-Tree.
-    //
-    // First we do the same kind of search described in
-    // `find`. But we need to maintain a stack of all the nodes/edges in our search path.
-    // If we don't find the key, then we just return `None` and do nothing. If we do find the
-    // key, we perform two operations: remove the item, and then possibly handle underflow.
-    //
-    // # removing the item
-    //      If the node is a leaf, we just remove the item, and shift
-    //      any items after it back to fill the hole.
-    //
-    //      If the node is an internal node, we *swap* the item with the smallest item in
-    //      in its right subtree (which must reside in a leaf), and then revert to the leaf
-    //      case
-    //
-    // # handling underflow
-    //      After removing an item, there may be too few items in the node. We want nodes
-    //      to be mostly full for efficiency, although we make an exception for the root, which
-    //      may have as few as one item. If this is the case, we may first try to steal
-    //      an item from our left or right neighbour.
-    //
-    //      To steal from the left (right) neighbour,
-    //      we take the largest (smallest) item and child from it. We then swap the taken item
-    //      with the item in their mutual parent that separates them, and then insert the
-    //      parent's item and the taken child into the first (last) index of the underflowed node.
-    //
-    //      However, stealing has the possibility of underflowing our neighbour. If this is the
-    //      case, we instead *merge* with our neighbour. This of course reduces the number of
-    //      children in the parent. Therefore, we also steal the item that separates the now
-    //      merged nodes, and insert it into the merged node.
-    //
-    //      Merging may cause the parent to underflow. If this is the case, then we must repeat
-    //      the underflow handling process on the parent. If merging merges the last two children
-    //      of the root, then we replace the root with the merged node.
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let mut stack = PartialSearchStack::new(self);
-        loop {
-            match stack.next().search(key) {
-                Found(i) => {
-                    // exact match
-                    return Some(stack.seal(i).remove());
-                }
-                GoDown(i) => {
-                    stack = match stack.push(i) {
-                        Grew(new_stack) => new_stack,
-                        Done(_) => return None,
-                    }
-                }
-            };
-        }
-    }
-}
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn small_test1() {
-        let mut bt = BTreeMap::new();
-        (0..100).for_each(|i| {
-            bt.insert(i, i);
-        });
-
-        (0..100).for_each(|i| {
-            assert_eq!(bt.find(&i), Some(&i));
-        });
-    }
-
-    #[test]
-    fn test_basic_large() {
-        let mut map = BTreeMap::new();
-        let size = 10000;
-        assert_eq!(map.len(), 0);
-
-        for i in 0..size {
-            assert_eq!(map.insert(i, 10 * i), None);
-            assert_eq!(map.len(), i + 1);
-        }
-
-        for i in 0..size {
-            assert_eq!(map.find(&i).unwrap(), &(i * 10));
-        }
-
-        for i in size..size * 2 {
-            assert_eq!(map.find(&i), None);
-        }
-
-        for i in 0..size {
-            assert_eq!(map.insert(i, 100 * i), Some(10 * i));
-            assert_eq!(map.len(), size);
-        }
-
-        for i in 0..size {
-            assert_eq!(map.find(&i).unwrap(), &(i * 100));
-        }
-
-        for i in 0..size / 2 {
-            assert_eq!(map.remove(&(i * 2)), Some(i * 200));
-            assert_eq!(map.len(), size - i - 1);
-        }
-
-        for i in 0..size / 2 {
-            assert_eq!(map.find(&(2 * i)), None);
-            assert_eq!(map.find(&(2 * i + 1)).unwrap(), &(i * 200 + 100));
-        }
-
-        for i in 0..size / 2 {
-            assert_eq!(map.remove(&(2 * i)), None);
-            assert_eq!(map.remove(&(2 * i + 1)), Some(i * 200 + 100));
-            assert_eq!(map.len(), size / 2 - i - 1);
-        }
-    }
-
-    #[test]
-    fn test_basic_small() {
-        let mut map = BTreeMap::new();
-        assert_eq!(map.remove(&1), None);
-        assert_eq!(map.find(&1), None);
-        assert_eq!(map.insert(1, 1), None);
-        assert_eq!(map.find(&1), Some(&1));
-        assert_eq!(map.insert(1, 2), Some(1));
-        assert_eq!(map.find(&1), Some(&2));
-        assert_eq!(map.insert(2, 4), None);
-        assert_eq!(map.find(&2), Some(&4));
-        assert_eq!(map.remove(&1), Some(2));
-        assert_eq!(map.remove(&2), Some(4));
-        assert_eq!(map.remove(&1), None);
-    }
-}
+mod builder;
+mod codec;
+mod cursor;
+#[cfg(any(test, feature = "debug"))]
+mod debug;
+mod entry;
+mod iter;
+mod merge;
+mod stack;
+
+pub use self::builder::BTreeMapBuilder;
+pub use self::codec::{Decode, Encode};
+pub use self::cursor::{Cursor, CursorMut};
+pub use self::entry::{EntryRef, OccupiedEntry, OccupiedError, VacantEntryRef};
+pub use self::iter::{Drain, IntoIter, IntoKeys, IntoValues, Iter, LeafChunks};
+pub use self::merge::{Difference, Intersection, Merged, Union};
+
+use super::compare::{Comparator, Natural};
+use super::node::{Node, NodePool, SearchResult::*};
+use crate::myvec::{Allocator, Global};
+use stack::{PartialSearchStack, PushResult::*, Scratch};
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::from_fn,
+    marker::PhantomData,
+    mem,
+    ops::{
+        Bound::{self, *},
+        Index, IndexMut, RangeBounds,
+    },
+    ptr,
+};
+// use std::collections::VecDeque;
+
+// The most emptied nodes a map keeps around for reuse.
+const NODE_POOL_LIMIT: usize = 16;
+
+// The bytes of entries a node should hold when `new` picks B. Keys and values sit in separate
+// buffers, so searches only walk the keys, and wide nodes pay off even for large values. Below the
+// floor nodes get too deep for big entries, and above the cap shifting entries within a node
+// starts to cost more than the shallower tree saves.
+const NODE_TARGET_BYTES: usize = 512;
+const MIN_DEFAULT_B: usize = 6;
+const MAX_DEFAULT_B: usize = 16;
+
+// Pick B so that a full node holds about NODE_TARGET_BYTES of entries.
+fn default_b<K, V>() -> usize {
+    let entry_size = mem::size_of::<K>() + mem::size_of::<V>();
+    let capacity = NODE_TARGET_BYTES / entry_size.max(1);
+    capacity.div_ceil(2).clamp(MIN_DEFAULT_B, MAX_DEFAULT_B)
+}
+
+/// An ordered map based on a B-Tree. Keys are ordered by the comparator `C`, which is their `Ord`
+/// impl unless the map is made with another one. The nodes get their memory from the allocator
+/// `A`, which is the global one unless the map is made with `new_in` or `with_comparator_in`.
+///
+/// If the comparator panics, the map is never left broken. Single-key operations do all their
+/// comparing before they change anything, so the map is as it was before the call. Operations that
+/// cut up or rebuild the whole tree, like `remove_range`, `merge_with` and `compact`, leave the
+/// map empty.
+pub struct BTreeMap<K, V, C = Natural, A: Allocator = Global> {
+    root: Node<K, V, A>,
+    length: usize,
+    depth: usize,
+    b: usize,
+    pool: NodePool<K, V, A>,
+    scratch: Scratch<K, V, A>,
+    cmp: C,
+}
+
+/// A summary of a map's tree structure, from `BTreeMap::stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    /// The number of levels of nodes, counting the root and the leaves.
+    pub depth: usize,
+    pub nodes: usize,
+    pub leaves: usize,
+    /// The fraction of the nodes' key slots that hold an entry.
+    pub fill_factor: f64,
+    /// The heap memory held by the nodes, divided by the number of entries. This counts each
+    /// node's buffers at their capacity, but not the allocator's own overhead. It's 0 for an
+    /// empty map.
+    pub bytes_per_entry: f64,
+}
+
+impl<K, V, C, A> Default for BTreeMap<K, V, C, A>
+where
+    C: Comparator<K> + Default,
+    A: Allocator + Clone + Default,
+{
+    fn default() -> Self {
+        Self::with_comparator_in(C::default(), A::default())
+    }
+}
+
+impl<K: Ord, V> BTreeMap<K, V> {
+    /// Makes a new empty BTreeMap, with B chosen from the size of the keys and values.
+    pub fn new() -> BTreeMap<K, V> {
+        BTreeMap::with_b(default_b::<K, V>())
+    }
+
+    /// Builds a map from entries sorted by key in O(n), by packing nodes directly rather than
+    /// inserting one entry at a time. Runs of equal keys keep the last value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not in ascending order.
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> BTreeMap<K, V> {
+        BTreeMap::from_sorted_iter_with_comparator(iter, Natural)
+    }
+
+    /// Makes a new empty BTreeMap with the given B.
+    pub fn with_b(b: usize) -> BTreeMap<K, V> {
+        BTreeMap::with_b_and_comparator(b, Natural)
+    }
+}
+
+impl<K: Ord, V, A: Allocator + Clone> BTreeMap<K, V, Natural, A> {
+    /// Makes a new empty BTreeMap whose nodes get their memory from `alloc`, with B chosen as for
+    /// `new`.
+    pub fn new_in(alloc: A) -> BTreeMap<K, V, Natural, A> {
+        BTreeMap::with_comparator_in(Natural, alloc)
+    }
+}
+
+impl<K, V, C: Comparator<K>> BTreeMap<K, V, C> {
+    /// Makes a new empty BTreeMap that orders its keys with `cmp`.
+    pub fn with_comparator(cmp: C) -> BTreeMap<K, V, C> {
+        BTreeMap::with_comparator_in(cmp, Global)
+    }
+
+    /// Builds a map that orders its keys with `cmp` from entries already sorted by it, in O(n),
+    /// like `from_sorted_iter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys are not in ascending order by `cmp`.
+    pub fn from_sorted_iter_with_comparator<I>(iter: I, cmp: C) -> BTreeMap<K, V, C>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut map = BTreeMap::with_comparator(cmp);
+        let (root, length, depth) = Node::from_sorted_iter(map.b, iter, &map.cmp, &Global);
+        map.root = root;
+        map.length = length;
+        map.depth = depth;
+        map
+    }
+
+    /// Makes a new empty BTreeMap with the given B, that orders its keys with `cmp`.
+    pub fn with_b_and_comparator(b: usize, cmp: C) -> BTreeMap<K, V, C> {
+        BTreeMap::new_with(b, cmp, Global)
+    }
+}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> BTreeMap<K, V, C, A> {
+    /// Makes a new empty BTreeMap that orders its keys with `cmp`, and whose nodes get their
+    /// memory from `alloc`.
+    pub fn with_comparator_in(cmp: C, alloc: A) -> BTreeMap<K, V, C, A> {
+        BTreeMap::new_with(default_b::<K, V>(), cmp, alloc)
+    }
+
+    // Makes a new empty map from every setting at once, for the constructors above and the
+    // builder.
+    fn new_with(b: usize, cmp: C, alloc: A) -> BTreeMap<K, V, C, A> {
+        assert!(b > 1, "B must be greater than 1");
+        BTreeMap {
+            length: 0,
+            depth: 1,
+            root: Node::make_leaf_root(b, &alloc),
+            b,
+            pool: NodePool::new(NODE_POOL_LIMIT, alloc),
+            scratch: Scratch::new(),
+            cmp,
+        }
+    }
+
+    /// Returns the allocator the map's nodes get their memory from.
+    pub fn allocator(&self) -> &A {
+        self.pool.allocator()
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Walks the tree and measures its shape, for tuning B against a workload.
+    pub fn stats(&self) -> Stats {
+        let mut nodes = 0;
+        let mut leaves = 0;
+        let mut slots = 0;
+        let mut bytes = 0;
+        let mut pending = vec![&self.root];
+        while let Some(node) = pending.pop() {
+            nodes += 1;
+            slots += node.capacity();
+            bytes += node.capacity() * (mem::size_of::<K>() + mem::size_of::<V>());
+            if node.is_leaf() {
+                leaves += 1;
+            } else {
+                bytes += (node.capacity() + 1) * mem::size_of::<Node<K, V, A>>();
+                pending.extend((0..=node.len()).filter_map(|i| node.edge(i)));
+            }
+        }
+        let ratio = |a: usize, b: usize| if b == 0 { 0.0 } else { a as f64 / b as f64 };
+        Stats {
+            depth: self.depth,
+            nodes,
+            leaves,
+            fill_factor: ratio(self.length, slots),
+            bytes_per_entry: ratio(bytes, self.length),
+        }
+    }
+
+    /// Gets an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V, A> {
+        Iter::new(&self.root, self.length)
+    }
+
+    /// Gets an iterator over the entries of the map in chunks, as slices of keys and values
+    /// borrowed straight from the nodes, for code that wants to work through runs of entries at
+    /// once. See `LeafChunks` for how the entries are split up.
+    pub fn leaf_chunks(&self) -> LeafChunks<'_, K, V, A> {
+        LeafChunks::new(&self.root)
+    }
+
+    /// Turns the map into an iterator over its keys, in order.
+    pub fn into_keys(self) -> IntoKeys<K, V, A> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Turns the map into an iterator over its values, in key order.
+    pub fn into_values(self) -> IntoValues<K, V, A> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    /// Takes all the entries out of the map, in order. The map is emptied straight away and keeps
+    /// its `b` and spare nodes, so it can be refilled once the drain is gone; the new root comes
+    /// out of the node pool when there's one to spare.
+    pub fn drain(&mut self) -> Drain<'_, K, V, C, A> {
+        let root = self.pool.take(self.root.capacity(), false);
+        let root = mem::replace(&mut self.root, root);
+        let length = mem::replace(&mut self.length, 0);
+        self.depth = 1;
+        Drain {
+            inner: IntoIter::new(root, length),
+            marker: PhantomData,
+        }
+    }
+
+    /// Gets the entry with the smallest key, or None if the map is empty.
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, C, A>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut stack = PartialSearchStack::new(self);
+        while !stack.next().is_leaf() {
+            stack = match stack.push(0) {
+                Grew(new_stack) => new_stack,
+                Done(_) => unreachable!(),
+            };
+        }
+        Some(OccupiedEntry {
+            stack: stack.seal(0),
+        })
+    }
+
+    /// Gets the entry with the largest key, or None if the map is empty.
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, C, A>> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut stack = PartialSearchStack::new(self);
+        while !stack.next().is_leaf() {
+            let len = stack.next().len();
+            stack = match stack.push(len) {
+                Grew(new_stack) => new_stack,
+                Done(_) => unreachable!(),
+            };
+        }
+        let last = stack.next().len() - 1;
+        Some(OccupiedEntry {
+            stack: stack.seal(last),
+        })
+    }
+
+    /// Gets a cursor pointing at the first entry of the map.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, V, C, A> {
+        let mut cursor = CursorMut::new(self);
+        cursor.move_next();
+        cursor
+    }
+
+    /// Gets a cursor pointing at the last entry of the map.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, K, V, C, A> {
+        let mut cursor = CursorMut::new(self);
+        cursor.move_prev();
+        cursor
+    }
+
+    /// Gets a cursor pointing at `key`, or at the first entry after it if `key` isn't in the map.
+    pub fn cursor_mut(&mut self, key: &K) -> CursorMut<'_, K, V, C, A> {
+        let mut cursor = CursorMut::new(self);
+        cursor.seek(key);
+        cursor
+    }
+
+    /// Gets a cursor at the first entry at or above `bound`: the first key >= `k` for
+    /// `Included(k)`, the first key > `k` for `Excluded(k)`, and the first entry for `Unbounded`.
+    /// The cursor is at the ghost position if there's no such entry.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cursor = Cursor::new(self);
+        match bound {
+            Included(key) => {
+                cursor.seek(key);
+            }
+            Excluded(key) => {
+                if cursor.seek(key) {
+                    cursor.move_next();
+                }
+            }
+            Unbounded => cursor.move_next(),
+        }
+        cursor
+    }
+
+    /// Gets a cursor at the last entry at or below `bound`: the last key <= `k` for
+    /// `Included(k)`, the last key < `k` for `Excluded(k)`, and the last entry for `Unbounded`.
+    /// The cursor is at the ghost position if there's no such entry.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cursor = Cursor::new(self);
+        match bound {
+            Included(key) => {
+                if !cursor.seek(key) {
+                    cursor.move_prev();
+                }
+            }
+            Excluded(key) => {
+                cursor.seek(key);
+                cursor.move_prev();
+            }
+            Unbounded => cursor.move_prev(),
+        }
+        cursor
+    }
+
+    /// Like `lower_bound`, but the cursor can edit the map.
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cursor = CursorMut::new(self);
+        match bound {
+            Included(key) => {
+                cursor.seek(key);
+            }
+            Excluded(key) => {
+                if cursor.seek(key) {
+                    cursor.move_next();
+                }
+            }
+            Unbounded => cursor.move_next(),
+        }
+        cursor
+    }
+
+    /// Like `upper_bound`, but the cursor can edit the map.
+    pub fn upper_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cursor = CursorMut::new(self);
+        match bound {
+            Included(key) => {
+                if !cursor.seek(key) {
+                    cursor.move_prev();
+                }
+            }
+            Excluded(key) => {
+                cursor.seek(key);
+                cursor.move_prev();
+            }
+            Unbounded => cursor.move_prev(),
+        }
+        cursor
+    }
+
+    pub fn find<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cur_node = &self.root;
+        loop {
+            match cur_node.search(key, &self.cmp) {
+                Found(i) => return cur_node.val(i),
+                GoDown(i) => match cur_node.edge(i) {
+                    None => return None,
+                    Some(next_node) => {
+                        cur_node = next_node;
+                        continue;
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn find_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut cur_node = &mut self.root;
+        loop {
+            let node = cur_node;
+            match node.search(key, &self.cmp) {
+                Found(i) => return node.val_mut(i),
+                GoDown(i) => match node.edge_mut(i) {
+                    None => return None,
+                    Some(next_node) => cur_node = next_node,
+                },
+            }
+        }
+    }
+
+    /// Returns the key stored in the map along with its value. The stored key can differ from the
+    /// one looked up in anything that isn't part of the ordering.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut node = &self.root;
+        loop {
+            match node.search(key, &self.cmp) {
+                Found(i) => return node.key(i).zip(node.val(i)),
+                GoDown(i) => node = node.edge(i)?,
+            }
+        }
+    }
+
+    /// Returns the entry with the greatest key at or below `key`.
+    pub fn get_floor<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        // The best so far is the key just left of each edge we go down; anything we find further
+        // down is closer.
+        let mut best = None;
+        let mut node = &self.root;
+        loop {
+            match node.search(key, &self.cmp) {
+                Found(i) => return node.key(i).zip(node.val(i)),
+                GoDown(i) => {
+                    if i > 0 {
+                        best = node.key(i - 1).zip(node.val(i - 1));
+                    }
+                    match node.edge(i) {
+                        Some(next) => node = next,
+                        None => return best,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the entry with the smallest key at or above `key`.
+    pub fn get_ceiling<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut best = None;
+        let mut node = &self.root;
+        loop {
+            match node.search(key, &self.cmp) {
+                Found(i) => return node.key(i).zip(node.val(i)),
+                GoDown(i) => {
+                    if i < node.len() {
+                        best = node.key(i).zip(node.val(i));
+                    }
+                    match node.edge(i) {
+                        Some(next) => node = next,
+                        None => return best,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the `n`th smallest entry, counting from zero, in O(log n).
+    pub fn select(&self, mut n: usize) -> Option<(&K, &V)> {
+        if n >= self.length {
+            return None;
+        }
+        let mut node = &self.root;
+        'descend: loop {
+            for i in 0..=node.len() {
+                if let Some(edge) = node.edge(i) {
+                    if n < edge.size() {
+                        node = edge;
+                        continue 'descend;
+                    }
+                    n -= edge.size();
+                }
+                if n == 0 {
+                    return node.key(i).zip(node.val(i));
+                }
+                n -= 1;
+            }
+            unreachable!();
+        }
+    }
+
+    /// Returns the number of keys below `key`, in O(log n). This is also the position `key` has,
+    /// or would have, in the map's order.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.count_below(key, false)
+    }
+
+    /// Counts the keys in `range` in O(log n), without visiting them.
+    pub fn range_count<Q, R>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Included(key) => self.count_below(key, false),
+            Excluded(key) => self.count_below(key, true),
+            Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Included(key) => self.count_below(key, true),
+            Excluded(key) => self.count_below(key, false),
+            Unbounded => self.length,
+        };
+        end.saturating_sub(start)
+    }
+
+    // Count the keys below `key`, and `key` itself if it's there and `inclusive` is set.
+    fn count_below<Q>(&self, key: &Q, inclusive: bool) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut rank = 0;
+        let mut node = &self.root;
+        loop {
+            let (i, found) = match node.search(key, &self.cmp) {
+                Found(i) => (i, true),
+                GoDown(i) => (i, false),
+            };
+            // Everything left of the edge we'd take down is smaller.
+            rank += i
+                + (0..i)
+                    .filter_map(|j| node.edge(j))
+                    .map(Node::size)
+                    .sum::<usize>();
+            if found {
+                return rank + node.edge(i).map_or(0, Node::size) + inclusive as usize;
+            }
+            match node.edge(i) {
+                Some(edge) => node = edge,
+                None => return rank,
+            }
+        }
+    }
+
+    /// Looks up a batch of keys at once, and returns their values in the same order. The keys
+    /// must be sorted, though they may repeat. Rather than going down from the root once per key,
+    /// this makes a single pass down the tree that splits the batch up between the subtrees, so
+    /// keys that are close together share most of the work.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the keys aren't in ascending order.
+    pub fn get_many<Q>(&self, sorted_keys: &[Q]) -> Vec<Option<&V>>
+    where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        assert!(
+            sorted_keys
+                .windows(2)
+                .all(|pair| self.cmp.compare(&pair[0], &pair[1]) != Ordering::Greater),
+            "keys must be in ascending order"
+        );
+        let mut values = Vec::with_capacity(sorted_keys.len());
+        self.get_many_in(&self.root, sorted_keys, &mut values);
+        values
+    }
+
+    // Look up `keys` in the subtree under `node`, pushing their values onto `values`.
+    fn get_many_in<'a, Q>(
+        &self,
+        node: &'a Node<K, V, A>,
+        keys: &[Q],
+        values: &mut Vec<Option<&'a V>>,
+    ) where
+        K: Borrow<Q>,
+        C: Comparator<Q>,
+    {
+        if keys.is_empty() {
+            return;
+        }
+        let down = |edge: usize, run: &[Q], values: &mut Vec<Option<&'a V>>| match node.edge(edge) {
+            Some(child) => self.get_many_in(child, run, values),
+            None => values.extend(run.iter().map(|_| None)),
+        };
+        // Walk the node's keys alongside the batch. `i` is the edge the current key goes down if
+        // it isn't in this node, and `start` is where the run of keys going down it began.
+        let (mut i, mut start) = (0, 0);
+        for (q, key) in keys.iter().enumerate() {
+            let mut order = Ordering::Less;
+            while let Some(node_key) = node.key(i) {
+                order = self.cmp.compare(key, node_key.borrow());
+                if order != Ordering::Greater {
+                    break;
+                }
+                down(i, &keys[start..q], values);
+                i += 1;
+                start = q;
+                order = Ordering::Less;
+            }
+            if order == Ordering::Equal {
+                down(i, &keys[start..q], values);
+                values.push(node.val(i));
+                start = q + 1;
+            }
+        }
+        down(i, &keys[start..], values);
+    }
+
+    /// Gets mutable references to the values of several distinct keys at once. Returns None if any
+    /// key is missing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the same key is asked for twice.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        for (i, key) in keys.iter().enumerate() {
+            assert!(
+                keys[..i]
+                    .iter()
+                    .all(|other| self.cmp.compare(*other, *key) != Ordering::Equal),
+                "duplicate key in get_many_mut"
+            );
+        }
+        // Every lookup starts from the same raw pointer to the root rather than from `find_mut`, whose
+        // `&mut` to the node's values would invalidate pointers to values found earlier in that node.
+        let root: *mut Node<K, V, A> = &mut self.root;
+        let mut values = [ptr::null_mut(); N];
+        for (value, key) in values.iter_mut().zip(keys) {
+            *value = unsafe { Node::find_val_ptr(root, key, &self.cmp)? };
+        }
+        // The keys are distinct so these all point at different values, and nothing has touched
+        // the tree's shape since we looked them up.
+        Some(values.map(|value| unsafe { &mut *value }))
+    }
+
+    pub fn insert(&mut self, key: K, mut value: V) -> Option<V> {
+        // Insertion in a B-Tree is a bit complicated.
+        //
+        // First we do the same kind of search described in `find`. But we need to maintain a stack of
+        // all the nodes/edges in our search path. If we find a match for the key we're trying to
+        // insert, just swap the vals and return the old ones. However, when we bottom out in a leaf,
+        // we attempt to insert our key-value pair at the same location we would want to follow another
+        // edge.
+        //
+        // If the node has room, then this is done in the obvious way by shifting elements. However,
+        // if the node itself is full, we split node into two, and give its median key-value
+        // pair to its parent to insert the new node with. Of course, the parent may also be
+        // full, and insertion can propagate until we reach the root. If we reach the root, and
+        // it is *also* full, then we split the root and place the two nodes under a newly made root.
+        //
+        // Note that we subtly deviate from Open Data Structures in our implementation of split.
+        // ODS describes inserting into the node *regardless* of its capacity, and then
+        // splitting *afterwards* if it happens to be overfull. However, this is inefficient.
+        // Instead, we split beforehand, and then insert the key-value pair into the appropriate
+        // result node. This has two consequences:
+        //
+        // 1) While ODS produces a left node of size B-1, and a right node of size B,
+        // we may potentially reverse this. However, this shouldn't effect the analysis.
+        //
+        // 2) While ODS may potentially return the pair we *just* inserted after
+        // the split, we will never do this. Again, this shouldn't effect the analysis.
+        // let stack = VecDeque::new();
+        if self.root.is_leaf() && !self.root.is_full() {
+            return self.insert_into_root_leaf(key, value);
+        }
+        let mut stack = PartialSearchStack::new(self);
+        loop {
+            match stack.next().search(&key, stack.cmp()) {
+                Found(i) => unsafe {
+                    let next = stack.into_next();
+                    mem::swap(next.unsafe_val_mut(i), &mut value);
+                    return Some(value);
+                    // return Some(mem::replace(next.unsafe_val_mut(i), value));
+                },
+                GoDown(i) => {
+                    stack = match stack.push(i) {
+                        Done(new_stack) => {
+                            new_stack.insert(key, value);
+                            return None;
+                        }
+                        Grew(new_stack) => new_stack,
+                    };
+                }
+            }
+        }
+    }
+
+    // While the whole map fits in a leaf root with room to spare, nothing can split and there's no
+    // path to remember, so small maps skip the search stack altogether.
+    fn insert_into_root_leaf(&mut self, key: K, mut value: V) -> Option<V> {
+        match self.root.search(&key, &self.cmp) {
+            Found(i) => {
+                mem::swap(self.root.val_mut(i).unwrap(), &mut value);
+                Some(value)
+            }
+            GoDown(i) => {
+                self.root.insert_as_leaf(i, key, value, &mut self.pool);
+                self.length += 1;
+                None
+            }
+        }
+    }
+
+    /// Inserts a key-value pair only if the key isn't in the map yet, and returns a mutable
+    /// reference to the new value.
+    ///
+    /// If the key is already present, the map is left untouched and an error is returned holding
+    /// the occupied entry and the rejected value.
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<&mut V, OccupiedError<'_, K, V, C, A>> {
+        let mut stack = PartialSearchStack::new(self);
+        loop {
+            match stack.next().search(&key, stack.cmp()) {
+                Found(i) => {
+                    let entry = OccupiedEntry {
+                        stack: stack.seal(i),
+                    };
+                    return Err(OccupiedError { entry, value });
+                }
+                GoDown(i) => {
+                    stack = match stack.push(i) {
+                        Done(new_stack) => return Ok(new_stack.insert(key, value)),
+                        Grew(new_stack) => new_stack,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Gets the entry for a borrowed key. The key is only converted to an owned `K`, through
+    /// `From<&Q>`, if the entry is vacant and a value is inserted, so looking up keys that are
+    /// already present doesn't allocate.
+    ///
+    /// `K::from(key)` must compare equal to `key`, or the map will be out of order.
+    pub fn entry_ref<'a, 'b, Q>(&'a mut self, key: &'b Q) -> EntryRef<'a, 'b, K, Q, V, C, A>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        let mut stack = PartialSearchStack::new(self);
+        loop {
+            match stack.next().search(key, stack.cmp()) {
+                Found(i) => {
+                    return EntryRef::Occupied(OccupiedEntry {
+                        stack: stack.seal(i),
+                    });
+                }
+                GoDown(i) => {
+                    stack = match stack.push(i) {
+                        Done(stack) => return EntryRef::Vacant(VacantEntryRef { key, stack }),
+                        Grew(new_stack) => new_stack,
+                    };
+                }
+            }
+        }
+    }
+
+    /// Rebuilds the tree with every node packed full, and lets go of any spare nodes the map was
+    /// keeping for reuse. After deleting most of a map's entries, this gets its memory and
+    /// iteration speed back in line with its size.
+    ///
+    /// If the comparator panics, the map is left empty.
+    pub fn compact(&mut self) {
+        let root = mem::replace(
+            &mut self.root,
+            Node::make_leaf_root(self.b, self.pool.allocator()),
+        );
+        let length = mem::replace(&mut self.length, 0);
+        self.depth = 1;
+        self.pool.clear();
+        let entries = IntoIter::new(root, length);
+        let (root, length, depth) =
+            Node::from_sorted_iter(self.b, entries, &self.cmp, self.pool.allocator());
+        self.root = root;
+        self.length = length;
+        self.depth = depth;
+    }
+
+    /// Moves every entry of `other` into this map. Where both maps have a key, `f` gets the key and
+    /// both values, ours first, and returns the value to keep.
+    ///
+    /// This walks both maps in order once and rebuilds the tree packed, rather than inserting
+    /// entries one at a time. If `f` panics, the map is left empty.
+    pub fn merge_with<F>(&mut self, other: BTreeMap<K, V, C, A>, mut f: F)
+    where
+        F: FnMut(&K, V, V) -> V,
+    {
+        let root = mem::replace(
+            &mut self.root,
+            Node::make_leaf_root(self.b, self.pool.allocator()),
+        );
+        let length = mem::replace(&mut self.length, 0);
+        self.depth = 1;
+        let mut ours = IntoIter::new(root, length).peekable();
+        let mut theirs = other.into_iter().peekable();
+
+        let cmp = &self.cmp;
+        let merged = from_fn(|| {
+            let order = match (ours.peek(), theirs.peek()) {
+                (Some((a, _)), Some((b, _))) => cmp.compare(a, b),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => return None,
+            };
+            match order {
+                Ordering::Less => ours.next(),
+                Ordering::Greater => theirs.next(),
+                Ordering::Equal => {
+                    let (key, a) = ours.next()?;
+                    let (_, b) = theirs.next()?;
+                    let value = f(&key, a, b);
+                    Some((key, value))
+                }
+            }
+        });
+        let (root, length, depth) =
+            Node::from_sorted_iter(self.b, merged, cmp, self.pool.allocator());
+        self.root = root;
+        self.length = length;
+        self.depth = depth;
+    }
+
+    // Deletion is the most complicated operation for a B-Tree.
+    //
+    // First we do the same kind of search described in
+    // `find`. But we need to maintain a stack of all the nodes/edges in our search path.
+    // If we don't find the key, then we just return `None` and do nothing. If we do find the
+    // key, we perform two operations: remove the item, and then possibly handle underflow.
+    //
+    // # removing the item
+    //      If the node is a leaf, we just remove the item, and shift
+    //      any items after it back to fill the hole.
+    //
+    //      If the node is an internal node, we *swap* the item with the smallest item in
+    //      in its right subtree (which must reside in a leaf), and then revert to the leaf
+    //      case
+    //
+    // # handling underflow
+    //      After removing an item, there may be too few items in the node. We want nodes
+    //      to be mostly full for efficiency, although we make an exception for the root, which
+    //      may have as few as one item. If this is the case, we may first try to steal
+    //      an item from our left or right neighbour.
+    //
+    //      To steal from the left (right) neighbour,
+    //      we take the largest (smallest) item and child from it. We then swap the taken item
+    //      with the item in their mutual parent that separates them, and then insert the
+    //      parent's item and the taken child into the first (last) index of the underflowed node.
+    //
+    //      However, stealing has the possibility of underflowing our neighbour. If this is the
+    //      case, we instead *merge* with our neighbour. This of course reduces the number of
+    //      children in the parent. Therefore, we also steal the item that separates the now
+    //      merged nodes, and insert it into the merged node.
+    //
+    //      Merging may cause the parent to underflow. If this is the case, then we must repeat
+    //      the underflow handling process on the parent. If merging merges the last two children
+    //      of the root, then we replace the root with the merged node.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Removes every entry with a key in `range`, and returns how many there were.
+    ///
+    /// Rather than removing the entries one at a time, this cuts the tree on either side of the
+    /// range and joins what's left back together, so it takes O(log n) time plus the time to drop
+    /// the removed entries. If the comparator panics, the map is left empty.
+    pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+        R: RangeBounds<Q>,
+    {
+        let cmp = &self.cmp;
+        let past_end = |key: &K| match range.end_bound() {
+            Included(end) => cmp.compare(key.borrow(), end) == Ordering::Greater,
+            Excluded(end) => cmp.compare(key.borrow(), end) != Ordering::Less,
+            Unbounded => false,
+        };
+        let from_start = |key: &K| match range.start_bound() {
+            Included(start) => cmp.compare(key.borrow(), start) != Ordering::Less,
+            Excluded(start) => cmp.compare(key.borrow(), start) == Ordering::Greater,
+            Unbounded => true,
+        };
+        // The cuts call the comparator as they go, so make them on a tree taken out of the map. A
+        // panic partway through then leaves behind an empty map rather than a half-cut tree.
+        let placeholder = self.pool.take(self.root.capacity(), false);
+        let mut left = mem::replace(&mut self.root, placeholder);
+        let length = mem::replace(&mut self.length, 0);
+        let depth = mem::replace(&mut self.depth, 1);
+        let mut right = left.split_off_by(&past_end, &mut self.pool);
+        let removed = left.split_off_by(&from_start, &mut self.pool);
+
+        let (mut left_depth, mut right_depth) = (depth, depth);
+        Node::fix_right_border(&mut left, &mut left_depth, &mut self.pool);
+        Node::fix_left_border(&mut right, &mut right_depth, &mut self.pool);
+        let (root, depth) = Node::join(left, left_depth, right, right_depth, &mut self.pool);
+        let placeholder = mem::replace(&mut self.root, root);
+        self.pool.put(placeholder);
+        self.depth = depth;
+        self.length = length - removed.size();
+        // Only drop the removed entries once the map is whole again.
+        removed.size()
+    }
+
+    /// Removes `key` from the map, and returns the stored key along with its value.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        if self.root.is_leaf() {
+            return match self.root.search(key, &self.cmp) {
+                Found(i) => {
+                    self.length -= 1;
+                    Some(self.root.remove_as_leaf(i))
+                }
+                GoDown(_) => None,
+            };
+        }
+        let mut stack = PartialSearchStack::new(self);
+        loop {
+            match stack.next().search(key, stack.cmp()) {
+                Found(i) => {
+                    // exact match
+                    return Some(stack.seal(i).remove());
+                }
+                GoDown(i) => {
+                    stack = match stack.push(i) {
+                        Grew(new_stack) => new_stack,
+                        Done(new_stack) => {
+                            new_stack.discard();
+                            return None;
+                        }
+                    }
+                }
+            };
+        }
+    }
+}
+
+impl<K, V, C, A: Allocator> IntoIterator for BTreeMap<K, V, C, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    fn into_iter(self) -> IntoIter<K, V, A> {
+        IntoIter::new(self.root, self.length)
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>, A: Allocator + Clone> IntoIterator for &'a BTreeMap<K, V, C, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, A>;
+
+    fn into_iter(self) -> Iter<'a, K, V, A> {
+        self.iter()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C: Comparator<K>, A: Allocator + Clone> fmt::Debug
+    for BTreeMap<K, V, C, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: PartialEq, V: PartialEq, C: Comparator<K>, A: Allocator + Clone> PartialEq
+    for BTreeMap<K, V, C, A>
+{
+    fn eq(&self, other: &BTreeMap<K, V, C, A>) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, C: Comparator<K>, A: Allocator + Clone> Eq for BTreeMap<K, V, C, A> {}
+
+impl<K: PartialOrd, V: PartialOrd, C: Comparator<K>, A: Allocator + Clone> PartialOrd
+    for BTreeMap<K, V, C, A>
+{
+    fn partial_cmp(&self, other: &BTreeMap<K, V, C, A>) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord, C: Comparator<K>, A: Allocator + Clone> Ord for BTreeMap<K, V, C, A> {
+    fn cmp(&self, other: &BTreeMap<K, V, C, A>) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Hash, V: Hash, C: Comparator<K>, A: Allocator + Clone> Hash for BTreeMap<K, V, C, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Prefix the length so that the entries of nested maps can't run together.
+        state.write_usize(self.len());
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> Index<&K> for BTreeMap<K, V, C, A> {
+    type Output = V;
+
+    /// Returns a reference to the value for `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the map.
+    fn index(&self, key: &K) -> &V {
+        self.find(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> IndexMut<&K> for BTreeMap<K, V, C, A> {
+    /// Returns a mutable reference to the value for `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the map.
+    fn index_mut(&mut self, key: &K) -> &mut V {
+        self.find_mut(key).expect("no entry found for key")
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V, C, A> arbitrary::Arbitrary<'a> for BTreeMap<K, V, C, A>
+where
+    K: arbitrary::Arbitrary<'a>,
+    V: arbitrary::Arbitrary<'a>,
+    C: Comparator<K> + Default,
+    A: Allocator + Clone + Default,
+{
+    /// Builds a map with an arbitrary B as well as arbitrary entries, so that fuzzing reaches
+    /// deep trees without needing huge inputs.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut map = BTreeMap::new_with(u.int_in_range(2..=8)?, C::default(), A::default());
+        for entry in u.arbitrary_iter()? {
+            let (key, value) = entry?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+
+    fn arbitrary_take_rest(mut u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut map = BTreeMap::new_with(u.int_in_range(2..=8)?, C::default(), A::default());
+        for entry in u.arbitrary_take_rest_iter()? {
+            let (key, value) = entry?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<K, V, C, A> FromIterator<(K, V)> for BTreeMap<K, V, C, A>
+where
+    C: Comparator<K> + Default,
+    A: Allocator + Clone + Default,
+{
+    /// Builds a map from an iterator of pairs. Later duplicates overwrite earlier ones.
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> BTreeMap<K, V, C, A> {
+        let mut map = BTreeMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for BTreeMap<K, V> {
+    /// Builds a map from an array of pairs, so small maps can be written out literally. Later
+    /// duplicates overwrite earlier ones.
+    fn from(entries: [(K, V); N]) -> BTreeMap<K, V> {
+        entries.into_iter().collect()
+    }
+}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> Extend<(K, V)> for BTreeMap<K, V, C, A> {
+    /// Inserts every pair, later duplicates overwriting earlier ones.
+    ///
+    /// When the map starts out empty and the iterator says it has entries, the run of them at the
+    /// front that comes in ascending order is gathered up, with room reserved from the size hint,
+    /// and packed straight into nodes the way `from_sorted_iter` does, rather than inserted one at
+    /// a time. Whatever follows the run is inserted as usual.
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let mut iter = iter.into_iter();
+        let mut rest = None;
+        let (lower, _) = iter.size_hint();
+        if self.is_empty() && lower > 0 {
+            let mut sorted: Vec<(K, V)> = Vec::with_capacity(lower);
+            for (key, value) in iter.by_ref() {
+                if let Some((last, _)) = sorted.last() {
+                    if self.cmp.compare(last, &key) == Ordering::Greater {
+                        rest = Some((key, value));
+                        break;
+                    }
+                }
+                sorted.push((key, value));
+            }
+            let (root, length, depth) =
+                Node::from_sorted_iter(self.b, sorted, &self.cmp, self.pool.allocator());
+            self.root = root;
+            self.length = length;
+            self.depth = depth;
+        }
+        for (key, value) in rest.into_iter().chain(iter) {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Copy, V: Copy, C: Comparator<K>, A: Allocator + Clone> Extend<(&'a K, &'a V)>
+    for BTreeMap<K, V, C, A>
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(&key, &value)| (key, value)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Lcg;
+
+    #[test]
+    fn small_test1() {
+        let mut bt = BTreeMap::new();
+        (0..100).for_each(|i| {
+            bt.insert(i, i);
+        });
+
+        (0..100).for_each(|i| {
+            assert_eq!(bt.find(&i), Some(&i));
+        });
+    }
+
+    #[test]
+    fn test_basic_large() {
+        let mut map = BTreeMap::new();
+        let size = 10000;
+        assert_eq!(map.len(), 0);
+
+        for i in 0..size {
+            assert_eq!(map.insert(i, 10 * i), None);
+            assert_eq!(map.len(), i + 1);
+        }
+
+        for i in 0..size {
+            assert_eq!(map.find(&i).unwrap(), &(i * 10));
+        }
+
+        for i in size..size * 2 {
+            assert_eq!(map.find(&i), None);
+        }
+
+        for i in 0..size {
+            assert_eq!(map.insert(i, 100 * i), Some(10 * i));
+            assert_eq!(map.len(), size);
+        }
+
+        for i in 0..size {
+            assert_eq!(map.find(&i).unwrap(), &(i * 100));
+        }
+
+        for i in 0..size / 2 {
+            assert_eq!(map.remove(&(i * 2)), Some(i * 200));
+            assert_eq!(map.len(), size - i - 1);
+        }
+
+        for i in 0..size / 2 {
+            assert_eq!(map.find(&(2 * i)), None);
+            assert_eq!(map.find(&(2 * i + 1)).unwrap(), &(i * 200 + 100));
+        }
+
+        for i in 0..size / 2 {
+            assert_eq!(map.remove(&(2 * i)), None);
+            assert_eq!(map.remove(&(2 * i + 1)), Some(i * 200 + 100));
+            assert_eq!(map.len(), size / 2 - i - 1);
+        }
+    }
+
+    #[test]
+    fn test_basic_small() {
+        let mut map = BTreeMap::new();
+        assert_eq!(map.remove(&1), None);
+        assert_eq!(map.find(&1), None);
+        assert_eq!(map.insert(1, 1), None);
+        assert_eq!(map.find(&1), Some(&1));
+        assert_eq!(map.insert(1, 2), Some(1));
+        assert_eq!(map.find(&1), Some(&2));
+        assert_eq!(map.insert(2, 4), None);
+        assert_eq!(map.find(&2), Some(&4));
+        assert_eq!(map.remove(&1), Some(2));
+        assert_eq!(map.remove(&2), Some(4));
+        assert_eq!(map.remove(&1), None);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let map: BTreeMap<_, _> = (0..100).map(|i| (i % 10, i)).collect();
+        assert_eq!(map.len(), 10);
+        for i in 0..10 {
+            assert_eq!(map.find(&i), Some(&(90 + i)));
+        }
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut map: BTreeMap<_, _> = (0..50).map(|i| (i, i)).collect();
+        map.extend((25..75).map(|i| (i, 2 * i)));
+        map.extend([(&100, &1), (&0, &7)]);
+        assert_eq!(map.len(), 76);
+        assert_eq!(map.find(&0), Some(&7));
+        assert_eq!(map.find(&24), Some(&24));
+        assert_eq!(map.find(&25), Some(&50));
+        assert_eq!(map.find(&100), Some(&1));
+    }
+
+    #[test]
+    fn test_extend_empty() {
+        // An ascending run with a repeat in it, which gets packed, then keys out of order after it.
+        let mut map = BTreeMap::with_b(3);
+        let run = (0..500).map(|i| (i, i)).chain([(499, -1)]);
+        map.extend(run.chain((0..1000).rev().map(|i| (i, 2 * i))));
+        map.check_invariants();
+        assert_eq!(map.len(), 1000);
+        assert!(map
+            .iter()
+            .map(|(&k, &v)| (k, v))
+            .eq((0..1000).map(|i| (i, 2 * i))));
+
+        // No size hint to go on.
+        let mut map = BTreeMap::new();
+        map.extend((0..100).filter(|i| i % 2 == 0).map(|i| (i, i)));
+        map.check_invariants();
+        assert_eq!(map.len(), 50);
+        for i in 100..200 {
+            map.insert(i, i);
+        }
+        map.check_invariants();
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut map = BTreeMap::with_b(2);
+        for i in (0..1000).rev() {
+            map.insert(i, i * 2);
+        }
+        for i in (0..1000).step_by(3) {
+            map.remove(&i);
+        }
+        let expected: Vec<_> = (0..1000).filter(|i| i % 3 != 0).collect();
+        assert_eq!(map.iter().size_hint(), (expected.len(), Some(expected.len())));
+        let mut count = 0;
+        for ((k, v), e) in map.iter().zip(&expected) {
+            assert_eq!((k, *v), (e, e * 2));
+            count += 1;
+        }
+        assert_eq!(count, expected.len());
+        assert_eq!(BTreeMap::<i32, i32>::new().iter().next(), None);
+    }
+
+    #[test]
+    fn test_debug() {
+        let map: BTreeMap<_, _> = [(3, "c"), (1, "a"), (2, "b")].into_iter().collect();
+        assert_eq!(format!("{:?}", map), r#"{1: "a", 2: "b", 3: "c"}"#);
+        assert_eq!(format!("{:?}", BTreeMap::<i32, i32>::new()), "{}");
+    }
+
+    #[test]
+    fn test_eq_ord_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash<T: Hash>(t: &T) -> u64 {
+            let mut s = DefaultHasher::new();
+            t.hash(&mut s);
+            s.finish()
+        }
+
+        let mut a = BTreeMap::new();
+        let mut b = BTreeMap::with_b(2);
+        for i in 0..100 {
+            a.insert(i, i);
+            b.insert(99 - i, 99 - i);
+        }
+        assert!(a == b);
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        assert_eq!(hash(&a), hash(&b));
+
+        b.insert(50, 0);
+        assert!(a != b);
+        assert!(b < a);
+        b.remove(&99);
+        b.insert(50, 50);
+        assert!(b < a);
+        assert_ne!(hash(&a), hash(&b));
+
+        let mut outer = BTreeMap::new();
+        outer.insert(a, "a");
+        outer.insert(b, "b");
+        assert_eq!(outer.len(), 2);
+    }
+
+    #[test]
+    fn test_index() {
+        let mut map: BTreeMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        for i in 0..100 {
+            map[&i] *= 3;
+        }
+        for i in 0..100 {
+            assert_eq!(map[&i], i * 3);
+        }
+        assert_eq!(map.find_mut(&100), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "no entry found for key")]
+    fn test_index_missing() {
+        let map: BTreeMap<i32, i32> = BTreeMap::new();
+        let _ = map[&0];
+    }
+
+    #[test]
+    fn test_cursor_walk() {
+        let mut map: BTreeMap<_, _> = (0..200).map(|i| (i * 2, i)).collect();
+        let mut cursor = map.cursor_front_mut();
+        for i in 0..200 {
+            assert_eq!(cursor.key(), Some(&(i * 2)));
+            *cursor.value_mut().unwrap() += 1;
+            cursor.move_next();
+        }
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        for i in (0..200).rev() {
+            assert_eq!(cursor.key(), Some(&(i * 2)));
+            assert_eq!(cursor.value(), Some(&(i + 1)));
+            cursor.move_prev();
+        }
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.key(), Some(&398));
+
+        assert!(!cursor.seek(&101));
+        assert_eq!(cursor.key(), Some(&102));
+        assert_eq!(cursor.peek_prev(), Some((&100, &51)));
+        assert_eq!(cursor.peek_next(), Some((&104, &53)));
+        assert!(cursor.seek(&0));
+        assert_eq!(cursor.peek_prev(), None);
+        assert!(!cursor.seek(&1000));
+        assert_eq!(cursor.key(), None);
+        assert_eq!(cursor.peek_next(), Some((&0, &1)));
+        assert_eq!(cursor.peek_prev(), Some((&398, &200)));
+
+        let mut empty: BTreeMap<i32, i32> = BTreeMap::new();
+        let mut cursor = empty.cursor_front_mut();
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.peek_next(), None);
+        assert_eq!(cursor.peek_prev(), None);
+    }
+
+    #[test]
+    fn test_cursor_edit() {
+        let mut map = BTreeMap::with_b(2);
+        let mut cursor = map.cursor_front_mut();
+        for i in 0..100 {
+            cursor.insert_before(i * 4, i);
+        }
+        let mut cursor = map.cursor_mut(&200);
+        for i in 50..100 {
+            assert_eq!(cursor.key(), Some(&(i * 4)));
+            cursor.insert_after(i * 4 + 2, 0);
+            cursor.insert_before(i * 4 - 1, 0);
+            cursor.move_next();
+            cursor.move_next();
+        }
+        assert_eq!(map.len(), 200);
+
+        let mut cursor = map.cursor_front_mut();
+        let mut removed = 0;
+        while let Some(&key) = cursor.key() {
+            if key % 4 == 0 {
+                cursor.move_next();
+            } else {
+                assert_eq!(cursor.remove_current(), Some((key, 0)));
+                removed += 1;
+            }
+        }
+        assert_eq!(removed, 100);
+        assert!(map.iter().map(|(k, _)| *k).eq((0..100).map(|i| i * 4)));
+    }
+
+    #[test]
+    #[should_panic(expected = "key must be ordered below the next entry")]
+    fn test_cursor_insert_unordered() {
+        let mut map: BTreeMap<_, _> = (0..10).map(|i| (i, i)).collect();
+        map.cursor_mut(&4).insert_after(6, 6);
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let mut map = BTreeMap::new();
+        for i in 0..100 {
+            *map.try_insert(i, i).unwrap() += 1;
+        }
+        let mut err = map.try_insert(42, 0).unwrap_err();
+        assert_eq!(err.value, 0);
+        assert_eq!(err.entry.key(), &42);
+        assert_eq!(err.entry.insert(7), 43);
+        assert_eq!(
+            err.to_string(),
+            "failed to insert 0, key 42 already exists with value 7"
+        );
+        assert_eq!(map.find(&42), Some(&7));
+
+        let err = map.try_insert(10, 0).unwrap_err();
+        assert_eq!(err.entry.remove(), 11);
+        assert_eq!(map.find(&10), None);
+        assert_eq!(map.len(), 99);
+    }
+
+    #[test]
+    fn test_from_sorted_iter() {
+        for size in [0, 1, 5, 11, 12, 100, 1000, 5000] {
+            let map = BTreeMap::from_sorted_iter((0..size).map(|i| (i, i * 2)));
+            assert_eq!(map.len(), size);
+            assert!(map.iter().map(|(&k, &v)| (k, v)).eq((0..size).map(|i| (i, i * 2))));
+
+            // Removal relies on every node meeting the minimum load, so drain the map to check.
+            let mut map = map;
+            for i in (1..size).step_by(2).rev().chain((0..size).step_by(2)) {
+                assert_eq!(map.remove(&i), Some(i * 2));
+                assert_eq!(map.find(&i), None);
+            }
+            assert!(map.is_empty());
+        }
+
+        let map = BTreeMap::from_sorted_iter([(1, 'a'), (1, 'b'), (2, 'c'), (2, 'd'), (3, 'e')]);
+        assert_eq!(format!("{:?}", map), "{1: 'b', 2: 'd', 3: 'e'}");
+    }
+
+    #[test]
+    #[should_panic(expected = "keys must be in ascending order")]
+    fn test_from_sorted_iter_unsorted() {
+        BTreeMap::from_sorted_iter([(1, 1), (3, 3), (2, 2)]);
+    }
+
+    #[test]
+    fn test_wide_nodes() {
+        // Large B makes nodes long enough to be searched with binary search.
+        let mut map = BTreeMap::with_b(32);
+        for i in (0..2000).rev() {
+            assert_eq!(map.insert(i * 2, i), None);
+        }
+        for i in 0..2000 {
+            assert_eq!(map.find(&(i * 2)), Some(&i));
+            assert_eq!(map.find(&(i * 2 + 1)), None);
+        }
+        for i in 0..2000 {
+            assert_eq!(map.remove(&(i * 2)), Some(i));
+        }
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_churn() {
+        // Alternate growing and shrinking so that merged nodes get reused by later splits.
+        let mut map = BTreeMap::with_b(3);
+        for round in 0..10 {
+            for i in 0..500 {
+                map.insert(i * 10 + round, i);
+            }
+            for i in 0..500 {
+                if i % 7 != 0 {
+                    assert_eq!(map.remove(&(i * 10 + round)), Some(i));
+                }
+            }
+        }
+        let expected: Vec<_> = (0..10)
+            .flat_map(|round| (0..500).step_by(7).map(move |i| i * 10 + round))
+            .collect();
+        let mut expected = expected;
+        expected.sort();
+        assert!(map.iter().map(|(k, _)| *k).eq(expected));
+    }
+
+    #[test]
+    fn test_iter_double_ended() {
+        let map: BTreeMap<_, _> = (0..1000).map(|i| (i, i)).collect();
+        assert!(map.iter().rev().map(|(k, _)| *k).eq((0..1000).rev()));
+        assert_eq!(map.iter().rposition(|(k, _)| *k == 10), Some(10));
+
+        // Meet in the middle from both ends, at every possible split point of a small map.
+        for size in 0..40 {
+            let map: BTreeMap<_, _> = (0..size).map(|i| (i, i)).collect();
+            for front in 0..=size {
+                let mut iter = map.iter();
+                let mut seen: Vec<_> = iter.by_ref().take(front).map(|(k, _)| *k).collect();
+                assert_eq!(iter.len(), size - front);
+                let back: Vec<_> = iter.by_ref().rev().map(|(k, _)| *k).collect();
+                assert_eq!(iter.next(), None);
+                assert_eq!(iter.next_back(), None);
+                seen.extend(back.into_iter().rev());
+                assert!(seen.into_iter().eq(0..size));
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_iter() {
+        use std::rc::Rc;
+
+        let map: BTreeMap<_, _> = (0..500).map(|i| (i, i.to_string())).collect();
+        assert!(map.into_iter().eq((0..500).map(|i| (i, i.to_string()))));
+        let map: BTreeMap<_, _> = (0..500).rev().map(|i| (i, ())).collect();
+        assert!(map.into_keys().rev().eq((0..500).rev()));
+
+        let map = BTreeMap::from_sorted_iter((0..500).map(|i| (i, i * 2)));
+        let mut values = map.into_values();
+        assert_eq!(values.len(), 500);
+        assert_eq!(values.next(), Some(0));
+        assert_eq!(values.next_back(), Some(998));
+        assert_eq!(values.len(), 498);
+        assert!(values.eq((1..499).map(|i| i * 2)));
+
+        // Whatever isn't consumed is dropped along with the iterator.
+        let tracker = Rc::new(());
+        let map: BTreeMap<_, _> = (0..300).map(|i| (i, Rc::clone(&tracker))).collect();
+        let mut iter = map.into_iter();
+        iter.by_ref().take(100).for_each(drop);
+        iter.next_back();
+        assert_eq!(Rc::strong_count(&tracker), 200);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&tracker), 1);
+    }
+
+    #[test]
+    fn test_drain() {
+        use std::rc::Rc;
+
+        let mut map = BTreeMap::with_b(3);
+        for round in 0..3 {
+            map.extend((0..200).map(|i| (i, round)));
+            let mut drain = map.drain();
+            assert_eq!(drain.len(), 200);
+            assert_eq!(drain.next_back(), Some((199, round)));
+            assert!(drain.take(50).eq((0..50).map(|i| (i, round))));
+            assert!(map.is_empty());
+            assert_eq!(map.iter().next(), None);
+        }
+
+        let tracker = Rc::new(());
+        let mut map: BTreeMap<_, _> = (0..100).map(|i| (i, Rc::clone(&tracker))).collect();
+        map.drain().next();
+        assert_eq!(Rc::strong_count(&tracker), 1);
+        map.insert(5, Rc::clone(&tracker));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_get_key_value() {
+        let mut map = BTreeMap::with_b(2);
+        for i in 0..100 {
+            map.insert(format!("{:03}", i), i);
+        }
+        let (key, value) = map.get_key_value("042").unwrap();
+        assert_eq!((key.as_str(), *value), ("042", 42));
+        assert_eq!(map.get_key_value("100"), None);
+        assert_eq!(map.find("007"), Some(&7));
+        *map.find_mut("007").unwrap() += 1;
+        assert_eq!(map.remove("007"), Some(8));
+        assert_eq!(map.len(), 99);
+    }
+
+    #[test]
+    fn test_remove_entry() {
+        let mut map = BTreeMap::with_b(2);
+        for i in 0..100 {
+            map.insert(Box::new(i), i * 10);
+        }
+        for i in (0..100).rev() {
+            let (key, value) = map.remove_entry(&i).unwrap();
+            assert_eq!((*key, value), (i, i * 10));
+            assert_eq!(map.remove_entry(&i), None);
+        }
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut map: BTreeMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let [a, b, c] = map.get_many_mut([&10, &90, &50]).unwrap();
+        mem::swap(a, b);
+        *c += 1;
+        assert_eq!((map[&10], map[&90], map[&50]), (90, 10, 51));
+        assert!(map.get_many_mut([&1, &100]).is_none());
+        assert!(map.get_many_mut::<i32, 0>([]).is_some());
+    }
+
+    #[test]
+    fn test_get_many_mut_same_leaf() {
+        // Values next to each other in one node, where taking the second mustn't invalidate the first.
+        let mut map = BTreeMap::from_sorted_iter((0..4).map(|i| (i, i)));
+        let [a, b] = map.get_many_mut([&1, &2]).unwrap();
+        mem::swap(a, b);
+        assert_eq!((map[&1], map[&2]), (2, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate key")]
+    fn test_get_many_mut_duplicate() {
+        let mut map: BTreeMap<_, _> = (0..10).map(|i| (i, i)).collect();
+        map.get_many_mut([&3, &4, &3]);
+    }
+
+    #[test]
+    fn test_first_last_entry() {
+        let mut map: BTreeMap<_, _> = (0..200).map(|i| (i, i)).collect();
+        *map.first_entry().unwrap().get_mut() += 1000;
+        assert_eq!(map[&0], 1000);
+        assert_eq!(map.last_entry().unwrap().key(), &199);
+
+        // Pop from both ends until they meet.
+        let mut popped = Vec::new();
+        while let Some(entry) = map.first_entry() {
+            popped.push(entry.remove_entry().0);
+            if let Some(entry) = map.last_entry() {
+                popped.push(entry.remove_entry().0);
+            }
+        }
+        assert_eq!(popped.len(), 200);
+        assert_eq!(popped[..4], [0, 199, 1, 198]);
+        assert!(map.is_empty() && map.last_entry().is_none());
+    }
+
+    #[test]
+    fn test_bounds() {
+        let map: BTreeMap<_, _> = (0..100).map(|i| (i * 10, i)).collect();
+        assert_eq!(map.lower_bound(Included(&250)).key(), Some(&250));
+        assert_eq!(map.lower_bound(Included(&251)).key(), Some(&260));
+        assert_eq!(map.lower_bound(Excluded(&250)).key(), Some(&260));
+        assert_eq!(map.lower_bound(Excluded(&990)).key(), None);
+        assert_eq!(map.lower_bound::<i32>(Unbounded).key(), Some(&0));
+        assert_eq!(map.upper_bound(Included(&250)).key(), Some(&250));
+        assert_eq!(map.upper_bound(Included(&249)).key(), Some(&240));
+        assert_eq!(map.upper_bound(Excluded(&250)).key(), Some(&240));
+        assert_eq!(map.upper_bound(Excluded(&0)).key(), None);
+        assert_eq!(map.upper_bound(Included(&5000)).key(), Some(&990));
+        assert_eq!(map.upper_bound::<i32>(Unbounded).key(), Some(&990));
+
+        // Scan a few entries either way from the bound.
+        let mut cursor = map.lower_bound(Excluded(&500));
+        assert_eq!(cursor.peek_prev(), Some((&500, &50)));
+        let mut keys = Vec::new();
+        for _ in 0..3 {
+            keys.push(*cursor.key().unwrap());
+            cursor.move_next();
+        }
+        assert_eq!(keys, [510, 520, 530]);
+        let mut cursor = map.upper_bound(Included(&15));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.value(), Some(&99));
+
+        let mut map = map;
+        let mut cursor = map.upper_bound_mut(Excluded(&500));
+        assert_eq!(cursor.remove_current(), Some((490, 49)));
+        assert_eq!(cursor.key(), Some(&500));
+        let mut cursor = map.lower_bound_mut(Included(&995));
+        cursor.insert_before(995, 0);
+        assert_eq!(map.len(), 100);
+    }
+
+    #[test]
+    fn test_floor_ceiling() {
+        let map: BTreeMap<_, _> = (1..=300).map(|i| (i * 2, i)).collect();
+        for key in 0..=602 {
+            let floor = (key >= 2).then(|| key.min(600) / 2 * 2);
+            let ceiling = (key <= 600).then(|| (key.max(2) + 1) / 2 * 2);
+            assert_eq!(map.get_floor(&key).map(|(k, _)| *k), floor);
+            assert_eq!(map.get_ceiling(&key).map(|(k, _)| *k), ceiling);
+        }
+        assert_eq!(map.get_floor(&7), Some((&6, &3)));
+        assert_eq!(BTreeMap::<i32, ()>::new().get_ceiling(&0), None);
+    }
+
+    #[test]
+    fn test_select_rank() {
+        let mut map = BTreeMap::with_b(2);
+        let mut keys = Vec::new();
+        for i in 0..600 {
+            let key = (i * 7919) % 1000;
+            map.insert(key, i);
+            keys.push(key);
+            if i % 3 == 0 {
+                let gone = keys.swap_remove((i * 31) % keys.len());
+                map.remove(&gone);
+            }
+        }
+        keys.sort();
+        assert_eq!(map.root.size(), keys.len());
+        for (n, key) in keys.iter().enumerate() {
+            assert_eq!(map.select(n).map(|(k, _)| k), Some(key));
+            assert_eq!(map.rank(key), n);
+            assert_eq!(map.rank(&(key + 1)), n + 1);
+        }
+        assert_eq!(map.select(keys.len()), None);
+
+        let map = BTreeMap::from_sorted_iter((0..1000).map(|i| (i * 2, ())));
+        assert_eq!(map.root.size(), 1000);
+        assert_eq!(map.select(321), Some((&642, &())));
+        assert_eq!(map.rank(&643), 322);
+        assert_eq!(map.rank(&-1), 0);
+        assert_eq!(map.rank(&5000), 1000);
+    }
+
+    #[test]
+    fn test_range_count() {
+        let mut map = BTreeMap::with_b(3);
+        for i in 0..500 {
+            map.insert(i * 2, ());
+        }
+        assert_eq!(map.range_count(..), 500);
+        assert_eq!(map.range_count(10..20), 5);
+        assert_eq!(map.range_count(10..=20), 6);
+        assert_eq!(map.range_count(11..=19), 4);
+        assert_eq!(map.range_count((Excluded(10), Included(20))), 5);
+        assert_eq!(map.range_count(..7), 4);
+        assert_eq!(map.range_count(990..), 5);
+        assert_eq!(map.range_count(2000..), 0);
+        assert_eq!(map.range_count((Included(20), Excluded(10))), 0);
+        for i in (0..1000).step_by(4) {
+            map.remove(&i);
+        }
+        assert_eq!(map.range_count(0..100), 25);
+    }
+
+    #[test]
+    fn test_comparator() {
+        // Reversed order, without a wrapper type.
+        let mut map = BTreeMap::with_b_and_comparator(2, |a: &i32, b: &i32| b.cmp(a));
+        map.extend((0..100).map(|i| (i, i)));
+        assert!(map.iter().map(|(k, _)| *k).eq((0..100).rev()));
+        assert_eq!(map.get_floor(&50), Some((&50, &50)));
+        assert_eq!(map.lower_bound(Excluded(&50)).key(), Some(&49));
+        assert_eq!(map.select(0), Some((&99, &99)));
+        assert_eq!(map.remove(&10), Some(10));
+
+        // Case-insensitive keys.
+        let mut map = BTreeMap::with_comparator(|a: &String, b: &String| {
+            a.to_lowercase().cmp(&b.to_lowercase())
+        });
+        map.insert("Apple".to_string(), 1);
+        assert_eq!(map.insert("APPLE".to_string(), 2), Some(1));
+        assert_eq!(map.find(&"apple".to_string()), Some(&2));
+        assert_eq!(map.len(), 1);
+
+        // Keys that aren't Ord at all.
+        let mut map = BTreeMap::with_comparator(f64::total_cmp);
+        for x in [2.5, -1.0, 0.0, 10.0] {
+            map.insert(x, ());
+        }
+        assert!(map.into_keys().eq([-1.0, 0.0, 2.5, 10.0]));
+    }
+
+    #[test]
+    fn test_merge_with() {
+        let mut a: BTreeMap<_, _> = (0..300).map(|i| (i * 2, 1)).collect();
+        let b: BTreeMap<_, _> = (0..300).map(|i| (i * 3, 10)).collect();
+        a.merge_with(b, |_, x, y| x + y);
+        let expected = (0..900).filter_map(|i| match (i % 2 == 0 && i < 600, i % 3 == 0) {
+            (true, true) => Some((i, 11)),
+            (true, false) => Some((i, 1)),
+            (false, true) => Some((i, 10)),
+            (false, false) => None,
+        });
+        assert!(a.iter().map(|(k, v)| (*k, *v)).eq(expected));
+        assert_eq!(a.len(), a.iter().count());
+
+        // The map is still a working tree afterwards.
+        for i in 0..900 {
+            a.remove(&i);
+        }
+        assert!(a.is_empty());
+        a.merge_with(BTreeMap::new(), |_, x, _| x);
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = BTreeMap::<u64, u64>::with_b(2).stats();
+        assert_eq!((stats.depth, stats.nodes, stats.leaves), (1, 1, 1));
+        assert_eq!((stats.fill_factor, stats.bytes_per_entry), (0.0, 0.0));
+
+        // Merging bulk loads, so this packs a root with one key over two full leaves of three.
+        let mut map = BTreeMap::with_b(2);
+        map.merge_with(
+            BTreeMap::from_sorted_iter((0..7u64).map(|i| (i, i))),
+            |_, v, _| v,
+        );
+        let stats = map.stats();
+        assert_eq!((stats.depth, stats.nodes, stats.leaves), (2, 3, 2));
+        assert_eq!(stats.fill_factor, 7.0 / 9.0);
+        assert!(stats.bytes_per_entry > 16.0);
+    }
+
+    #[test]
+    fn test_from_array() {
+        let map = BTreeMap::from([(3, "c"), (1, "a"), (2, "b"), (1, "z")]);
+        assert_eq!(map.len(), 3);
+        assert!(map.iter().eq([(&1, &"z"), (&2, &"b"), (&3, &"c")]));
+        assert!(BTreeMap::<i32, ()>::from([]).is_empty());
+    }
+
+    #[test]
+    fn test_default_b() {
+        assert_eq!(default_b::<u64, u64>(), 16);
+        assert_eq!(default_b::<u64, [u8; 56]>(), 6);
+        assert_eq!(default_b::<String, [u8; 1000]>(), 6);
+        assert_eq!(default_b::<(), ()>(), 16);
+        assert_eq!(BTreeMap::<u64, [u64; 3]>::new().b, 8);
+        assert_eq!(BTreeMap::<u8, u8>::with_b(3).b, 3);
+    }
+
+    #[test]
+    fn test_cursor_append() {
+        // Appending through a cursor parked on the last entry only descends after a split.
+        let mut map = BTreeMap::with_b(3);
+        let mut cursor = map.cursor_front_mut();
+        for i in 0..2000 {
+            cursor.insert_after(i, i);
+            cursor.move_next();
+            assert_eq!(cursor.key(), Some(&i));
+        }
+        map.check_invariants();
+        assert!(map.iter().map(|(k, _)| *k).eq(0..2000));
+
+        // Filling in between two entries from the upper one.
+        let mut map = BTreeMap::with_b(2);
+        map.insert(0, 0);
+        map.insert(10000, 0);
+        let mut cursor = map.cursor_mut(&10000);
+        for i in 1..1000 {
+            cursor.insert_before(i * 10, i);
+            assert_eq!(cursor.key(), Some(&10000));
+            assert_eq!(cursor.peek_prev(), Some((&(i * 10), &i)));
+        }
+        map.check_invariants();
+        assert_eq!(map.len(), 1001);
+    }
+
+    #[test]
+    fn test_scratch_stack() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        // Every kind of operation hands the parked stack back, including misses and replacements.
+        let mut map = BTreeMap::with_b(2);
+        assert_send_sync(&map);
+        for i in 0..500 {
+            map.insert(i, i);
+            assert_eq!(map.insert(i, i + 1), Some(i));
+            assert_eq!(map.remove(&(i + 1000)), None);
+            assert!(map.try_insert(i, 0).is_err());
+        }
+        for i in (0..500).step_by(2) {
+            assert_eq!(map.remove(&i), Some(i + 1));
+        }
+        *map.first_entry().unwrap().into_mut() = 0;
+        map.check_invariants();
+        assert_eq!(map.len(), 250);
+        assert_eq!(map[&1], 0);
+    }
+
+    #[test]
+    fn test_small_maps() {
+        // Small key spaces keep the map hovering around a single full leaf, so operations keep
+        // switching between the root-leaf fast paths and the general ones.
+        let mut rng = Lcg::new(7);
+        for b in [2, 3, 6] {
+            let mut map = BTreeMap::with_b(b);
+            let mut model = std::collections::BTreeMap::new();
+            for _ in 0..5000 {
+                let key = rng.below(4 * b as u32);
+                if rng.below(2) == 0 {
+                    assert_eq!(map.insert(key, b), model.insert(key, b));
+                } else {
+                    assert_eq!(map.remove(&key), model.remove(&key));
+                }
+                map.check_invariants();
+                assert_eq!(map.len(), model.len());
+            }
+            assert!(map.iter().eq(model.iter()));
+        }
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut map: BTreeMap<u32, u32> = BTreeMap::with_b(3);
+        for i in 0..10000 {
+            map.insert(i, i);
+        }
+        for i in (0..10000).filter(|i| i % 10 != 0) {
+            map.remove(&i);
+        }
+        let before = map.stats();
+        map.compact();
+        map.check_invariants();
+        let after = map.stats();
+        assert!(after.nodes * 2 < before.nodes);
+        assert!(map.iter().map(|(k, _)| *k).eq((0..10000).step_by(10)));
+        assert_eq!(map.rank(&5000), 500);
+
+        let mut empty: BTreeMap<u32, u32> = BTreeMap::new();
+        empty.compact();
+        empty.check_invariants();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_leaf_chunks() {
+        for n in [0, 1, 10, 5000] {
+            let map: BTreeMap<u32, u32> = (0..n).map(|i| (i, i * 2)).collect();
+            let mut keys = Vec::new();
+            let mut leaf_entries = 0;
+            for (chunk_keys, chunk_vals) in map.leaf_chunks() {
+                assert_eq!(chunk_keys.len(), chunk_vals.len());
+                assert!(!chunk_keys.is_empty());
+                if chunk_keys.len() > 1 || map.stats().depth == 1 {
+                    leaf_entries += chunk_keys.len();
+                }
+                assert!(chunk_keys.iter().zip(chunk_vals).all(|(k, v)| *v == k * 2));
+                keys.extend_from_slice(chunk_keys);
+            }
+            assert!(keys.into_iter().eq(0..n));
+            // Most of the entries are in the leaves, so most come out in long runs.
+            assert!(leaf_entries * 10 >= n as usize * 8);
+        }
+    }
+
+    #[test]
+    fn test_get_many() {
+        let map: BTreeMap<u32, u32> = (0..3000).map(|i| (i * 2, i)).collect();
+        let keys: Vec<u32> = (0..200)
+            .map(|i| i * i / 7)
+            .chain([5998, 5999, 5999, 7000])
+            .collect();
+        let expected: Vec<_> = keys.iter().map(|k| map.find(k)).collect();
+        assert_eq!(map.get_many(&keys), expected);
+        assert_eq!(map.get_many::<u32>(&[]), []);
+        assert_eq!(BTreeMap::<u32, u32>::new().get_many(&[1, 2]), [None, None]);
+
+        let words: BTreeMap<String, usize> = ["a", "b", "c"]
+            .iter()
+            .map(|w| (w.to_string(), w.len()))
+            .collect();
+        assert_eq!(words.get_many(&["b".to_string()]), [Some(&1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ascending")]
+    fn test_get_many_unsorted() {
+        let map: BTreeMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        map.get_many(&[3, 1]);
+    }
+
+    #[test]
+    fn test_panicking_comparator() {
+        use std::{
+            cell::Cell,
+            panic::{catch_unwind, AssertUnwindSafe},
+        };
+
+        // The comparator panics once `fuse` comparisons have been made after it's lit.
+        let fuse = Cell::new(usize::MAX);
+        let cmp = |a: &u32, b: &u32| {
+            fuse.set(fuse.get() - 1);
+            assert!(fuse.get() != 0, "boom");
+            a.cmp(b)
+        };
+        let full = || {
+            let mut map = BTreeMap::with_b_and_comparator(2, &cmp);
+            for i in 0..200 {
+                map.insert(i * 2, i);
+            }
+            map
+        };
+        let reference = full();
+        let same = |map: &BTreeMap<u32, u32, _>| map.iter().eq(reference.iter());
+
+        for countdown in 1..40 {
+            let mut map = full();
+            fuse.set(countdown);
+            let result = catch_unwind(AssertUnwindSafe(|| map.insert(101, 0)));
+            fuse.set(usize::MAX);
+            map.check_invariants();
+            assert!(result.is_ok() || same(&map));
+
+            let mut map = full();
+            fuse.set(countdown);
+            let result = catch_unwind(AssertUnwindSafe(|| map.remove(&100)));
+            fuse.set(usize::MAX);
+            map.check_invariants();
+            assert!(result.is_ok() || same(&map));
+
+            let mut map = full();
+            fuse.set(countdown);
+            let result = catch_unwind(AssertUnwindSafe(|| map.remove_range(51..=301)));
+            fuse.set(usize::MAX);
+            map.check_invariants();
+            assert!(result.is_ok() || map.is_empty());
+            map.insert(1, 1);
+            map.check_invariants();
+        }
+    }
+
+    #[test]
+    fn test_entry_ref() {
+        use std::cell::Cell;
+
+        thread_local!(static CONVERSIONS: Cell<usize> = const { Cell::new(0) });
+
+        #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Key(String);
+
+        impl From<&str> for Key {
+            fn from(key: &str) -> Key {
+                CONVERSIONS.with(|count| count.set(count.get() + 1));
+                Key(key.to_owned())
+            }
+        }
+
+        impl Borrow<str> for Key {
+            fn borrow(&self) -> &str {
+                &self.0
+            }
+        }
+
+        let words = "the quick brown fox jumps over the lazy dog the end";
+        let mut map = BTreeMap::<Key, i32>::with_b(2);
+        for word in words.split(' ') {
+            *map.entry_ref(word).or_insert(0) += 1;
+        }
+        assert_eq!(CONVERSIONS.with(Cell::get), 9);
+        assert_eq!(map.len(), 9);
+        assert_eq!(map.find("the"), Some(&3));
+        assert_eq!(map.find("fox"), Some(&1));
+
+        match map.entry_ref("cat") {
+            EntryRef::Vacant(entry) => assert_eq!(entry.key(), "cat"),
+            EntryRef::Occupied(_) => panic!("cat is not in the map"),
+        }
+        map.entry_ref("dog").and_modify(|n| *n = 10).or_default();
+        map.entry_ref("cat").and_modify(|n| *n = 10).or_default();
+        map.check_invariants();
+        assert_eq!(map.find("dog"), Some(&10));
+        assert_eq!(map.find("cat"), Some(&0));
+        assert_eq!(CONVERSIONS.with(Cell::get), 10);
+    }
+
+    #[test]
+    fn test_remove_range() {
+        let mut rng = Lcg::new(99);
+        for b in [2, 3, 6] {
+            for round in 0..200 {
+                let n = rng.below(600);
+                let mut map = BTreeMap::with_b(b);
+                let mut expected = std::collections::BTreeMap::new();
+                for _ in 0..n {
+                    let key = rng.below(1000);
+                    map.insert(key, round);
+                    expected.insert(key, round);
+                }
+                let (lo, hi) = (rng.below(1100), rng.below(1100));
+                let bounds = match round % 4 {
+                    0 => (Included(lo), Excluded(hi)),
+                    1 => (Excluded(lo), Included(hi)),
+                    2 => (Unbounded, Included(hi)),
+                    _ => (Included(lo), Unbounded),
+                };
+                let count = map.range_count(bounds);
+                assert_eq!(map.remove_range(bounds), count);
+                // std's ranges panic when they're back to front, where ours are just empty.
+                if round % 4 >= 2 || lo <= hi {
+                    expected.retain(|key, _| !bounds.contains(key));
+                }
+                map.check_invariants();
+                assert!(map.iter().eq(expected.iter()));
+            }
+        }
+
+        let mut map: BTreeMap<_, _> = (0..10000).map(|i| (i, i)).collect();
+        assert_eq!(map.remove_range(100..9900), 9800);
+        assert_eq!(map.remove_range(5000..), 100);
+        assert_eq!(map.remove_range((Included(50), Excluded(40))), 0);
+        assert_eq!(map.remove_range(..), 100);
+        map.check_invariants();
+        assert!(map.is_empty());
+        map.insert(1, 1);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_allocator() {
+        use crate::myvec::AllocError;
+        use std::alloc::Layout;
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+
+        // Counts the bytes it has out.
+        #[derive(Default)]
+        struct Tracked {
+            live: Cell<usize>,
+        }
+
+        unsafe impl Allocator for Tracked {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                self.live.set(self.live.get() + layout.size());
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.live.set(self.live.get() - layout.size());
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        let tracked = Tracked::default();
+        let mut map = BTreeMap::new_in(&tracked);
+        assert!(std::ptr::eq(*map.allocator(), &tracked));
+        let empty = tracked.live.get();
+        for i in 0..5000u32 {
+            map.insert(i, i.to_string());
+        }
+        map.check_invariants();
+        assert!(tracked.live.get() > empty);
+        for i in (0..5000).filter(|i| i % 4 != 0) {
+            map.remove(&i);
+        }
+        map.compact();
+        map.check_invariants();
+        assert!(map.iter().map(|(k, _)| *k).eq((0..5000).step_by(4)));
+
+        let mut other = BTreeMap::with_comparator_in(Natural, &tracked);
+        other.extend((0..1000).map(|i| (i * 4 + 1, String::new())));
+        map.merge_with(other, |_, a, _| a);
+        map.check_invariants();
+        assert_eq!(map.len(), 2250);
+
+        let mut iter = map.into_iter();
+        assert_eq!(iter.next(), Some((0, "0".to_string())));
+        drop(iter);
+        assert_eq!(tracked.live.get(), 0);
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        // A byte for B, then for each entry an odd byte to keep going, the key and the value.
+        let mut bytes = vec![3];
+        for i in 0..500u16 {
+            let [lo, hi] = i.to_le_bytes();
+            bytes.extend([1, lo, hi, 0]);
+        }
+        let map = BTreeMap::<u16, u8>::arbitrary_take_rest(Unstructured::new(&bytes)).unwrap();
+        map.check_invariants();
+        assert_eq!(map.len(), 500);
+    }
+}
@@ -0,0 +1,166 @@
+use super::super::compare::{Comparator, Natural};
+use super::super::node::{capacity_from_b, NodePool};
+use super::{default_b, BTreeMap, NODE_POOL_LIMIT};
+use crate::myvec::{Allocator, Global};
+
+/// Configures a BTreeMap before it's made, for when the defaults for B, the comparator, the
+/// allocator and the node pool aren't what you want.
+#[derive(Clone, Copy, Debug)]
+pub struct BTreeMapBuilder<C = Natural, A = Global> {
+    b: Option<usize>,
+    cmp: C,
+    alloc: A,
+    pool_limit: usize,
+    preallocate: usize,
+}
+
+impl Default for BTreeMapBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BTreeMapBuilder {
+    /// Starts from the settings `BTreeMap::new` uses.
+    pub fn new() -> BTreeMapBuilder {
+        BTreeMapBuilder {
+            b: None,
+            cmp: Natural,
+            alloc: Global,
+            pool_limit: NODE_POOL_LIMIT,
+            preallocate: 0,
+        }
+    }
+}
+
+impl<C, A> BTreeMapBuilder<C, A> {
+    /// Sets B. Nodes hold between B-1 and 2B-1 entries. If it isn't set, B is picked from the size
+    /// of the keys and values, as `BTreeMap::new` does.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b` is less than 2.
+    pub fn b(mut self, b: usize) -> BTreeMapBuilder<C, A> {
+        assert!(b > 1, "B must be greater than 1");
+        self.b = Some(b);
+        self
+    }
+
+    /// Sets the comparator that orders the map's keys.
+    pub fn comparator<D>(self, cmp: D) -> BTreeMapBuilder<D, A> {
+        BTreeMapBuilder {
+            b: self.b,
+            cmp,
+            alloc: self.alloc,
+            pool_limit: self.pool_limit,
+            preallocate: self.preallocate,
+        }
+    }
+
+    /// Sets the allocator the map's nodes get their memory from.
+    pub fn allocator<B: Allocator + Clone>(self, alloc: B) -> BTreeMapBuilder<C, B> {
+        BTreeMapBuilder {
+            b: self.b,
+            cmp: self.cmp,
+            alloc,
+            pool_limit: self.pool_limit,
+            preallocate: self.preallocate,
+        }
+    }
+
+    /// Sets how many emptied nodes the map keeps around for reuse, rather than freeing them.
+    pub fn pool_limit(mut self, limit: usize) -> BTreeMapBuilder<C, A> {
+        self.pool_limit = limit;
+        self
+    }
+
+    /// Allocates `count` nodes into the map's pool up front, so the map's first splits don't have
+    /// to. This raises the pool limit to `count` if it's lower.
+    pub fn preallocate_nodes(mut self, count: usize) -> BTreeMapBuilder<C, A> {
+        self.preallocate = count;
+        self.pool_limit = self.pool_limit.max(count);
+        self
+    }
+
+    /// Makes the map.
+    pub fn build<K, V>(self) -> BTreeMap<K, V, C, A>
+    where
+        C: Comparator<K>,
+        A: Allocator + Clone,
+    {
+        let b = self.b.unwrap_or_else(default_b::<K, V>);
+        let mut map = BTreeMap::new_with(b, self.cmp, self.alloc.clone());
+        map.pool = NodePool::new(self.pool_limit, self.alloc);
+        map.pool.fill(capacity_from_b(b), self.preallocate);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::BTreeMap;
+    use super::BTreeMapBuilder;
+
+    #[test]
+    fn test_builder() {
+        let mut map: BTreeMap<u32, u32> = BTreeMapBuilder::new().build();
+        map.insert(1, 1);
+        assert_eq!(map.b, BTreeMap::<u32, u32>::new().b);
+
+        let mut map = BTreeMapBuilder::new()
+            .b(3)
+            .comparator(|a: &u32, b: &u32| b.cmp(a))
+            .pool_limit(4)
+            .preallocate_nodes(10)
+            .build();
+        assert_eq!(map.b, 3);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        map.check_invariants();
+        assert!(map.iter().map(|(k, _)| *k).eq((0..1000).rev()));
+    }
+
+    #[test]
+    fn test_builder_allocator() {
+        use crate::myvec::{AllocError, Allocator, Global};
+        use std::alloc::Layout;
+        use std::cell::Cell;
+        use std::ptr::NonNull;
+
+        // Counts the blocks it has out.
+        #[derive(Default)]
+        struct Counted {
+            blocks: Cell<usize>,
+        }
+
+        unsafe impl Allocator for Counted {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                self.blocks.set(self.blocks.get() + 1);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.blocks.set(self.blocks.get() - 1);
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        let counted = Counted::default();
+        let mut map = BTreeMapBuilder::new()
+            .b(4)
+            .allocator(&counted)
+            .preallocate_nodes(5)
+            .build();
+        assert!(std::ptr::eq(*map.allocator(), &counted));
+        // The root's keys and values, and two buffers for each pooled node.
+        assert_eq!(counted.blocks.get(), 2 + 5 * 2);
+        for i in 0..1000u32 {
+            map.insert(i, i);
+        }
+        map.check_invariants();
+        assert!(counted.blocks.get() > 2 + 5 * 2);
+        drop(map);
+        assert_eq!(counted.blocks.get(), 0);
+    }
+}
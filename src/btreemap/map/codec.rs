@@ -0,0 +1,329 @@
+use super::super::compare::{Comparator, OrderedF32, OrderedF64};
+use super::{BTreeMap, Node};
+use crate::myvec::Allocator;
+use std::{
+    cmp::Ordering,
+    io::{self, Read, Write},
+};
+
+// Every encoded map starts with these, followed by B as a u32 and the number of entries as a u64,
+// all little-endian, and then the entries in key order.
+const MAGIC: &[u8; 4] = b"TCBT";
+const VERSION: u8 = 1;
+
+// Nodes are allocated at full size up front, so B comes from the input only up to this. It's far
+// beyond any B that's useful in memory, and maps with a bigger one refuse to be written.
+const MAX_B: u32 = 1 << 16;
+
+/// A type that can be written out in `BTreeMap`'s binary format.
+pub trait Encode {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()>;
+}
+
+/// A type that can be read back from `BTreeMap`'s binary format.
+pub trait Decode: Sized {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self>;
+}
+
+impl<K: Encode, V: Encode, C: Comparator<K>, A: Allocator + Clone> BTreeMap<K, V, C, A> {
+    /// Writes the map out in a compact, versioned binary format that `decode_from` reads back.
+    /// This makes a lot of small writes, so `w` should be buffered.
+    pub fn encode_to<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        // Check B before writing anything, so a map that can't be encoded leaves `w` untouched.
+        let b = u32::try_from(self.b)
+            .ok()
+            .filter(|&b| b <= MAX_B)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "B too large to encode"))?;
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&b.to_le_bytes())?;
+        (self.length as u64).encode(w)?;
+        for (key, value) in self.iter() {
+            key.encode(w)?;
+            value.encode(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<K, V, C, A> BTreeMap<K, V, C, A>
+where
+    K: Decode,
+    V: Decode,
+    C: Comparator<K> + Default,
+    A: Allocator + Clone + Default,
+{
+    /// Reads a map written by `encode_to`, and rebuilds it with the same B by packing the nodes
+    /// directly. Input that isn't a well-formed map, including one whose keys aren't in strictly
+    /// ascending order, is an `InvalidData` error.
+    pub fn decode_from<R: Read + ?Sized>(r: &mut R) -> io::Result<BTreeMap<K, V, C, A>> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an encoded BTreeMap"));
+        }
+        if u8::decode(r)? != VERSION {
+            return Err(invalid("unsupported BTreeMap encoding version"));
+        }
+        let b = u32::decode(r)?;
+        if b < 2 {
+            return Err(invalid("B must be greater than 1"));
+        }
+        if b > MAX_B {
+            return Err(invalid("B too large"));
+        }
+        let b = b as usize;
+        let length = decode_len(r)?;
+
+        let mut map = BTreeMap::new_with(b, C::default(), A::default());
+        let mut entries = Vec::with_capacity(length.min(PREALLOCATE_MAX));
+        for _ in 0..length {
+            entries.push((K::decode(r)?, V::decode(r)?));
+        }
+        if entries
+            .windows(2)
+            .any(|pair| map.cmp.compare(&pair[0].0, &pair[1].0) != Ordering::Less)
+        {
+            return Err(invalid("keys out of order"));
+        }
+        let (root, length, depth) =
+            Node::from_sorted_iter(b, entries, &map.cmp, map.pool.allocator());
+        map.root = root;
+        map.length = length;
+        map.depth = depth;
+        Ok(map)
+    }
+}
+
+// Lengths come from the input, so don't trust them with more than this much up front.
+const PREALLOCATE_MAX: usize = 4096;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn decode_len<R: Read + ?Sized>(r: &mut R) -> io::Result<usize> {
+    usize::try_from(u64::decode(r)?).map_err(|_| invalid("length too large"))
+}
+
+macro_rules! impl_codec_for_number {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+                w.write_all(&self.to_le_bytes())
+            }
+        }
+
+        impl Decode for $t {
+            fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+                let mut bytes = [0; std::mem::size_of::<$t>()];
+                r.read_exact(&mut bytes)?;
+                Ok(<$t>::from_le_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+impl_codec_for_number!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+macro_rules! impl_codec_for_ordered_float {
+    ($($t:ident($float:ty)),*) => {$(
+        impl Encode for $t {
+            fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+                self.0.encode(w)
+            }
+        }
+
+        impl Decode for $t {
+            fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+                <$float>::decode(r).map($t)
+            }
+        }
+    )*};
+}
+
+impl_codec_for_ordered_float!(OrderedF32(f32), OrderedF64(f64));
+
+impl Encode for bool {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u8).encode(w)
+    }
+}
+
+impl Decode for bool {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        match u8::decode(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("bad bool")),
+        }
+    }
+}
+
+impl Encode for char {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u32).encode(w)
+    }
+}
+
+impl Decode for char {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        char::from_u32(u32::decode(r)?).ok_or_else(|| invalid("bad char"))
+    }
+}
+
+impl Encode for () {
+    fn encode<W: Write + ?Sized>(&self, _: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decode for () {
+    fn decode<R: Read + ?Sized>(_: &mut R) -> io::Result<Self> {
+        Ok(())
+    }
+}
+
+impl Encode for str {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (self.len() as u64).encode(w)?;
+        w.write_all(self.as_bytes())
+    }
+}
+
+impl Encode for String {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.as_str().encode(w)
+    }
+}
+
+impl Decode for String {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        let bytes = Vec::<u8>::decode(r)?;
+        String::from_utf8(bytes).map_err(|_| invalid("bad UTF-8"))
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (self.len() as u64).encode(w)?;
+        self.iter().try_for_each(|elem| elem.encode(w))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.as_slice().encode(w)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        let len = decode_len(r)?;
+        let mut vec = Vec::with_capacity(len.min(PREALLOCATE_MAX));
+        for _ in 0..len {
+            vec.push(T::decode(r)?);
+        }
+        Ok(vec)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            None => false.encode(w),
+            Some(value) => {
+                true.encode(w)?;
+                value.encode(w)
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        Ok(if bool::decode(r)? {
+            Some(T::decode(r)?)
+        } else {
+            None
+        })
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        self.0.encode(w)?;
+        self.1.encode(w)
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode<R: Read + ?Sized>(r: &mut R) -> io::Result<Self> {
+        Ok((A::decode(r)?, B::decode(r)?))
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode<W: Write + ?Sized>(&self, w: &mut W) -> io::Result<()> {
+        (**self).encode(w)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{super::BTreeMap, MAX_B};
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_round_trip() {
+        let map: BTreeMap<u64, String> = (0..5000).map(|i| (i * 3, format!("v{i}"))).collect();
+        let mut bytes = Vec::new();
+        map.encode_to(&mut bytes).unwrap();
+        let decoded = BTreeMap::<u64, String>::decode_from(&mut bytes.as_slice()).unwrap();
+        decoded.check_invariants();
+        assert_eq!(decoded, map);
+        assert_eq!(decoded.b, map.b);
+
+        let empty = BTreeMap::<i8, Vec<(bool, char)>>::new();
+        let mut bytes = Vec::new();
+        empty.encode_to(&mut bytes).unwrap();
+        assert!(
+            BTreeMap::<i8, Vec<(bool, char)>>::decode_from(&mut &bytes[..])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let map: BTreeMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        let mut bytes = Vec::new();
+        map.encode_to(&mut bytes).unwrap();
+        let decode = |bytes: &[u8]| BTreeMap::<u32, u32>::decode_from(&mut &bytes[..]);
+
+        assert_eq!(
+            decode(&bytes[..bytes.len() - 1]).unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        let mut bad = bytes.clone();
+        bad[0] = b'X';
+        assert_eq!(decode(&bad).unwrap_err().kind(), ErrorKind::InvalidData);
+        // Swap the first two keys.
+        let mut bad = bytes.clone();
+        bad[17..21].copy_from_slice(&1u32.to_le_bytes());
+        bad[25..29].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(decode(&bad).unwrap_err().kind(), ErrorKind::InvalidData);
+        // A B that would need a huge node just to start with.
+        let mut bad = bytes.clone();
+        bad[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(decode(&bad).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let map = BTreeMap::<u32, u32>::with_b(MAX_B as usize + 1);
+        let mut out = Vec::new();
+        assert_eq!(
+            map.encode_to(&mut out).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert!(out.is_empty());
+    }
+}
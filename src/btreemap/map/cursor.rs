@@ -0,0 +1,593 @@
+use super::super::compare::{Comparator, Natural};
+use super::super::inline_stack::InlineStack;
+use super::super::node::{Node, SearchResult::*};
+use super::stack::{SearchStack, Stack, StackItem};
+use super::BTreeMap;
+use crate::myvec::{Allocator, Global};
+use std::{borrow::Borrow, cmp::Ordering::*, mem, ops::Deref};
+
+/// A read-only cursor over a BTreeMap. It moves the same way as `CursorMut`, including the ghost
+/// position between the last entry and the first.
+pub struct Cursor<'a, K: 'a, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    root: &'a Node<K, V, A>,
+    cmp: &'a C,
+    // The ancestors of the current node, each with the index of the edge we took down from it.
+    stack: InlineStack<(&'a Node<K, V, A>, usize)>,
+    // The current node and the index of the current entry in it, or None at the ghost.
+    current: Option<(&'a Node<K, V, A>, usize)>,
+}
+
+/// A cursor over a BTreeMap that can move back and forth and edit the map in place.
+///
+/// The cursor either points at an entry, or at a "ghost" position that sits after the last entry
+/// and before the first one. Moving off either end of the map lands on the ghost, and moving on
+/// from the ghost wraps around to the other end.
+pub struct CursorMut<'a, K: 'a, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    map: &'a mut BTreeMap<K, V, C, A>,
+    // The ancestors of the current node, each with the index of the edge we took down from it.
+    stack: Stack<K, V, A>,
+    // The current node and the index of the current entry in it, or None at the ghost.
+    current: Option<StackItem<K, V, A>>,
+}
+
+impl<'a, K, V, C, A: Allocator + Clone> CursorMut<'a, K, V, C, A>
+where
+    C: Comparator<K>,
+{
+    // Makes a cursor pointing at the ghost position.
+    pub(super) fn new(map: &'a mut BTreeMap<K, V, C, A>) -> CursorMut<'a, K, V, C, A> {
+        let depth = map.depth;
+        CursorMut {
+            map,
+            stack: Vec::with_capacity(depth),
+            current: None,
+        }
+    }
+
+    /// Returns the key of the current entry, or None at the ghost position.
+    pub fn key(&self) -> Option<&K> {
+        self.current.and_then(|(node, index)| unsafe { (*node).key(index) })
+    }
+
+    /// Returns the value of the current entry, or None at the ghost position.
+    pub fn value(&self) -> Option<&V> {
+        self.current.and_then(|(node, index)| unsafe { (*node).val(index) })
+    }
+
+    /// Returns the value of the current entry mutably, or None at the ghost position.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        self.current.and_then(|(node, index)| unsafe { (*node).val_mut(index) })
+    }
+
+    /// Returns the entry after the current one without moving the cursor. At the ghost position
+    /// this is the first entry of the map.
+    pub fn peek_next(&self) -> Option<(&K, &V)> {
+        let (path, current) = self.shared_path();
+        let (node, index) = peek_after(&self.map.root, path, current)?;
+        node.key(index).zip(node.val(index))
+    }
+
+    /// Returns the entry before the current one without moving the cursor. At the ghost position
+    /// this is the last entry of the map.
+    pub fn peek_prev(&self) -> Option<(&K, &V)> {
+        let (path, current) = self.shared_path();
+        let (node, index) = peek_before(&self.map.root, path, current)?;
+        node.key(index).zip(node.val(index))
+    }
+
+    /// Moves the cursor to the next entry. From the last entry this moves to the ghost position,
+    /// and from the ghost position to the first entry.
+    pub fn move_next(&mut self) {
+        let root = self.root();
+        self.current = unsafe { step_next(root, &mut self.stack, self.current) };
+    }
+
+    /// Moves the cursor to the previous entry. From the first entry this moves to the ghost
+    /// position, and from the ghost position to the last entry.
+    pub fn move_prev(&mut self) {
+        let root = self.root();
+        self.current = unsafe { step_prev(root, &mut self.stack, self.current) };
+    }
+
+    /// Moves the cursor to `key`, or to the first entry after it if `key` isn't in the map.
+    /// Returns whether `key` was found.
+    pub fn seek<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.stack.clear();
+        let mut node = self.root();
+        loop {
+            unsafe {
+                match (*node).search(key, &self.map.cmp) {
+                    Found(i) => {
+                        self.current = Some((node, i));
+                        return true;
+                    }
+                    GoDown(i) => match (*node).edge_mut(i) {
+                        Some(edge) => {
+                            self.stack.push((node, i));
+                            node = edge;
+                        }
+                        None => {
+                            self.current = if i < (*node).len() {
+                                Some((node, i))
+                            } else {
+                                ascend_next(&mut self.stack)
+                            };
+                            return false;
+                        }
+                    },
+                }
+            }
+        }
+    }
+
+    /// Inserts a new entry right after the current one, or at the front of the map at the ghost
+    /// position. The cursor stays where it is.
+    ///
+    /// The cursor only searches the tree again when the insert splits a node, so appending sorted
+    /// entries with `insert_after` and `move_next` costs amortized O(1) each, not O(log n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` doesn't sort strictly between the current entry and the next one.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        if let Some(current) = self.key() {
+            assert!(
+                self.map.cmp.compare(current, &key) == Less,
+                "key must be ordered above the current entry"
+            );
+        }
+        if let Some((next, _)) = self.peek_next() {
+            assert!(
+                self.map.cmp.compare(&key, next) == Less,
+                "key must be ordered below the next entry"
+            );
+        }
+
+        let depth = self.stack.len();
+        let top = unsafe {
+            match self.current {
+                None => {
+                    self.stack.clear();
+                    let root = self.root();
+                    (descend_first(&mut self.stack, root), 0)
+                }
+                Some((node, index)) => match node.child(index + 1) {
+                    Some(edge) => {
+                        self.stack.push((node, index + 1));
+                        (descend_first(&mut self.stack, edge), 0)
+                    }
+                    None => (node, index + 1),
+                },
+            }
+        };
+        if let Err(inserted) = self.insert_at(top, key, value, depth) {
+            if self.current.is_some() {
+                unsafe { self.seek(&*inserted) };
+                self.move_prev();
+            }
+        }
+    }
+
+    /// Inserts a new entry right before the current one, or at the back of the map at the ghost
+    /// position. The cursor stays where it is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` doesn't sort strictly between the previous entry and the current one.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        if let Some(current) = self.key() {
+            assert!(
+                self.map.cmp.compare(&key, current) == Less,
+                "key must be ordered below the current entry"
+            );
+        }
+        if let Some((prev, _)) = self.peek_prev() {
+            assert!(
+                self.map.cmp.compare(prev, &key) == Less,
+                "key must be ordered above the previous entry"
+            );
+        }
+
+        let depth = self.stack.len();
+        let top = unsafe {
+            match self.current {
+                None => {
+                    self.stack.clear();
+                    let root = self.root();
+                    descend_last(&mut self.stack, root)
+                }
+                Some((node, index)) => match node.child(index) {
+                    Some(edge) => {
+                        self.stack.push((node, index));
+                        descend_last(&mut self.stack, edge)
+                    }
+                    None => (node, index),
+                },
+            }
+        };
+        match self.insert_at(top, key, value, depth) {
+            // The new entry went in just before the current one in the same node, shifting it up.
+            Ok(()) => {
+                if let Some((node, index)) = self.current {
+                    if node == top.0 {
+                        self.current = Some((node, index + 1));
+                    }
+                }
+            }
+            Err(inserted) => {
+                if self.current.is_some() {
+                    unsafe { self.seek(&*inserted) };
+                    self.move_next();
+                }
+            }
+        }
+    }
+
+    /// Removes the current entry and moves the cursor to the entry after it. Returns None and does
+    /// nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let top = self.current.take()?;
+        let stack = mem::take(&mut self.stack);
+        let (key, value) = SearchStack::from_parts(self.map, stack, top).remove();
+        self.seek(&key);
+        Some((key, value))
+    }
+}
+
+// private functions
+impl<'a, K, V, C, A: Allocator + Clone> CursorMut<'a, K, V, C, A>
+where
+    C: Comparator<K>,
+{
+    fn root(&mut self) -> *mut Node<K, V, A> {
+        &mut self.map.root
+    }
+
+    // The path and current entry as shared references, for looking around without moving.
+    #[allow(clippy::type_complexity)]
+    fn shared_path(
+        &self,
+    ) -> (
+        impl DoubleEndedIterator<Item = (&Node<K, V, A>, usize)>,
+        Option<(&Node<K, V, A>, usize)>,
+    ) {
+        // The cursor's pointers all point into the map it borrows, which `&self` keeps borrowed.
+        let path = self
+            .stack
+            .iter()
+            .map(|&(node, edge)| (unsafe { &*node }, edge));
+        let current = self.current.map(|(node, index)| (unsafe { &*node }, index));
+        (path, current)
+    }
+
+    // Insert at the leaf position `top`, whose ancestors are on the stack above the cursor's own
+    // `depth` entries. If no node split, the cursor's path is still good and is put back. If one
+    // did, the path is lost and the cursor has to find its way back using the returned key.
+    fn insert_at(
+        &mut self,
+        top: StackItem<K, V, A>,
+        key: K,
+        value: V,
+        depth: usize,
+    ) -> Result<(), *const K> {
+        let stack = mem::take(&mut self.stack);
+        match SearchStack::from_parts(self.map, stack, top).insert_entry(key, value) {
+            (_, _, Some(mut stack)) => {
+                stack.truncate(depth);
+                self.stack = stack;
+                Ok(())
+            }
+            (key, _, None) => Err(key),
+        }
+    }
+}
+
+impl<'a, K, V, C, A: Allocator> Cursor<'a, K, V, C, A>
+where
+    C: Comparator<K>,
+{
+    // Makes a cursor pointing at the ghost position.
+    pub(super) fn new(map: &'a BTreeMap<K, V, C, A>) -> Cursor<'a, K, V, C, A> {
+        Cursor {
+            root: &map.root,
+            cmp: &map.cmp,
+            stack: InlineStack::new(),
+            current: None,
+        }
+    }
+
+    /// Returns the key of the current entry, or None at the ghost position.
+    pub fn key(&self) -> Option<&'a K> {
+        self.current.and_then(|(node, index)| node.key(index))
+    }
+
+    /// Returns the value of the current entry, or None at the ghost position.
+    pub fn value(&self) -> Option<&'a V> {
+        self.current.and_then(|(node, index)| node.val(index))
+    }
+
+    /// Returns the entry after the current one without moving the cursor. At the ghost position
+    /// this is the first entry of the map.
+    pub fn peek_next(&self) -> Option<(&'a K, &'a V)> {
+        let (node, index) = peek_after(self.root, self.stack.iter().copied(), self.current)?;
+        node.key(index).zip(node.val(index))
+    }
+
+    /// Returns the entry before the current one without moving the cursor. At the ghost position
+    /// this is the last entry of the map.
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a V)> {
+        let (node, index) = peek_before(self.root, self.stack.iter().copied(), self.current)?;
+        node.key(index).zip(node.val(index))
+    }
+
+    /// Moves the cursor to the next entry. From the last entry this moves to the ghost position,
+    /// and from the ghost position to the first entry.
+    pub fn move_next(&mut self) {
+        self.current = unsafe { step_next(self.root, &mut self.stack, self.current) };
+    }
+
+    /// Moves the cursor to the previous entry. From the first entry this moves to the ghost
+    /// position, and from the ghost position to the last entry.
+    pub fn move_prev(&mut self) {
+        self.current = unsafe { step_prev(self.root, &mut self.stack, self.current) };
+    }
+
+    /// Moves the cursor to `key`, or to the first entry after it if `key` isn't in the map.
+    /// Returns whether `key` was found.
+    pub fn seek<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        self.stack.clear();
+        let mut node = self.root;
+        loop {
+            match node.search(key, self.cmp) {
+                Found(i) => {
+                    self.current = Some((node, i));
+                    return true;
+                }
+                GoDown(i) => match node.edge(i) {
+                    Some(edge) => {
+                        self.stack.push((node, i));
+                        node = edge;
+                    }
+                    None => {
+                        self.current = if i < node.len() {
+                            Some((node, i))
+                        } else {
+                            unsafe { ascend_next(&mut self.stack) }
+                        };
+                        return false;
+                    }
+                },
+            }
+        }
+    }
+}
+
+// Both cursors move through the tree the same way, and differ only in how they hold on to nodes:
+// `Cursor` by shared reference, and `CursorMut` by raw pointer so it can edit through them. The
+// movement is written once below, over either kind of handle.
+trait Handle: Copy {
+    // The number of entries in the node.
+    //
+    // Safety: for a raw pointer, the node must be alive and not borrowed anywhere else.
+    unsafe fn entries(self) -> usize;
+
+    // The child at `index`, or None in a leaf. The same safety rules apply.
+    unsafe fn child(self, index: usize) -> Option<Self>;
+}
+
+impl<K, V, A: Allocator> Handle for &Node<K, V, A> {
+    unsafe fn entries(self) -> usize {
+        self.len()
+    }
+
+    unsafe fn child(self, index: usize) -> Option<Self> {
+        self.edge(index)
+    }
+}
+
+impl<K, V, A: Allocator> Handle for *mut Node<K, V, A> {
+    unsafe fn entries(self) -> usize {
+        (*self).len()
+    }
+
+    unsafe fn child(self, index: usize) -> Option<Self> {
+        (*self)
+            .edge_mut(index)
+            .map(|edge| edge as *mut Node<K, V, A>)
+    }
+}
+
+// The path a cursor keeps down to its current node: each ancestor, with the index of the edge
+// taken down from it.
+trait Path<P>: Deref<Target = [(P, usize)]> {
+    fn push(&mut self, item: (P, usize));
+    fn pop(&mut self) -> Option<(P, usize)>;
+    fn clear(&mut self);
+}
+
+impl<P> Path<P> for Vec<(P, usize)> {
+    fn push(&mut self, item: (P, usize)) {
+        Vec::push(self, item);
+    }
+
+    fn pop(&mut self) -> Option<(P, usize)> {
+        Vec::pop(self)
+    }
+
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+}
+
+impl<P: Copy> Path<P> for InlineStack<(P, usize)> {
+    fn push(&mut self, item: (P, usize)) {
+        InlineStack::push(self, item);
+    }
+
+    fn pop(&mut self) -> Option<(P, usize)> {
+        InlineStack::pop(self)
+    }
+
+    fn clear(&mut self) {
+        InlineStack::clear(self);
+    }
+}
+
+// Where moving forward from `current` lands, keeping `path` in step. From the last entry that's
+// the ghost position, and from the ghost the first entry.
+unsafe fn step_next<P: Handle, S: Path<P>>(
+    root: P,
+    path: &mut S,
+    current: Option<(P, usize)>,
+) -> Option<(P, usize)> {
+    match current {
+        None => {
+            path.clear();
+            let leaf = descend_first(path, root);
+            // Only the root of an empty map can be an empty leaf.
+            (leaf.entries() > 0).then_some((leaf, 0))
+        }
+        Some((node, index)) => match node.child(index + 1) {
+            Some(child) => {
+                path.push((node, index + 1));
+                Some((descend_first(path, child), 0))
+            }
+            None if index + 1 < node.entries() => Some((node, index + 1)),
+            None => ascend_next(path),
+        },
+    }
+}
+
+// Where moving back from `current` lands, keeping `path` in step. From the first entry that's the
+// ghost position, and from the ghost the last entry.
+unsafe fn step_prev<P: Handle, S: Path<P>>(
+    root: P,
+    path: &mut S,
+    current: Option<(P, usize)>,
+) -> Option<(P, usize)> {
+    match current {
+        None => {
+            path.clear();
+            let (leaf, len) = descend_last(path, root);
+            len.checked_sub(1).map(|index| (leaf, index))
+        }
+        Some((node, index)) => match node.child(index) {
+            Some(child) => {
+                path.push((node, index));
+                let (leaf, len) = descend_last(path, child);
+                Some((leaf, len - 1))
+            }
+            None if index > 0 => Some((node, index - 1)),
+            None => ascend_prev(path),
+        },
+    }
+}
+
+// Walk down to the leftmost leaf below `node`, pushing the path.
+unsafe fn descend_first<P: Handle, S: Path<P>>(path: &mut S, mut node: P) -> P {
+    while let Some(child) = node.child(0) {
+        path.push((node, 0));
+        node = child;
+    }
+    node
+}
+
+// Walk down to the rightmost leaf below `node`, pushing the path. Returns the leaf and its length.
+unsafe fn descend_last<P: Handle, S: Path<P>>(path: &mut S, mut node: P) -> (P, usize) {
+    loop {
+        let len = node.entries();
+        match node.child(len) {
+            Some(child) => {
+                path.push((node, len));
+                node = child;
+            }
+            None => return (node, len),
+        }
+    }
+}
+
+// We've run off the end of a node; the next entry is the key right of the first edge we took
+// down that has one, or the ghost if there's none.
+unsafe fn ascend_next<P: Handle, S: Path<P>>(path: &mut S) -> Option<(P, usize)> {
+    while let Some((parent, edge)) = path.pop() {
+        if edge < parent.entries() {
+            return Some((parent, edge));
+        }
+    }
+    None
+}
+
+unsafe fn ascend_prev<P: Handle, S: Path<P>>(path: &mut S) -> Option<(P, usize)> {
+    while let Some((parent, edge)) = path.pop() {
+        if edge > 0 {
+            return Some((parent, edge - 1));
+        }
+    }
+    None
+}
+
+// Where `step_next` would land, without touching the path.
+fn peek_after<'b, K, V, A: Allocator>(
+    root: &'b Node<K, V, A>,
+    mut path: impl DoubleEndedIterator<Item = (&'b Node<K, V, A>, usize)>,
+    current: Option<(&'b Node<K, V, A>, usize)>,
+) -> Option<(&'b Node<K, V, A>, usize)> {
+    match current {
+        None => Some((first_leaf(root), 0)),
+        Some((node, index)) => match node.edge(index + 1) {
+            Some(edge) => Some((first_leaf(edge), 0)),
+            None if index + 1 < node.len() => Some((node, index + 1)),
+            // We're at the end of a leaf, so the next entry is in the first ancestor we came
+            // down to on the left of a key.
+            None => path.rfind(|&(parent, edge)| edge < parent.len()),
+        },
+    }
+}
+
+// Where `step_prev` would land, without touching the path.
+fn peek_before<'b, K, V, A: Allocator>(
+    root: &'b Node<K, V, A>,
+    mut path: impl DoubleEndedIterator<Item = (&'b Node<K, V, A>, usize)>,
+    current: Option<(&'b Node<K, V, A>, usize)>,
+) -> Option<(&'b Node<K, V, A>, usize)> {
+    match current {
+        None => {
+            let leaf = last_leaf(root);
+            Some((leaf, leaf.len().checked_sub(1)?))
+        }
+        Some((node, index)) => match node.edge(index) {
+            Some(edge) => {
+                let leaf = last_leaf(edge);
+                Some((leaf, leaf.len() - 1))
+            }
+            None if index > 0 => Some((node, index - 1)),
+            None => path
+                .rfind(|&(_, edge)| edge > 0)
+                .map(|(parent, edge)| (parent, edge - 1)),
+        },
+    }
+}
+
+fn first_leaf<K, V, A: Allocator>(mut node: &Node<K, V, A>) -> &Node<K, V, A> {
+    while let Some(edge) = node.edge(0) {
+        node = edge;
+    }
+    node
+}
+
+fn last_leaf<K, V, A: Allocator>(mut node: &Node<K, V, A>) -> &Node<K, V, A> {
+    while let Some(edge) = node.edge(node.len()) {
+        node = edge;
+    }
+    node
+}
@@ -0,0 +1,227 @@
+use super::super::{compare::Comparator, node::Node};
+use super::BTreeMap;
+use crate::myvec::Allocator;
+use std::{cmp::Ordering::*, fmt, io};
+
+impl<K: fmt::Debug, V, C, A: Allocator> BTreeMap<K, V, C, A> {
+    /// Writes the shape of the tree as a Graphviz DOT graph, with each node drawn as a record of
+    /// its keys and a port for each child edge. Render it with e.g. `dot -Tsvg`.
+    pub fn dump_dot<W: io::Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "digraph btree {{")?;
+        writeln!(w, "    node [shape=record];")?;
+        let mut next_id = 0;
+        dump_node(&self.root, &mut w, &mut next_id)?;
+        writeln!(w, "}}")
+    }
+}
+
+impl<K: fmt::Debug, V, C: Comparator<K>, A: Allocator> BTreeMap<K, V, C, A> {
+    /// Checks the structure of the tree, and panics with a description of the first problem it
+    /// finds. It checks that keys are in order within and across nodes, that internal nodes have
+    /// one more child than they have keys, that every leaf is at the same depth, that nodes other
+    /// than the root are neither under nor over their load limits, and that the counts kept in
+    /// the map and nodes are right.
+    pub fn check_invariants(&self) {
+        let root = &self.root;
+        if !root.is_leaf() {
+            assert!(root.len() > 0, "internal root has no keys");
+        }
+        let size = self.check_node(root, &mut Vec::new(), None, None);
+        assert_eq!(
+            size, self.length,
+            "map length doesn't match the entries in the tree"
+        );
+    }
+
+    // Check the subtree at `path`, whose keys must all fall strictly between `lower` and `upper`.
+    // Returns the number of entries in it.
+    fn check_node(
+        &self,
+        node: &Node<K, V, A>,
+        path: &mut Vec<usize>,
+        lower: Option<&K>,
+        upper: Option<&K>,
+    ) -> usize {
+        let len = node.len();
+        let depth = path.len() + 1;
+        let max_load = 2 * self.b - 1;
+        assert!(
+            len <= max_load,
+            "node at {:?} has {} keys, over the maximum of {}",
+            path,
+            len,
+            max_load
+        );
+        if depth > 1 {
+            let min_load = max_load / 2;
+            assert!(
+                len >= min_load,
+                "node at {:?} has {} keys, under the minimum of {}",
+                path,
+                len,
+                min_load
+            );
+        }
+        if node.is_leaf() {
+            assert_eq!(
+                depth, self.depth,
+                "leaf at {:?} is at the wrong depth",
+                path
+            );
+        } else {
+            assert!(
+                node.edge(len).is_some() && node.edge(len + 1).is_none(),
+                "node at {:?} doesn't have {} children",
+                path,
+                len + 1
+            );
+        }
+
+        let mut size = len;
+        let mut prev = lower;
+        for i in 0..=len {
+            let key = node.key(i);
+            if let Some(edge) = node.edge(i) {
+                path.push(i);
+                size += self.check_node(edge, path, prev, key.or(upper));
+                path.pop();
+            }
+            let Some(key) = key else { break };
+            if let Some(prev) = prev {
+                assert!(
+                    self.cmp.compare(prev, key) == Less,
+                    "key {:?} at {:?}[{}] isn't above {:?}",
+                    key,
+                    path,
+                    i,
+                    prev
+                );
+            }
+            prev = Some(key);
+        }
+        if let (Some(last), Some(upper)) = (prev, upper) {
+            assert!(
+                self.cmp.compare(last, upper) == Less,
+                "key {:?} in node at {:?} isn't below {:?}",
+                last,
+                path,
+                upper
+            );
+        }
+        assert_eq!(
+            node.size(),
+            size,
+            "node at {:?} has the wrong subtree size",
+            path
+        );
+        size
+    }
+}
+
+// Write `node` and everything below it, numbering nodes in pre-order. Returns the node's number.
+fn dump_node<K: fmt::Debug, V, A: Allocator, W: io::Write>(
+    node: &Node<K, V, A>,
+    w: &mut W,
+    next_id: &mut usize,
+) -> io::Result<usize> {
+    let id = *next_id;
+    *next_id += 1;
+
+    let mut label = String::new();
+    for i in 0..node.len() {
+        if !node.is_leaf() {
+            label.push_str(&format!("<e{}>|", i));
+        }
+        label.push_str(&escape(&format!("{:?}", node.key(i).unwrap())));
+        label.push('|');
+    }
+    if node.is_leaf() {
+        label.pop();
+    } else {
+        label.push_str(&format!("<e{}>", node.len()));
+    }
+    writeln!(w, "    n{} [label=\"{}\"];", id, label)?;
+
+    for i in 0..=node.len() {
+        if let Some(edge) = node.edge(i) {
+            let child = dump_node(edge, w, next_id)?;
+            writeln!(w, "    n{}:e{} -> n{};", id, i, child)?;
+        }
+    }
+    Ok(id)
+}
+
+// Escape the characters that mean something in a record label.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | '{' | '}' | '|' | '<' | '>' | ' ') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::BTreeMap;
+    use crate::test_util::Lcg;
+
+    #[test]
+    fn test_dump_dot() {
+        let mut out = Vec::new();
+        BTreeMap::<i32, ()>::new().dump_dot(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "digraph btree {\n    node [shape=record];\n    n0 [label=\"\"];\n}\n"
+        );
+
+        let mut map = BTreeMap::with_b(2);
+        for i in 1..=4 {
+            map.insert(i.to_string(), ());
+        }
+        let mut out = Vec::new();
+        map.dump_dot(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("n0 [label=\"<e0>|\\\"2\\\"|<e1>\"];"));
+        assert!(out.contains("n1 [label=\"\\\"1\\\"\"];"));
+        assert!(out.contains("n0:e1 -> n2;"));
+    }
+
+    #[test]
+    fn test_invariants_under_churn() {
+        for b in 2..6 {
+            let mut map = BTreeMap::with_b(b);
+            let mut rng = Lcg::new(1);
+            for i in 0..3000 {
+                let key = rng.below(500);
+                match i % 5 {
+                    0 | 1 => {
+                        map.remove(&key);
+                    }
+                    2 => {
+                        let mut cursor = map.lower_bound_mut(std::ops::Bound::Included(&key));
+                        cursor.remove_current();
+                    }
+                    _ => {
+                        map.insert(key, i);
+                    }
+                }
+                map.check_invariants();
+            }
+            BTreeMap::<i32, ()>::with_b(b).check_invariants();
+        }
+        for n in [0, 1, 2, 3, 50, 999] {
+            BTreeMap::from_sorted_iter((0..n).map(|i| (i, ()))).check_invariants();
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "map length doesn't match")]
+    fn test_invariants_catch_bad_length() {
+        let mut map: BTreeMap<_, _> = (0..10).map(|i| (i, ())).collect();
+        map.length += 1;
+        map.check_invariants();
+    }
+}
@@ -0,0 +1,182 @@
+use super::super::compare::Natural;
+use super::stack::SearchStack;
+use crate::myvec::{Allocator, Global};
+use std::{error::Error, fmt, mem};
+
+/// A view into an occupied entry in a BTreeMap.
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    pub(super) stack: SearchStack<'a, K, V, C, A>,
+}
+
+/// A view into a single entry of a BTreeMap, looked up by a borrowed key. Returned by
+/// `entry_ref`.
+pub enum EntryRef<'a, 'b, K: 'a, Q: ?Sized, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    Occupied(OccupiedEntry<'a, K, V, C, A>),
+    Vacant(VacantEntryRef<'a, 'b, K, Q, V, C, A>),
+}
+
+/// A view into a vacant entry of a BTreeMap, holding the borrowed key it was looked up with. The
+/// key is only turned into an owned `K` if a value gets inserted.
+pub struct VacantEntryRef<'a, 'b, K: 'a, Q: ?Sized, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    pub(super) key: &'b Q,
+    pub(super) stack: SearchStack<'a, K, V, C, A>,
+}
+
+/// The error returned by `try_insert` when the key is already in the map. It holds the occupied
+/// entry, and the value that was not inserted.
+pub struct OccupiedError<'a, K: 'a, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    pub entry: OccupiedEntry<'a, K, V, C, A>,
+    pub value: V,
+}
+
+impl<'a, K, V, C, A: Allocator> OccupiedEntry<'a, K, V, C, A> {
+    /// Gets a reference to the key in the entry.
+    pub fn key(&self) -> &K {
+        self.stack.peek_key()
+    }
+
+    /// Gets a reference to the value in the entry.
+    pub fn get(&self) -> &V {
+        self.stack.peek()
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    pub fn get_mut(&mut self) -> &mut V {
+        self.stack.peek_mut()
+    }
+
+    /// Converts the entry into a mutable reference to its value that lives as long as the map
+    /// borrow.
+    pub fn into_mut(self) -> &'a mut V {
+        self.stack.into_top()
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value of the entry out of the map, and returns it.
+    pub fn remove(self) -> V
+    where
+        A: Clone,
+    {
+        self.stack.remove().1
+    }
+
+    /// Takes the entry out of the map, and returns its key and value.
+    pub fn remove_entry(self) -> (K, V)
+    where
+        A: Clone,
+    {
+        self.stack.remove()
+    }
+}
+
+impl<'a, 'b, K, Q: ?Sized, V, C, A: Allocator + Clone> EntryRef<'a, 'b, K, Q, V, C, A> {
+    /// Inserts `default` if the entry is vacant, and returns a mutable reference to the value.
+    pub fn or_insert(self, default: V) -> &'a mut V
+    where
+        K: From<&'b Q>,
+    {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, and returns a mutable reference to
+    /// the value.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V
+    where
+        K: From<&'b Q>,
+    {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Inserts the default value if the entry is vacant, and returns a mutable reference to the
+    /// value.
+    pub fn or_default(self) -> &'a mut V
+    where
+        K: From<&'b Q>,
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Calls `f` on the value if the entry is occupied.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, 'b, K, Q: ?Sized, V, C, A: Allocator + Clone> VacantEntryRef<'a, 'b, K, Q, V, C, A> {
+    /// Gets the borrowed key the entry was looked up with.
+    pub fn key(&self) -> &'b Q {
+        self.key
+    }
+
+    /// Converts the key to a `K` and inserts it with `value`, returning a mutable reference to
+    /// the value.
+    pub fn insert(self, value: V) -> &'a mut V
+    where
+        K: From<&'b Q>,
+    {
+        self.stack.insert(K::from(self.key), value)
+    }
+}
+
+impl<K: fmt::Debug, Q: ?Sized + fmt::Debug, V: fmt::Debug, C, A: Allocator> fmt::Debug
+    for EntryRef<'_, '_, K, Q, V, C, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryRef::Occupied(entry) => f.debug_tuple("EntryRef").field(entry).finish(),
+            EntryRef::Vacant(entry) => f.debug_tuple("EntryRef").field(entry).finish(),
+        }
+    }
+}
+
+impl<K, Q: ?Sized + fmt::Debug, V, C, A: Allocator> fmt::Debug
+    for VacantEntryRef<'_, '_, K, Q, V, C, A>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VacantEntryRef").field(&self.key).finish()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C, A: Allocator> fmt::Debug for OccupiedEntry<'_, K, V, C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C, A: Allocator> fmt::Debug for OccupiedError<'_, K, V, C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("key", self.entry.key())
+            .field("old_value", self.entry.get())
+            .field("new_value", &self.value)
+            .finish()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C, A: Allocator> fmt::Display for OccupiedError<'_, K, V, C, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, key {:?} already exists with value {:?}",
+            self.value,
+            self.entry.key(),
+            self.entry.get(),
+        )
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C, A: Allocator> Error for OccupiedError<'_, K, V, C, A> {}
@@ -0,0 +1,337 @@
+use self::Pending::*;
+use super::super::compare::Natural;
+use super::super::inline_stack::InlineStack;
+use super::super::node::Node;
+use super::BTreeMap;
+use crate::myvec::{Allocator, Global};
+use std::{collections::VecDeque, iter::FusedIterator, marker::PhantomData};
+
+/// An iterator over the entries of a BTreeMap, in key order.
+pub struct Iter<'a, K, V, A: Allocator = Global> {
+    // The paths to the next entry from the front and from the back. Each item is a node and a
+    // position in it: the index of the next key to yield going forwards, or one past the next key
+    // to yield going backwards. The top of each stack is the node holding that end's next entry.
+    front: InlineStack<(&'a Node<K, V, A>, usize)>,
+    back: InlineStack<(&'a Node<K, V, A>, usize)>,
+    // The two ends don't know about each other, so this is what stops them crossing.
+    length: usize,
+}
+
+impl<'a, K, V, A: Allocator> Iter<'a, K, V, A> {
+    pub(super) fn new(root: &'a Node<K, V, A>, length: usize) -> Iter<'a, K, V, A> {
+        let mut iter = Iter {
+            front: InlineStack::new(),
+            back: InlineStack::new(),
+            length,
+        };
+        iter.descend_front(root);
+        iter.descend_back(root);
+        iter
+    }
+
+    // Push the path from `node` down to its leftmost leaf.
+    fn descend_front(&mut self, mut node: &'a Node<K, V, A>) {
+        loop {
+            self.front.push((node, 0));
+            match node.edge(0) {
+                Some(next) => node = next,
+                None => return,
+            }
+        }
+    }
+
+    // Push the path from `node` down to its rightmost leaf.
+    fn descend_back(&mut self, mut node: &'a Node<K, V, A>) {
+        loop {
+            self.back.push((node, node.len()));
+            match node.edge(node.len()) {
+                Some(next) => node = next,
+                None => return,
+            }
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for Iter<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.length == 0 {
+            return None;
+        }
+        while let Some(&mut (node, ref mut index)) = self.front.last_mut() {
+            let i = *index;
+            if i < node.len() {
+                *index += 1;
+                // Everything in the right subtree of this key comes before the next key.
+                if let Some(edge) = node.edge(i + 1) {
+                    self.descend_front(edge);
+                }
+                self.length -= 1;
+                return node.key(i).zip(node.val(i));
+            }
+            // This node is exhausted, go back up to the parent.
+            self.front.pop();
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<'a, K, V, A: Allocator> DoubleEndedIterator for Iter<'a, K, V, A> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        if self.length == 0 {
+            return None;
+        }
+        while let Some(&mut (node, ref mut index)) = self.back.last_mut() {
+            if *index > 0 {
+                *index -= 1;
+                let i = *index;
+                // Everything in the left subtree of this key comes after the previous key.
+                if let Some(edge) = node.edge(i) {
+                    self.descend_back(edge);
+                }
+                self.length -= 1;
+                return node.key(i).zip(node.val(i));
+            }
+            self.back.pop();
+        }
+        None
+    }
+}
+
+impl<K, V, A: Allocator> ExactSizeIterator for Iter<'_, K, V, A> {}
+
+impl<K, V, A: Allocator> FusedIterator for Iter<'_, K, V, A> {}
+
+/// An iterator over the entries of a BTreeMap in runs, as the slices of keys and values the nodes
+/// store them in. Each leaf comes out whole; the entries between leaves live in the internal nodes
+/// and come out one at a time, as chunks of length 1. Put together, the chunks hold every entry in
+/// key order.
+pub struct LeafChunks<'a, K, V, A: Allocator = Global> {
+    // The path to the next chunk, as in `Iter::front`.
+    stack: InlineStack<(&'a Node<K, V, A>, usize)>,
+}
+
+impl<'a, K, V, A: Allocator> LeafChunks<'a, K, V, A> {
+    pub(super) fn new(root: &'a Node<K, V, A>) -> LeafChunks<'a, K, V, A> {
+        let mut chunks = LeafChunks {
+            stack: InlineStack::new(),
+        };
+        if root.len() > 0 {
+            chunks.descend(root);
+        }
+        chunks
+    }
+
+    fn descend(&mut self, mut node: &'a Node<K, V, A>) {
+        loop {
+            self.stack.push((node, 0));
+            match node.edge(0) {
+                Some(next) => node = next,
+                None => return,
+            }
+        }
+    }
+}
+
+impl<'a, K, V, A: Allocator> Iterator for LeafChunks<'a, K, V, A> {
+    type Item = (&'a [K], &'a [V]);
+
+    fn next(&mut self) -> Option<(&'a [K], &'a [V])> {
+        while let Some(&mut (node, ref mut index)) = self.stack.last_mut() {
+            if node.is_leaf() {
+                self.stack.pop();
+                return Some((node.keys(), node.vals()));
+            }
+            let i = *index;
+            if i < node.len() {
+                *index += 1;
+                if let Some(edge) = node.edge(i + 1) {
+                    self.descend(edge);
+                }
+                return Some((&node.keys()[i..=i], &node.vals()[i..=i]));
+            }
+            self.stack.pop();
+        }
+        None
+    }
+}
+
+impl<K, V, A: Allocator> FusedIterator for LeafChunks<'_, K, V, A> {}
+
+/// An owning iterator over the entries of a BTreeMap, in key order.
+pub struct IntoIter<K, V, A: Allocator = Global> {
+    // What's left of the map, in order, as a mix of loose entries and whole subtrees. A subtree is
+    // only unpacked once one of the ends reaches it.
+    pending: VecDeque<Pending<K, V, A>>,
+    length: usize,
+}
+
+enum Pending<K, V, A: Allocator> {
+    Entry(K, V),
+    Subtree(Node<K, V, A>),
+}
+
+impl<K, V, A: Allocator> IntoIter<K, V, A> {
+    pub(super) fn new(root: Node<K, V, A>, length: usize) -> IntoIter<K, V, A> {
+        let mut pending = VecDeque::new();
+        pending.push_back(Subtree(root));
+        IntoIter { pending, length }
+    }
+
+    // Replace a subtree taken off the front with its contents, pushed in reverse so that they come
+    // back out in order.
+    fn unpack_front(&mut self, node: Node<K, V, A>) {
+        let (keys, vals, edges) = node.into_parts();
+        let mut edges = edges.into_iter().rev();
+        if let Some(edge) = edges.next() {
+            self.pending.push_front(Subtree(edge));
+        }
+        for (key, val) in keys.into_iter().zip(vals).rev() {
+            self.pending.push_front(Entry(key, val));
+            if let Some(edge) = edges.next() {
+                self.pending.push_front(Subtree(edge));
+            }
+        }
+    }
+
+    // Replace a subtree taken off the back with its contents.
+    fn unpack_back(&mut self, node: Node<K, V, A>) {
+        let (keys, vals, edges) = node.into_parts();
+        let mut edges = edges.into_iter();
+        if let Some(edge) = edges.next() {
+            self.pending.push_back(Subtree(edge));
+        }
+        for (key, val) in keys.into_iter().zip(vals) {
+            self.pending.push_back(Entry(key, val));
+            if let Some(edge) = edges.next() {
+                self.pending.push_back(Subtree(edge));
+            }
+        }
+    }
+}
+
+impl<K, V, A: Allocator> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            match self.pending.pop_front()? {
+                Entry(key, val) => {
+                    self.length -= 1;
+                    return Some((key, val));
+                }
+                Subtree(node) => self.unpack_front(node),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<K, V, A: Allocator> DoubleEndedIterator for IntoIter<K, V, A> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        loop {
+            match self.pending.pop_back()? {
+                Entry(key, val) => {
+                    self.length -= 1;
+                    return Some((key, val));
+                }
+                Subtree(node) => self.unpack_back(node),
+            }
+        }
+    }
+}
+
+impl<K, V, A: Allocator> ExactSizeIterator for IntoIter<K, V, A> {}
+
+impl<K, V, A: Allocator> FusedIterator for IntoIter<K, V, A> {}
+
+/// An owning iterator over the keys of a BTreeMap, in order.
+pub struct IntoKeys<K, V, A: Allocator = Global> {
+    pub(super) inner: IntoIter<K, V, A>,
+}
+
+impl<K, V, A: Allocator> Iterator for IntoKeys<K, V, A> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, A: Allocator> DoubleEndedIterator for IntoKeys<K, V, A> {
+    fn next_back(&mut self) -> Option<K> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, V, A: Allocator> ExactSizeIterator for IntoKeys<K, V, A> {}
+
+impl<K, V, A: Allocator> FusedIterator for IntoKeys<K, V, A> {}
+
+/// An owning iterator over the values of a BTreeMap, in key order.
+pub struct IntoValues<K, V, A: Allocator = Global> {
+    pub(super) inner: IntoIter<K, V, A>,
+}
+
+impl<K, V, A: Allocator> Iterator for IntoValues<K, V, A> {
+    type Item = V;
+
+    fn next(&mut self) -> Option<V> {
+        self.inner.next().map(|(_, val)| val)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, A: Allocator> DoubleEndedIterator for IntoValues<K, V, A> {
+    fn next_back(&mut self) -> Option<V> {
+        self.inner.next_back().map(|(_, val)| val)
+    }
+}
+
+impl<K, V, A: Allocator> ExactSizeIterator for IntoValues<K, V, A> {}
+
+impl<K, V, A: Allocator> FusedIterator for IntoValues<K, V, A> {}
+
+/// A draining iterator over the entries of a BTreeMap, in key order. The map is already empty
+/// while this is alive, and whatever isn't consumed is dropped along with it.
+pub struct Drain<'a, K: 'a, V: 'a, C: 'a = Natural, A: Allocator = Global> {
+    pub(super) inner: IntoIter<K, V, A>,
+    pub(super) marker: PhantomData<&'a mut BTreeMap<K, V, C, A>>,
+}
+
+impl<K, V, C, A: Allocator> Iterator for Drain<'_, K, V, C, A> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V, C, A: Allocator> DoubleEndedIterator for Drain<'_, K, V, C, A> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        self.inner.next_back()
+    }
+}
+
+impl<K, V, C, A: Allocator> ExactSizeIterator for Drain<'_, K, V, C, A> {}
+
+impl<K, V, C, A: Allocator> FusedIterator for Drain<'_, K, V, C, A> {}
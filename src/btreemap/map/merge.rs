@@ -0,0 +1,202 @@
+use super::super::compare::Comparator;
+use super::{BTreeMap, Iter};
+use crate::myvec::{Allocator, Global};
+use std::{
+    cmp::{self, Ordering::*},
+    iter::{FusedIterator, Peekable},
+};
+
+/// Where a key of a [`Union`] was found, with its values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Merged<'a, V> {
+    Left(&'a V),
+    Right(&'a V),
+    Both(&'a V, &'a V),
+}
+
+/// An iterator over the keys of either of two maps, in order. Made by [`BTreeMap::union`].
+pub struct Union<'a, K, V, C, A: Allocator = Global> {
+    left: Peekable<Iter<'a, K, V, A>>,
+    right: Peekable<Iter<'a, K, V, A>>,
+    cmp: &'a C,
+}
+
+/// An iterator over the keys found in both of two maps, in order. Made by
+/// [`BTreeMap::intersection`].
+pub struct Intersection<'a, K, V, C, A: Allocator = Global> {
+    left: Iter<'a, K, V, A>,
+    right: Peekable<Iter<'a, K, V, A>>,
+    cmp: &'a C,
+}
+
+/// An iterator over the keys of one map that aren't in another, in order. Made by
+/// [`BTreeMap::difference`].
+pub struct Difference<'a, K, V, C, A: Allocator = Global> {
+    left: Iter<'a, K, V, A>,
+    right: Peekable<Iter<'a, K, V, A>>,
+    cmp: &'a C,
+}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> BTreeMap<K, V, C, A> {
+    /// Walks the keys of both maps together, in order, yielding each key once with where it was
+    /// found. Keys in both maps come with both values, and the key from `self`.
+    ///
+    /// The maps are compared with `self`'s comparator.
+    pub fn union<'a>(&'a self, other: &'a BTreeMap<K, V, C, A>) -> Union<'a, K, V, C, A> {
+        Union {
+            left: self.iter().peekable(),
+            right: other.iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+
+    /// Walks the keys found in both maps, in order, with the value from each.
+    pub fn intersection<'a>(
+        &'a self,
+        other: &'a BTreeMap<K, V, C, A>,
+    ) -> Intersection<'a, K, V, C, A> {
+        Intersection {
+            left: self.iter(),
+            right: other.iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+
+    /// Walks the entries of `self` whose keys aren't in `other`, in order.
+    pub fn difference<'a>(&'a self, other: &'a BTreeMap<K, V, C, A>) -> Difference<'a, K, V, C, A> {
+        Difference {
+            left: self.iter(),
+            right: other.iter().peekable(),
+            cmp: &self.cmp,
+        }
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>, A: Allocator + Clone> Iterator for Union<'a, K, V, C, A> {
+    type Item = (&'a K, Merged<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let order = match (self.left.peek(), self.right.peek()) {
+            (Some(&(l, _)), Some(&(r, _))) => self.cmp.compare(l, r),
+            (Some(_), None) => Less,
+            (None, Some(_)) => Greater,
+            (None, None) => return None,
+        };
+        match order {
+            Less => self.left.next().map(|(k, v)| (k, Merged::Left(v))),
+            Greater => self.right.next().map(|(k, v)| (k, Merged::Right(v))),
+            Equal => {
+                let (k, l) = self.left.next()?;
+                let (_, r) = self.right.next()?;
+                Some((k, Merged::Both(l, r)))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left, right) = (self.left.len(), self.right.len());
+        (cmp::max(left, right), left.checked_add(right))
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>, A: Allocator + Clone> Iterator for Intersection<'a, K, V, C, A> {
+    type Item = (&'a K, &'a V, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (mut key, mut val) = self.left.next()?;
+        loop {
+            let &(other, _) = self.right.peek()?;
+            match self.cmp.compare(key, other) {
+                Less => (key, val) = self.left.next()?,
+                Greater => {
+                    self.right.next();
+                }
+                Equal => {
+                    let (_, other_val) = self.right.next()?;
+                    return Some((key, val, other_val));
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(cmp::min(self.left.len(), self.right.len())))
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>, A: Allocator + Clone> Iterator for Difference<'a, K, V, C, A> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, val) = self.left.next()?;
+            loop {
+                let Some(&(other, _)) = self.right.peek() else {
+                    return Some((key, val));
+                };
+                match self.cmp.compare(key, other) {
+                    Less => return Some((key, val)),
+                    Greater => {
+                        self.right.next();
+                    }
+                    Equal => {
+                        self.right.next();
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.left.len();
+        (left.saturating_sub(self.right.len()), Some(left))
+    }
+}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> FusedIterator for Union<'_, K, V, C, A> {}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> FusedIterator for Intersection<'_, K, V, C, A> {}
+
+impl<K, V, C: Comparator<K>, A: Allocator + Clone> FusedIterator for Difference<'_, K, V, C, A> {}
+
+#[cfg(test)]
+mod test {
+    use super::super::BTreeMap;
+    use super::Merged::*;
+
+    #[test]
+    fn test_merge_iters() {
+        let left: BTreeMap<i32, i32> = (0..100).step_by(2).map(|i| (i, i)).collect();
+        let right: BTreeMap<i32, i32> = (0..100).step_by(3).map(|i| (i, -i)).collect();
+
+        let union: Vec<_> = left.union(&right).collect();
+        assert_eq!(union.len(), 67);
+        assert!(union.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(union[0], (&0, Both(&0, &0)));
+        assert_eq!(union[1], (&2, Left(&2)));
+        assert_eq!(union[2], (&3, Right(&-3)));
+
+        let both: Vec<_> = left.intersection(&right).collect();
+        let expected: Vec<_> = (0..100).step_by(6).collect();
+        assert!(both
+            .iter()
+            .map(|&(k, _, _)| *k)
+            .eq(expected.iter().copied()));
+        assert!(both.iter().all(|&(k, l, r)| *l == *k && *r == -*k));
+
+        let only_left: Vec<_> = left.difference(&right).map(|(k, _)| *k).collect();
+        let expected: Vec<_> = (0..100).filter(|i| i % 2 == 0 && i % 3 != 0).collect();
+        assert_eq!(only_left, expected);
+        let only_right: Vec<_> = right.difference(&left).map(|(k, _)| *k).collect();
+        let expected: Vec<_> = (0..100).filter(|i| i % 3 == 0 && i % 2 != 0).collect();
+        assert_eq!(only_right, expected);
+
+        let empty = BTreeMap::new();
+        assert_eq!(left.union(&empty).count(), 50);
+        assert_eq!(empty.union(&left).count(), 50);
+        assert_eq!(left.intersection(&empty).next(), None);
+        assert_eq!(left.difference(&empty).count(), 50);
+        assert_eq!(empty.difference(&left).next(), None);
+    }
+}
@@ -1,212 +1,295 @@
-use self::PushResult::*;
-use super::super::node::{InsertionResult::*, SearchResult::*};
-use super::{BTreeMap, Node};
-
-type StackItem<K, V> = (*mut Node<K, V>, usize);
-type Stack<K, V> = Vec<StackItem<K, V>>;
-
-/// A partitialsearchstack handles the construction of a search stack.
-pub struct PartialSearchStack<'a, K: 'a + Ord, V: 'a> {
-    map: &'a mut BTreeMap<K, V>,
-    stack: Stack<K, V>,
-    next: *mut Node<K, V>,
-}
-
-/// A search stack represents a full path to an element of interest. It provides methods for manipulating the element at the top of its stack.
-pub struct SearchStack<'a, K: 'a + Ord, V: 'a> {
-    map: &'a mut BTreeMap<K, V>,
-    stack: Stack<K, V>,
-    top: StackItem<K, V>,
-}
-
-impl<'a, K, V> PartialSearchStack<'a, K, V>
-where
-    K: Ord,
-{
-    pub fn new(map: &mut BTreeMap<K, V>) -> PartialSearchStack<K, V> {
-        let depth = map.depth;
-        let next = &mut map.root as *mut _;
-
-        PartialSearchStack {
-            map,
-            stack: Vec::with_capacity(depth),
-            next,
-        }
-    }
-
-    pub fn next(&self) -> &Node<K, V> {
-        unsafe { &*self.next }
-    }
-
-    pub fn into_next(mut self) -> &'a mut Node<K, V> {
-        unsafe { &mut *self.next }
-    }
-
-    // Transform self to SearchStack
-    pub fn seal(self, index: usize) -> SearchStack<'a, K, V> {
-        SearchStack {
-            map: self.map,
-            stack: self.stack,
-            top: (self.next as *mut _, index),
-        }
-    }
-
-    // Pushes the requested child of the stack's current top on top of the stack. If the child exists, then a new PartialSearchStack is yielded. Otherwise, a full SearchStack is yielded.
-    pub fn push(self, edge: usize) -> PushResult<'a, K, V> {
-        let map = self.map;
-        let mut stack = self.stack;
-        let next_ptr = self.next;
-        let next_node = unsafe {
-            &mut *next_ptr
-        };
-        let to_insert = (next_ptr, edge);
-        match next_node.edge_mut(edge)  {
-            None => Done(SearchStack {
-                map,
-                stack,
-                top: to_insert,
-            }),
-            Some(node) => {
-                stack.push(to_insert);
-                Grew(PartialSearchStack {
-                    map,
-                    stack,
-                    next: node as *mut _,
-                })
-            }
-        }
-    }
-}
-
-pub enum PushResult<'a, K: 'a + Ord, V: 'a> {
-    Grew(PartialSearchStack<'a, K, V>),
-    Done(SearchStack<'a, K, V>),
-}
-
-impl<'a, K, V> SearchStack<'a, K, V>
-where
-    K: Ord,
-{
-    pub fn peek(&self) -> &V {
-        let (leaf_ptr, index) = self.top;
-        unsafe { (*leaf_ptr).unsafe_val_mut(index) }
-    }
-
-    pub fn peek_mut(&mut self) -> &mut V {
-        let (leaf_ptr, index) = self.top;
-        unsafe { (*leaf_ptr).unsafe_val_mut(index) }
-    }
-
-    pub fn into_top(self) -> &'a mut V {
-        let (leaf_ptr, index) = self.top;
-        unsafe { (*leaf_ptr).unsafe_val_mut(index) }
-    }
-
-    pub fn insert(self, key: K, val: V) -> &'a mut V {
-        let map = self.map;
-        map.length += 1;
-
-        let mut stack = self.stack;
-        let (node_ptr, index) = self.top;
-        let (mut insertion, inserted_ptr) = unsafe { (*node_ptr).insert_as_leaf(index, key, val) };
-
-        loop {
-            match insertion {
-                Fit => unsafe {
-                    return &mut *inserted_ptr;
-                },
-                Split(key, val, right) => match stack.pop() {
-                    // The last insertion triggered a split, so get the next element on the stack to recursively insert the split node into.
-                    None => {
-                        // The stack was empty, we've split to the root node.
-                        Node::make_internal_root(&mut map.root, map.b, key, val, right);
-                        map.depth += 1;
-                        return unsafe { &mut *inserted_ptr };
-                    }
-                    Some((node, index)) => {
-                        insertion = unsafe { (*node).insert_as_internal(index, key, val, right) };
-                    }
-                },
-            }
-        }
-    }
-
-    // Remove 'top' and handle underflow
-    pub fn remove(mut self) -> V {
-        self.leafify();
-
-        let mut stack = self.stack;
-        let map = self.map;
-        map.length -= 1;
-        // remove the kv pair the SearchStack points to.
-        let (value, mut underflow) = unsafe {
-            let (leaf_ptr, index) = self.top;
-            let leaf = &mut *leaf_ptr;
-            let (_key, value) = leaf.remove_as_leaf(index);
-            (value, leaf.is_underfull())
-        };
-
-        loop {
-            match stack.pop() {
-                None => {
-                    // Now we reached the root.
-                    if map.root.len() == 0 && !map.root.is_leaf() {
-                        map.depth -= 1;
-                        map.root = map.root.pop_edge().unwrap();
-                    }
-                    return value;
-                }
-                Some((parent_ptr, index)) => {
-                    if underflow {
-                        let parent = unsafe { &mut *parent_ptr };
-                        parent.handle_underflow(index);
-                        underflow = parent.is_underfull();
-                    } else {
-                        // All done!
-                        return value;
-                    }
-                }
-            }
-        }
-    }
-}
-
-impl<'a, K, V> SearchStack<'a, K, V>
-where
-    K: Ord,
-{
-    fn leafify(&mut self) {
-        let (node_ptr, index) = self.top;
-        let node = unsafe { &mut *node_ptr };
-        let (key_ptr, val_ptr) = unsafe {
-            (
-                (*node_ptr).unsafe_key_mut(index),
-                (*node_ptr).unsafe_val_mut(index),
-            )
-        };
-
-        match node.edge_mut(index + 1) {
-            Some(mut temp_node) => {
-                // we're not at proper leaf node
-                self.stack.push((node_ptr, index + 1));
-                loop {
-                    let node = temp_node;
-                    let node_ptr = node as *mut _;
-                    if node.is_leaf() {
-                        self.top = (node_ptr, 0);
-                        unsafe { node.unsafe_swap(0, &mut *key_ptr, &mut *val_ptr) };
-                        break;
-                    } else {
-                        // This node is internal, go deeper.
-                        self.stack.push((node_ptr, 0));
-                        temp_node = unsafe { node.unsafe_edge_mut(0) };
-                    }
-                }
-            }
-            None => {
-                // we're at a proper leaf node, nothing to do.
-            }
-        }
-    }
-}
-
+use self::PushResult::*;
+use super::super::node::{InsertionResult::*, SearchResult::*};
+use super::{BTreeMap, Node};
+use crate::myvec::Allocator;
+use std::mem;
+
+// The pointers on a stack lie along one path down from the root, and the stack holds the map's
+// `&mut` for as long as it lives, so nothing else can touch the nodes. A child lives in a slot of
+// its parent's edge buffer, so a pointer to it goes stale once that buffer shifts. Inserts and
+// removes only ever change the buffers of the node at the top and its children, then pop it, so
+// every pointer still on the stack points at a node that hasn't moved.
+pub type StackItem<K, V, A> = (*mut Node<K, V, A>, usize);
+pub type Stack<K, V, A> = Vec<StackItem<K, V, A>>;
+
+/// A search stack's buffer, parked on the map between operations so that inserts and removes
+/// don't allocate a fresh one every time.
+pub struct Scratch<K, V, A: Allocator>(Stack<K, V, A>);
+
+impl<K, V, A: Allocator> Scratch<K, V, A> {
+    pub fn new() -> Scratch<K, V, A> {
+        Scratch(Vec::new())
+    }
+
+    fn take(&mut self) -> Stack<K, V, A> {
+        mem::take(&mut self.0)
+    }
+
+    fn park(&mut self, mut stack: Stack<K, V, A>) {
+        stack.clear();
+        self.0 = stack;
+    }
+}
+
+// A parked buffer is always empty, so it never holds on to pointers into the tree.
+unsafe impl<K: Send, V: Send, A: Allocator + Send> Send for Scratch<K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: Allocator + Sync> Sync for Scratch<K, V, A> {}
+
+/// A partitialsearchstack handles the construction of a search stack.
+pub struct PartialSearchStack<'a, K: 'a, V: 'a, C: 'a, A: Allocator> {
+    map: &'a mut BTreeMap<K, V, C, A>,
+    stack: Stack<K, V, A>,
+    next: *mut Node<K, V, A>,
+}
+
+/// A search stack represents a full path to an element of interest. It provides methods for manipulating the element at the top of its stack.
+pub struct SearchStack<'a, K: 'a, V: 'a, C: 'a, A: Allocator> {
+    map: &'a mut BTreeMap<K, V, C, A>,
+    stack: Stack<K, V, A>,
+    top: StackItem<K, V, A>,
+}
+
+impl<'a, K, V, C, A: Allocator> PartialSearchStack<'a, K, V, C, A> {
+    pub fn new(map: &mut BTreeMap<K, V, C, A>) -> PartialSearchStack<'_, K, V, C, A> {
+        let mut stack = map.scratch.take();
+        stack.reserve(map.depth);
+        let next = &mut map.root as *mut _;
+
+        PartialSearchStack { map, stack, next }
+    }
+
+    pub fn next(&self) -> &Node<K, V, A> {
+        unsafe { &*self.next }
+    }
+
+    pub fn cmp(&self) -> &C {
+        &self.map.cmp
+    }
+
+    pub fn into_next(self) -> &'a mut Node<K, V, A> {
+        self.map.scratch.park(self.stack);
+        unsafe { &mut *self.next }
+    }
+
+    // Transform self to SearchStack
+    pub fn seal(self, index: usize) -> SearchStack<'a, K, V, C, A> {
+        SearchStack {
+            map: self.map,
+            stack: self.stack,
+            top: (self.next as *mut _, index),
+        }
+    }
+
+    // Pushes the requested child of the stack's current top on top of the stack. If the child exists, then a new PartialSearchStack is yielded. Otherwise, a full SearchStack is yielded.
+    pub fn push(self, edge: usize) -> PushResult<'a, K, V, C, A> {
+        let map = self.map;
+        let mut stack = self.stack;
+        let next_ptr = self.next;
+        let next_node = unsafe {
+            &mut *next_ptr
+        };
+        let to_insert = (next_ptr, edge);
+        match next_node.edge_mut(edge)  {
+            None => Done(SearchStack {
+                map,
+                stack,
+                top: to_insert,
+            }),
+            Some(node) => {
+                stack.push(to_insert);
+                Grew(PartialSearchStack {
+                    map,
+                    stack,
+                    next: node as *mut _,
+                })
+            }
+        }
+    }
+}
+
+pub enum PushResult<'a, K: 'a, V: 'a, C: 'a, A: Allocator> {
+    Grew(PartialSearchStack<'a, K, V, C, A>),
+    Done(SearchStack<'a, K, V, C, A>),
+}
+
+impl<'a, K, V, C, A: Allocator> SearchStack<'a, K, V, C, A> {
+    // Build a search stack from a path found elsewhere, such as by a cursor. `stack` must hold the
+    // ancestors of `top` with the edge taken down from each of them.
+    pub fn from_parts(
+        map: &'a mut BTreeMap<K, V, C, A>,
+        stack: Stack<K, V, A>,
+        top: StackItem<K, V, A>,
+    ) -> SearchStack<'a, K, V, C, A> {
+        SearchStack { map, stack, top }
+    }
+
+    pub fn peek_key(&self) -> &K {
+        let (leaf_ptr, index) = self.top;
+        unsafe { (*leaf_ptr).unsafe_key_mut(index) }
+    }
+
+    pub fn peek(&self) -> &V {
+        let (leaf_ptr, index) = self.top;
+        unsafe { (*leaf_ptr).unsafe_val_mut(index) }
+    }
+
+    pub fn peek_mut(&mut self) -> &mut V {
+        let (leaf_ptr, index) = self.top;
+        unsafe { (*leaf_ptr).unsafe_val_mut(index) }
+    }
+
+    pub fn into_top(self) -> &'a mut V {
+        let (leaf_ptr, index) = self.top;
+        self.map.scratch.park(self.stack);
+        unsafe { (*leaf_ptr).unsafe_val_mut(index) }
+    }
+
+    // Give the stack back to the map without doing anything with it.
+    pub fn discard(self) {
+        self.map.scratch.park(self.stack);
+    }
+}
+
+impl<'a, K, V, C, A: Allocator + Clone> SearchStack<'a, K, V, C, A> {
+    pub fn insert(self, key: K, val: V) -> &'a mut V {
+        self.insert_inner(key, val, false).1
+    }
+
+    // Like `insert`, but also hand back the key in its final place. If no node had to split, the
+    // path is still good and the stack comes back too, so the caller can keep using it.
+    pub fn insert_entry(self, key: K, val: V) -> (&'a K, &'a mut V, Option<Stack<K, V, A>>) {
+        self.insert_inner(key, val, true)
+    }
+
+    fn insert_inner(
+        self,
+        key: K,
+        val: V,
+        keep_stack: bool,
+    ) -> (&'a K, &'a mut V, Option<Stack<K, V, A>>) {
+        let map = self.map;
+        map.length += 1;
+
+        let mut stack = self.stack;
+        let depth = stack.len();
+        let (node_ptr, index) = self.top;
+        let (mut insertion, (key_ptr, val_ptr)) =
+            unsafe { (*node_ptr).insert_as_leaf(index, key, val, &mut map.pool) };
+
+        loop {
+            match insertion {
+                Fit => unsafe {
+                    // Nothing further up changes shape, but they all hold one more entry now.
+                    for &(node, _) in &stack {
+                        (*node).inc_size();
+                    }
+                    if keep_stack && stack.len() == depth {
+                        return (&*key_ptr, &mut *val_ptr, Some(stack));
+                    }
+                    map.scratch.park(stack);
+                    return (&*key_ptr, &mut *val_ptr, None);
+                },
+                Split(key, val, right) => match stack.pop() {
+                    // The last insertion triggered a split, so get the next element on the stack to recursively insert the split node into.
+                    None => {
+                        // The stack was empty, we've split to the root node.
+                        Node::make_internal_root(
+                            &mut map.root,
+                            map.b,
+                            key,
+                            val,
+                            right,
+                            &mut map.pool,
+                        );
+                        map.depth += 1;
+                        map.scratch.park(stack);
+                        return unsafe { (&*key_ptr, &mut *val_ptr, None) };
+                    }
+                    Some((node, index)) => {
+                        insertion = unsafe {
+                            (*node).insert_as_internal(index, key, val, right, &mut map.pool)
+                        };
+                    }
+                },
+            }
+        }
+    }
+
+    // Remove 'top' and handle underflow
+    pub fn remove(mut self) -> (K, V) {
+        self.leafify();
+
+        let mut stack = self.stack;
+        let map = self.map;
+        map.length -= 1;
+        // remove the kv pair the SearchStack points to.
+        let (entry, mut underflow) = unsafe {
+            let (leaf_ptr, index) = self.top;
+            let leaf = &mut *leaf_ptr;
+            let entry = leaf.remove_as_leaf(index);
+            (entry, leaf.is_underfull())
+        };
+
+        loop {
+            match stack.pop() {
+                None => {
+                    // Now we reached the root.
+                    if map.root.len() == 0 && !map.root.is_leaf() {
+                        map.depth -= 1;
+                        let child = map.root.pop_edge().unwrap();
+                        let old_root = mem::replace(&mut map.root, child);
+                        map.pool.put(old_root);
+                    }
+                    map.scratch.park(stack);
+                    return entry;
+                }
+                Some((parent_ptr, index)) => {
+                    // Every ancestor loses an entry, even once the underflow has been dealt with.
+                    let parent = unsafe { &mut *parent_ptr };
+                    parent.dec_size();
+                    if underflow {
+                        parent.handle_underflow(index, &mut map.pool);
+                        underflow = parent.is_underfull();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, K, V, C, A: Allocator + Clone> SearchStack<'a, K, V, C, A> {
+    fn leafify(&mut self) {
+        let (node_ptr, index) = self.top;
+        let node = unsafe { &mut *node_ptr };
+        let (key_ptr, val_ptr) = unsafe {
+            (
+                (*node_ptr).unsafe_key_mut(index),
+                (*node_ptr).unsafe_val_mut(index),
+            )
+        };
+
+        match node.edge_mut(index + 1) {
+            Some(mut temp_node) => {
+                // we're not at proper leaf node
+                self.stack.push((node_ptr, index + 1));
+                loop {
+                    let node = temp_node;
+                    let node_ptr = node as *mut _;
+                    if node.is_leaf() {
+                        self.top = (node_ptr, 0);
+                        unsafe { node.unsafe_swap(0, &mut *key_ptr, &mut *val_ptr) };
+                        break;
+                    } else {
+                        // This node is internal, go deeper.
+                        self.stack.push((node_ptr, 0));
+                        temp_node = unsafe { node.unsafe_edge_mut(0) };
+                    }
+                }
+            }
+            None => {
+                // we're at a proper leaf node, nothing to do.
+            }
+        }
+    }
+}
+
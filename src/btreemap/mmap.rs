@@ -0,0 +1,466 @@
+use super::compare::{Comparator, Natural};
+use super::map::{BTreeMap, Decode, Encode};
+use memmap2::Mmap;
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    marker::PhantomData,
+    path::Path,
+};
+
+// The file is a run of fixed-size pages. Page 0 is the header; the tree's pages follow with the
+// leaves first, in key order, and then each level of internal pages up to the root.
+const PAGE_SIZE: usize = 4096;
+const MAGIC: &[u8; 4] = b"TCBP";
+const VERSION: u8 = 1;
+
+// Every tree page starts with its kind and the number of entries it holds, as a u16. A leaf then
+// has its entries, each a key followed by its value. An internal page has the page number of its
+// first child, and then for each other child the child's first key and its page number.
+const LEAF: u8 = 0;
+const INTERNAL: u8 = 1;
+const PAGE_HEADER_LEN: usize = 3;
+const CHILD_LEN: usize = 8;
+
+/// A read-only ordered map that lives in a file, built from a BTreeMap by `flush`.
+///
+/// The file holds a B+Tree laid out in fixed-size pages, and `open` maps it into memory rather
+/// than reading it in, so a large index is ready to use straight after a restart. Lookups only
+/// decode the pages on their path. `load` reads the whole thing back into a BTreeMap when it needs
+/// to change.
+///
+/// The file doesn't record the order its keys are in, so it must be read with the same comparator
+/// `C` as the map it was flushed from. `open` makes one with `C::default()`, and
+/// `open_with_comparator` takes one for comparators without a default, like closures.
+pub struct MmapBTreeMap<K, V, C = Natural> {
+    mmap: Mmap,
+    length: usize,
+    // The root page, which is 0 (the header) when the map is empty.
+    root: u64,
+    depth: usize,
+    leaves: u64,
+    cmp: C,
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K: Encode, V: Encode, C: Comparator<K>> MmapBTreeMap<K, V, C> {
+    /// Writes `map` out to a file at `path`, replacing anything there. The file is written under a
+    /// temporary name and only renamed into place once it's complete and synced, so a crash
+    /// partway through leaves any old file whole.
+    ///
+    /// Returns an `InvalidInput` error if an entry is too big to fit in a page, or a key too big for
+    /// two to fit in an internal page along with their page numbers.
+    pub fn flush<P: AsRef<Path>>(map: &BTreeMap<K, V, C>, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temp_name = path.file_name().map_or_else(OsString::new, OsString::from);
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+
+        let result = Self::write_file(map, &temp_path).and_then(|()| fs::rename(&temp_path, path));
+        if result.is_err() {
+            // Whatever went wrong, don't leave a partly written file lying around.
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    // Write the whole file at `path` and sync it.
+    fn write_file(map: &BTreeMap<K, V, C>, path: &Path) -> io::Result<()> {
+        let mut out = PageWriter {
+            out: BufWriter::new(File::create(path)?),
+            pages: 0,
+        };
+        out.write_page(&[])?;
+
+        // Pack the leaves, keeping the first key and page number of each for the level above.
+        let mut level = Vec::new();
+        let mut page = PageBuilder::new(LEAF);
+        let mut key_bytes = Vec::new();
+        for (key, value) in map.iter() {
+            key_bytes.clear();
+            key.encode(&mut key_bytes)?;
+            // Internal pages need room for at least two children, or the levels above the leaves
+            // would never narrow down to a root.
+            if PAGE_HEADER_LEN + 2 * (key_bytes.len() + CHILD_LEN) > PAGE_SIZE {
+                return Err(too_big("key too big for an MmapBTreeMap page"));
+            }
+            let mut entry = key_bytes.clone();
+            value.encode(&mut entry)?;
+            if !page.fits(entry.len()) {
+                level.push(page.finish(&mut out)?);
+            }
+            page.push(&key_bytes, &entry)?;
+        }
+        if page.count > 0 {
+            level.push(page.finish(&mut out)?);
+        }
+        let leaves = out.pages - 1;
+
+        let mut depth = usize::from(!level.is_empty());
+        while level.len() > 1 {
+            let children = level.len();
+            let mut parents = Vec::new();
+            let mut page = PageBuilder::new(INTERNAL);
+            for (first_key, child) in level {
+                let mut entry = first_key.clone();
+                entry.extend_from_slice(&child.to_le_bytes());
+                if page.count == 0 {
+                    // A page's first child goes in without its key.
+                    page.first_key = first_key;
+                    page.body.extend_from_slice(&child.to_le_bytes());
+                    page.count = 1;
+                    continue;
+                }
+                if !page.fits(entry.len()) {
+                    parents.push(page.finish(&mut out)?);
+                    page.first_key = first_key;
+                    page.body.extend_from_slice(&child.to_le_bytes());
+                    page.count = 1;
+                    continue;
+                }
+                page.body.extend_from_slice(&entry);
+                page.count += 1;
+            }
+            parents.push(page.finish(&mut out)?);
+            if parents.len() == children {
+                return Err(too_big("keys too big for an MmapBTreeMap page"));
+            }
+            level = parents;
+            depth += 1;
+        }
+        let root = level.first().map_or(0, |&(_, page)| page);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        (PAGE_SIZE as u32).encode(&mut header)?;
+        (map.len() as u64).encode(&mut header)?;
+        root.encode(&mut header)?;
+        (depth as u32).encode(&mut header)?;
+        leaves.encode(&mut header)?;
+        out.out.seek(SeekFrom::Start(0))?;
+        out.out.write_all(&header)?;
+        let file = out.out.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()
+    }
+}
+
+impl<K, V, C: Default> MmapBTreeMap<K, V, C> {
+    /// Opens a map written by `flush`, and maps it into memory.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may change or truncate the file while the map is open, in this process or any
+    /// other. Writing a new version with `flush` is fine, since that replaces the file rather than
+    /// changing it, and the open map keeps reading the old one.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<MmapBTreeMap<K, V, C>> {
+        Self::open_with_comparator(path, C::default())
+    }
+}
+
+impl<K, V, C> MmapBTreeMap<K, V, C> {
+    /// Opens a map written by `flush` from a map ordered by `cmp`, and maps it into memory.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `open`.
+    pub unsafe fn open_with_comparator<P: AsRef<Path>>(
+        path: P,
+        cmp: C,
+    ) -> io::Result<MmapBTreeMap<K, V, C>> {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        if mmap.len() < PAGE_SIZE || mmap.len() % PAGE_SIZE != 0 {
+            return Err(invalid("not an MmapBTreeMap file"));
+        }
+        let mut header = &mmap[..PAGE_SIZE];
+        let mut magic = [0; 4];
+        io::Read::read_exact(&mut header, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an MmapBTreeMap file"));
+        }
+        if u8::decode(&mut header)? != VERSION {
+            return Err(invalid("unsupported MmapBTreeMap version"));
+        }
+        if u32::decode(&mut header)? as usize != PAGE_SIZE {
+            return Err(invalid("unsupported MmapBTreeMap page size"));
+        }
+        let length = u64::decode(&mut header)?;
+        let root = u64::decode(&mut header)?;
+        let depth = u32::decode(&mut header)? as usize;
+        let leaves = u64::decode(&mut header)?;
+        let pages = (mmap.len() / PAGE_SIZE) as u64;
+        // `load` reserves room for `length` entries up front, so it mustn't claim more than the
+        // leaves could hold.
+        let max_length = leaves.saturating_mul(u16::MAX as u64);
+        if root >= pages
+            || leaves >= pages
+            || depth as u64 >= pages
+            || length > max_length
+            || (root == 0) != (length == 0)
+        {
+            return Err(invalid("corrupt MmapBTreeMap header"));
+        }
+        let length = length as usize;
+        Ok(MmapBTreeMap {
+            mmap,
+            length,
+            root,
+            depth,
+            leaves,
+            cmp,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    // Get a page's entry count and the bytes after its header, checking it's the expected kind.
+    fn page(&self, number: u64, kind: u8) -> io::Result<(usize, &[u8])> {
+        let start = usize::try_from(number)
+            .ok()
+            .and_then(|n| n.checked_mul(PAGE_SIZE))
+            .filter(|&start| number > 0 && start < self.mmap.len())
+            .ok_or_else(|| invalid("page number out of range"))?;
+        let page = &self.mmap[start..start + PAGE_SIZE];
+        if page[0] != kind {
+            return Err(invalid("page of the wrong kind"));
+        }
+        let count = u16::from_le_bytes([page[1], page[2]]) as usize;
+        Ok((count, &page[PAGE_HEADER_LEN..]))
+    }
+}
+
+impl<K: Decode, V: Decode, C: Comparator<K>> MmapBTreeMap<K, V, C> {
+    /// Looks up `key`, decoding only the pages on the way down to it.
+    pub fn get<Q>(&self, key: &Q) -> io::Result<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let mut number = self.root;
+        for _ in 1..self.depth {
+            let (count, mut body) = self.page(number, INTERNAL)?;
+            // Go down the last child whose first key isn't past `key`.
+            number = u64::decode(&mut body)?;
+            for _ in 1..count {
+                let first_key = K::decode(&mut body)?;
+                let child = u64::decode(&mut body)?;
+                if self.cmp.compare(first_key.borrow(), key) == Ordering::Greater {
+                    break;
+                }
+                number = child;
+            }
+        }
+        let (count, mut body) = self.page(number, LEAF)?;
+        for _ in 0..count {
+            let entry_key = K::decode(&mut body)?;
+            let value = V::decode(&mut body)?;
+            match self.cmp.compare(entry_key.borrow(), key) {
+                Ordering::Less => {}
+                Ordering::Equal => return Ok(Some(value)),
+                Ordering::Greater => break,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads every entry back into an in-memory BTreeMap.
+    pub fn load(&self) -> io::Result<BTreeMap<K, V, C>>
+    where
+        C: Clone,
+    {
+        let mut entries = Vec::with_capacity(self.length);
+        for number in 1..=self.leaves {
+            let (count, mut body) = self.page(number, LEAF)?;
+            for _ in 0..count {
+                entries.push((K::decode(&mut body)?, V::decode(&mut body)?));
+            }
+        }
+        let out_of_order = entries
+            .windows(2)
+            .any(|pair| self.cmp.compare(&pair[0].0, &pair[1].0) != Ordering::Less);
+        if entries.len() != self.length || out_of_order {
+            return Err(invalid("corrupt MmapBTreeMap leaves"));
+        }
+        Ok(BTreeMap::from_sorted_iter_with_comparator(
+            entries,
+            self.cmp.clone(),
+        ))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn too_big(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+struct PageWriter {
+    out: BufWriter<File>,
+    pages: u64,
+}
+
+impl PageWriter {
+    // Write out a page, padded to the page size, and return its number.
+    fn write_page(&mut self, bytes: &[u8]) -> io::Result<u64> {
+        self.out.write_all(bytes)?;
+        self.out.write_all(&[0; PAGE_SIZE][bytes.len()..])?;
+        self.pages += 1;
+        Ok(self.pages - 1)
+    }
+}
+
+// A page being filled, along with the first key in it.
+struct PageBuilder {
+    kind: u8,
+    count: usize,
+    body: Vec<u8>,
+    first_key: Vec<u8>,
+}
+
+impl PageBuilder {
+    fn new(kind: u8) -> PageBuilder {
+        PageBuilder {
+            kind,
+            count: 0,
+            body: Vec::with_capacity(PAGE_SIZE),
+            first_key: Vec::new(),
+        }
+    }
+
+    fn fits(&self, len: usize) -> bool {
+        PAGE_HEADER_LEN + self.body.len() + len <= PAGE_SIZE && self.count < u16::MAX as usize
+    }
+
+    fn push(&mut self, key: &[u8], entry: &[u8]) -> io::Result<()> {
+        if !self.fits(entry.len()) {
+            return Err(too_big("entry too big for an MmapBTreeMap page"));
+        }
+        if self.count == 0 {
+            self.first_key = key.to_vec();
+        }
+        self.body.extend_from_slice(entry);
+        self.count += 1;
+        Ok(())
+    }
+
+    // Write the page out and start it over empty. Returns the first key and number of the page.
+    fn finish(&mut self, out: &mut PageWriter) -> io::Result<(Vec<u8>, u64)> {
+        let mut page = Vec::with_capacity(PAGE_SIZE);
+        page.push(self.kind);
+        page.extend_from_slice(&(self.count as u16).to_le_bytes());
+        page.extend_from_slice(&self.body);
+        let number = out.write_page(&page)?;
+        self.count = 0;
+        self.body.clear();
+        Ok((std::mem::take(&mut self.first_key), number))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{compare::Comparator, map::BTreeMap};
+    use super::MmapBTreeMap;
+    use std::{cmp::Ordering, fs, io::ErrorKind, path::PathBuf};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tiny-collections-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_flush_open_load() {
+        let path = temp_path("mmap");
+        // Big values keep the leaves small, so there are enough of them for three levels.
+        let map: BTreeMap<u64, Vec<u8>> = (0..20000).map(|i| (i * 2, vec![i as u8; 200])).collect();
+        MmapBTreeMap::flush(&map, &path).unwrap();
+        let file = unsafe { MmapBTreeMap::<u64, Vec<u8>>::open(&path) }.unwrap();
+        assert_eq!((file.len(), file.depth), (20000, 3));
+        for i in (0..40001).step_by(97) {
+            assert_eq!(file.get(&i).unwrap().as_ref(), map.find(&i));
+        }
+        assert_eq!(file.load().unwrap(), map);
+
+        let empty = BTreeMap::<u64, String>::new();
+        MmapBTreeMap::flush(&empty, &path).unwrap();
+        let file = unsafe { MmapBTreeMap::<u64, String>::open(&path) }.unwrap();
+        assert!(file.is_empty());
+        assert_eq!(file.get(&0).unwrap(), None);
+        assert!(file.load().unwrap().is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_comparator() {
+        #[derive(Clone, Copy, Default)]
+        struct Descending;
+
+        impl Comparator<u64> for Descending {
+            fn compare(&self, a: &u64, b: &u64) -> Ordering {
+                b.cmp(a)
+            }
+        }
+
+        let path = temp_path("mmap-comparator");
+        let mut map = BTreeMap::with_comparator(Descending);
+        for i in 0..5000 {
+            map.insert(i * 2, i);
+        }
+        MmapBTreeMap::flush(&map, &path).unwrap();
+        let file = unsafe { MmapBTreeMap::<u64, u64, Descending>::open(&path) }.unwrap();
+        assert!(file.depth > 1);
+        for i in 0..10001 {
+            assert_eq!(file.get(&i).unwrap().as_ref(), map.find(&i));
+        }
+        assert_eq!(file.load().unwrap(), map);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bad_files() {
+        let path = temp_path("mmap-bad");
+        let huge: BTreeMap<u32, Vec<u8>> = [(1, vec![0; 5000])].into();
+        let error = MmapBTreeMap::flush(&huge, &path).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(!temp_path("mmap-bad.tmp").exists());
+
+        // Keys that fit in a leaf, but not two to an internal page.
+        let wide: BTreeMap<String, ()> = (0..3).map(|i| (i.to_string().repeat(4075), ())).collect();
+        let error = MmapBTreeMap::flush(&wide, &path).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidInput);
+        assert!(!temp_path("mmap-bad.tmp").exists());
+
+        fs::write(&path, b"not a map").unwrap();
+        let error = unsafe { MmapBTreeMap::<u32, u32>::open(&path) }
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        // A header claiming far more entries than its leaves could hold.
+        let map: BTreeMap<u32, u32> = (0..10).map(|i| (i, i)).collect();
+        MmapBTreeMap::flush(&map, &path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[9..17].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        let error = unsafe { MmapBTreeMap::<u32, u32>::open(&path) }
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,257 @@
+use super::map::{self, BTreeMap};
+use std::{borrow::Borrow, fmt, iter::FusedIterator, slice};
+
+/// An ordered map that can hold several values under the same key. Each key keeps its values in
+/// the order they were inserted.
+pub struct BTreeMultiMap<K: Ord, V> {
+    map: BTreeMap<K, Vec<V>>,
+    // The number of values, as opposed to the number of distinct keys.
+    length: usize,
+}
+
+impl<K: Ord, V> Default for BTreeMultiMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> BTreeMultiMap<K, V> {
+    /// Makes a new empty BTreeMultiMap.
+    pub fn new() -> BTreeMultiMap<K, V> {
+        BTreeMultiMap {
+            map: BTreeMap::new(),
+            length: 0,
+        }
+    }
+
+    /// Makes a new empty BTreeMultiMap with the given B for the underlying map.
+    pub fn with_b(b: usize) -> BTreeMultiMap<K, V> {
+        BTreeMultiMap {
+            map: BTreeMap::with_b(b),
+            length: 0,
+        }
+    }
+
+    /// Returns the number of values in the map, counting every value under a shared key.
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    /// Returns the number of distinct keys in the map.
+    pub fn keys_len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Adds `value` after any values already under `key`.
+    pub fn insert(&mut self, key: K, value: V) {
+        // An empty Vec doesn't allocate, so offering one finds or makes the key's list in a
+        // single search.
+        let values = match self.map.try_insert(key, Vec::new()) {
+            Ok(values) => values,
+            Err(error) => error.entry.into_mut(),
+        };
+        values.push(value);
+        self.length += 1;
+    }
+
+    /// Returns all the values under `key`, oldest first. The slice is empty if the key is missing.
+    pub fn get_all<Q>(&self, key: &Q) -> &[V]
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.map.find(key).map_or(&[], |values| values)
+    }
+
+    /// Removes the newest value under `key`, dropping the key once it has none left. Taking from
+    /// the back keeps emptying a key one value at a time linear in the number of values.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let values = self.map.find_mut(key)?;
+        let value = values.pop()?;
+        if values.is_empty() {
+            self.map.remove(key);
+        }
+        self.length -= 1;
+        Some(value)
+    }
+
+    /// Removes `key` and returns all of its values, oldest first.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> Vec<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let values = self.map.remove(key).unwrap_or_default();
+        self.length -= values.len();
+        values
+    }
+
+    /// Gets an iterator over the keys in order, each with all of its values.
+    pub fn groups(&self) -> Groups<'_, K, V> {
+        Groups {
+            inner: self.map.iter(),
+        }
+    }
+
+    /// Gets an iterator over every key-value pair, sorted by key and then by insertion order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            groups: self.groups(),
+            current: None,
+            length: self.length,
+        }
+    }
+}
+
+/// An iterator over the keys of a BTreeMultiMap, each with the slice of its values.
+pub struct Groups<'a, K, V> {
+    inner: map::Iter<'a, K, Vec<V>>,
+}
+
+impl<'a, K: Ord, V> Iterator for Groups<'a, K, V> {
+    type Item = (&'a K, &'a [V]);
+
+    fn next(&mut self) -> Option<(&'a K, &'a [V])> {
+        self.inner
+            .next()
+            .map(|(key, values)| (key, values.as_slice()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for Groups<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a [V])> {
+        self.inner
+            .next_back()
+            .map(|(key, values)| (key, values.as_slice()))
+    }
+}
+
+impl<K: Ord, V> ExactSizeIterator for Groups<'_, K, V> {}
+
+impl<K: Ord, V> FusedIterator for Groups<'_, K, V> {}
+
+/// An iterator over every key-value pair of a BTreeMultiMap.
+pub struct Iter<'a, K, V> {
+    groups: Groups<'a, K, V>,
+    // The key we're going through the values of, and the values still to come.
+    current: Option<(&'a K, slice::Iter<'a, V>)>,
+    length: usize,
+}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            if let Some((key, values)) = &mut self.current {
+                if let Some(value) = values.next() {
+                    self.length -= 1;
+                    return Some((key, value));
+                }
+            }
+            let (key, values) = self.groups.next()?;
+            self.current = Some((key, values.iter()));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<K: Ord, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K: Ord, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<'a, K: Ord, V> IntoIterator for &'a BTreeMultiMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for BTreeMultiMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.groups()).finish()
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V> arbitrary::Arbitrary<'a> for BTreeMultiMap<K, V>
+where
+    K: Ord + arbitrary::Arbitrary<'a>,
+    V: arbitrary::Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for BTreeMultiMap<K, V> {
+    /// Builds a multimap from an iterator of pairs, keeping every value.
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> BTreeMultiMap<K, V> {
+        let mut map = BTreeMultiMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for BTreeMultiMap<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BTreeMultiMap;
+
+    #[test]
+    fn test_multimap() {
+        let mut map: BTreeMultiMap<_, _> = (0..300).map(|i| (i % 7, i)).collect();
+        assert_eq!((map.len(), map.keys_len()), (300, 7));
+        assert_eq!(map.get_all(&3)[..3], [3, 10, 17]);
+        assert!(map.get_all(&7).is_empty());
+        assert!(map.iter().map(|(k, _)| *k).eq((0..7).flat_map(|k| {
+            let count = (0..300).filter(|i| i % 7 == k).count();
+            std::iter::repeat_n(k, count)
+        })));
+
+        assert_eq!(map.remove_one(&3), Some(297));
+        assert_eq!(map.get_all(&3)[0], 3);
+        assert_eq!(map.get_all(&3).last(), Some(&290));
+        let all = map.remove_all(&3);
+        assert_eq!(all.len(), 42);
+        assert_eq!(map.remove_one(&3), None);
+        assert_eq!((map.len(), map.keys_len()), (257, 6));
+
+        map.insert(10, 0);
+        assert_eq!(map.remove_one(&10), Some(0));
+        assert_eq!(map.keys_len(), 6);
+        let groups: Vec<_> = map.groups().map(|(k, v)| (*k, v.len())).collect();
+        assert_eq!(
+            groups,
+            [(0, 43), (1, 43), (2, 43), (4, 43), (5, 43), (6, 42)]
+        );
+    }
+}
@@ -1,362 +1,921 @@
-use self::{InsertionResult::*, SearchResult::*};
-use std::{cmp::Ordering::*, mem, ptr};
-
-#[derive(Clone)]
-pub struct Node<K, V> {
-    keys: Vec<K>,
-    edges: Vec<Node<K, V>>,
-    vals: Vec<V>,
-}
-
-// public funtions
-impl<K: Ord, V> Node<K, V> {
-    pub fn search(&self, key: &K) -> SearchResult {
-        self.search_linear(key)
-    }
-
-    // make a new internal node
-    pub fn new_internal(capacity: usize) -> Node<K, V> {
-        Node {
-            keys: Vec::with_capacity(capacity),
-            vals: Vec::with_capacity(capacity),
-            edges: Vec::with_capacity(capacity + 1),
-        }
-    }
-
-    // make a leaf node
-    pub fn new_leaf(capacity: usize) -> Node<K, V> {
-        Node {
-            keys: Vec::with_capacity(capacity),
-            edges: Vec::new(),
-            vals: Vec::with_capacity(capacity),
-        }
-    }
-
-    /// Make a leaf root from scratch
-    pub fn make_leaf_root(b: usize) -> Node<K, V> {
-        Node::new_leaf(capacity_from_b(b))
-    }
-
-    // make an internal root and swap with an old root
-    pub fn make_internal_root(
-        left_and_out: &mut Node<K, V>,
-        b: usize,
-        key: K,
-        value: V,
-        right: Node<K, V>,
-    ) {
-        let mut node = Node::new_internal(capacity_from_b(b));
-        mem::swap(left_and_out, &mut node);
-        left_and_out.keys.push(key);
-        left_and_out.vals.push(value);
-        left_and_out.edges.push(node);
-        left_and_out.edges.push(right);
-    }
-
-    pub fn len(&self) -> usize {
-        self.keys.len()
-    }
-
-    pub fn capacity(&self) -> usize {
-        self.keys.capacity()
-    }
-
-    pub fn is_full(&self) -> bool {
-        self.len() == self.capacity()
-    }
-
-    pub fn is_underfull(&self) -> bool {
-        self.keys.len() < min_load_from_capacity(self.capacity())
-    }
-
-    pub unsafe fn unsafe_swap(&mut self, index: usize, key: &mut K, val: &mut V) {
-        mem::swap(self.keys.get_unchecked_mut(index), key);
-        mem::swap(self.vals.get_unchecked_mut(index), val);
-    }
-
-    pub fn key(&self, idx: usize) -> Option<&K> {
-        self.keys.get(idx)
-    }
-
-    pub fn key_mut(&mut self, idx: usize) -> Option<&mut K> {
-        self.keys.get_mut(idx)
-    }
-
-    pub unsafe fn unsafe_key_mut(&mut self, idx: usize) -> &mut K {
-        self.keys.get_unchecked_mut(idx)
-    }
-
-    pub fn val(&self, idx: usize) -> Option<&V> {
-        self.vals.get(idx)
-    }
-
-    pub fn val_mut(&mut self, idx: usize) -> Option<&mut V> {
-        self.vals.get_mut(idx)
-    }
-
-    pub unsafe fn unsafe_val_mut(&mut self, idx: usize) -> &mut V {
-        self.vals.get_unchecked_mut(idx)
-    }
-
-    pub fn edge(&self, idx: usize) -> Option<&Self> {
-        self.edges.get(idx)
-    }
-
-    pub fn edge_mut(&mut self, idx: usize) -> Option<&mut Node<K, V>> {
-        self.edges.get_mut(idx)
-    }
-
-    pub unsafe fn unsafe_edge_mut(&mut self, idx: usize) -> &mut Node<K, V> {
-        self.edges.get_unchecked_mut(idx)
-    }
-
-    pub fn pop_edge(&mut self) -> Option<Node<K, V>> {
-        self.edges.pop()
-    }
-
-    // If the node has any children
-    pub fn is_leaf(&self) -> bool {
-        self.edges.is_empty()
-    }
-
-    pub fn insert_as_leaf(
-        &mut self,
-        index: usize,
-        key: K,
-        value: V,
-    ) -> (InsertionResult<K, V>, *mut V) {
-        if !self.is_full() {
-            self.insert_fit_as_leaf(index, key, value);
-            (Fit, unsafe { self.unsafe_val_mut(index) as *mut _ })
-        } else {
-            // The new element can't fit, split
-            let (new_key, new_val, mut new_right) = self.split();
-            // now self is left
-            let left_len = self.len();
-
-            let ptr = if index <= left_len {
-                self.insert_fit_as_leaf(index, key, value);
-                unsafe { self.unsafe_val_mut(index) as *mut _ }
-            } else {
-                new_right.insert_fit_as_leaf(index - left_len - 1, key, value);
-                unsafe { new_right.unsafe_val_mut(index - left_len - 1) as *mut _ }
-            };
-            (Split(new_key, new_val, new_right), ptr)
-        }
-    }
-
-    pub fn insert_as_internal(
-        &mut self,
-        index: usize,
-        key: K,
-        value: V,
-        right: Node<K, V>,
-    ) -> InsertionResult<K, V> {
-        if !self.is_full() {
-            self.insert_fit_as_internal(index, key, value, right);
-            Fit
-        } else {
-            // The new element can't fit.
-            let (new_key, new_val, mut new_right) = self.split();
-            let left_len = self.len();
-            if index <= left_len {
-                self.insert_fit_as_internal(index, key, value, right);
-            } else {
-                new_right.insert_fit_as_internal(index - left_len - 1, key, value, right);
-            }
-            Split(new_key, new_val, new_right)
-        }
-    }
-
-    pub fn remove_as_leaf(&mut self, index: usize) -> (K, V) {
-        (self.keys.remove(index), self.vals.remove(index))
-    }
-
-    pub fn handle_underflow(&mut self, underflowed_child_index: usize) {
-        assert!(underflowed_child_index <= self.len());
-        if underflowed_child_index > 0 {
-            unsafe { self.handle_underflow_to_left(underflowed_child_index) };
-        } else {
-            unsafe { self.handle_underflow_to_right(underflowed_child_index) };
-        }
-    }
-}
-
-// private functions
-impl<K, V> Node<K, V>
-where
-    K: Ord,
-{
-    fn search_linear(&self, key: &K) -> SearchResult {
-        for (i, k) in self.keys.iter().enumerate() {
-            match k.cmp(key) {
-                Less => continue,
-                Equal => return Found(i),
-                Greater => return GoDown(i),
-            };
-        }
-        GoDown(self.len())
-    }
-
-    fn search_binary(&self, key: &K) -> SearchResult {
-        unimplemented!()
-    }
-
-    fn from_vecs(keys: Vec<K>, vals: Vec<V>, edges: Vec<Node<K, V>>) -> Node<K, V> {
-        Node { keys, vals, edges }
-    }
-
-    fn insert_fit_as_leaf(&mut self, index: usize, key: K, val: V) {
-        self.keys.insert(index, key);
-        self.vals.insert(index, val);
-    }
-
-    fn insert_fit_as_internal(&mut self, index: usize, key: K, val: V, right: Node<K, V>) {
-        self.keys.insert(index, key);
-        self.vals.insert(index, val);
-        self.edges.insert(index + 1, right);
-    }
-
-    // Node is full, so split it into two nodes, and yield the middle-most key-vale par
-    fn split(&mut self) -> (K, V, Node<K, V>) {
-        let r_keys = split(&mut self.keys);
-        let r_vals = split(&mut self.vals);
-        let r_edges = if self.edges.is_empty() {
-            Vec::new()
-        } else {
-            split(&mut self.edges)
-        };
-
-        let right = Node::from_vecs(r_keys, r_vals, r_edges);
-        let key = self.keys.pop().unwrap();
-        let val = self.vals.pop().unwrap();
-
-        (key, val, right)
-    }
-
-    // Right is underflowed, try to steal from left.
-    // Merge if left is also underflowed.
-    unsafe fn handle_underflow_to_left(&mut self, underflowed_child_index: usize) {
-        let left_len = self.edges[underflowed_child_index - 1].len();
-        if left_len > min_load_from_capacity(self.capacity()) {
-            self.steal_to_left(underflowed_child_index);
-        } else {
-            self.merge_children(underflowed_child_index - 1);
-        }
-    }
-
-    unsafe fn handle_underflow_to_right(&mut self, underflowed_child_index: usize) {
-        let right_len = self.edges[underflowed_child_index + 1].len();
-        if right_len > min_load_from_capacity(self.capacity()) {
-            self.steal_to_right(underflowed_child_index);
-        } else {
-            self.merge_children(underflowed_child_index);
-        }
-    }
-
-    /// Steal! Stealing is roughly analagous to a binary tree rotation.
-    /// In this case, we're "rotating" right.
-    unsafe fn steal_to_left(&mut self, underflowed_child_index: usize) {
-        // Get the last kv pair from left
-        let (mut key, mut val, edge) = {
-            let left = self.unsafe_edge_mut(underflowed_child_index - 1);
-            match (left.keys.pop(), left.vals.pop(), left.edges.pop()) {
-                (Some(key), Some(val), edge) => (key, val, edge),
-                _ => unreachable!(),
-            }
-        };
-
-        // swap the parent's seperating kv pair node with left
-        self.unsafe_swap(underflowed_child_index - 1, &mut key, &mut val);
-
-        // put it to the begin of right node
-        let right = self.unsafe_edge_mut(underflowed_child_index);
-        right.keys.insert(0, key);
-        right.vals.insert(0, val);
-        if let Some(edge) = edge {
-            right.edges.insert(0, edge);
-        }
-    }
-
-    unsafe fn steal_to_right(&mut self, underflowed_child_index: usize) {
-        // Get the first kv pair from right
-        let (mut key, mut val, edge) = {
-            let right = self.unsafe_edge_mut(underflowed_child_index + 1);
-            if right.edges.is_empty() {
-                (right.keys.remove(0), right.vals.remove(0), None)
-            } else {
-                (
-                    right.keys.remove(0),
-                    right.vals.remove(0),
-                    Some(right.edges.remove(0)),
-                )
-            }
-        };
-
-        // swap the parent's seperating kv pair node.
-        self.unsafe_swap(underflowed_child_index, &mut key, &mut val);
-
-        // put it to the end of left node
-        let left = self.unsafe_edge_mut(underflowed_child_index);
-        left.keys.push(key);
-        left.vals.push(val);
-        if let Some(edge) = edge {
-            left.edges.push(edge);
-        }
-    }
-
-    unsafe fn merge_children(&mut self, left_index: usize) {
-        let (key, val, right) = (
-            self.keys.remove(left_index),
-            self.vals.remove(left_index),
-            self.edges.remove(left_index + 1),
-        );
-        let left = self.unsafe_edge_mut(left_index);
-        left.absorb(key, val, right);
-    }
-
-    fn absorb(&mut self, key: K, val: V, right: Node<K, V>) {
-        debug_assert!(self.len() + right.len() <= self.capacity());
-
-        self.keys.push(key);
-        self.vals.push(val);
-        self.keys.extend(right.keys);
-        self.vals.extend(right.vals);
-        self.edges.extend(right.edges);
-    }
-}
-
-// Takes a Vec, and splits half the elements into a new one.
-fn split<T>(left: &mut Vec<T>) -> Vec<T> {
-    let len = left.len();
-    let right_len = len / 2;
-    let left_len = len - right_len;
-    let mut right = Vec::with_capacity(left.capacity());
-    unsafe {
-        let left_ptr = left.get_unchecked_mut(left_len) as *mut _;
-        let right_ptr = right.as_mut_ptr();
-        ptr::copy_nonoverlapping(left_ptr, right_ptr, right_len);
-        left.set_len(left_len);
-        right.set_len(right_len);
-    }
-    right
-}
-
-fn capacity_from_b(b: usize) -> usize {
-    2 * b - 1
-}
-
-fn min_load_from_capacity(capacity: usize) -> usize {
-    // b - 1
-    capacity / 2
-}
-
-pub enum SearchResult {
-    Found(usize),
-    GoDown(usize),
-}
-
-pub enum InsertionResult<K, V> {
-    Fit,
-    Split(K, V, Node<K, V>),
-}
+use self::{InsertionResult::*, SearchResult::*};
+use super::compare::Comparator;
+use crate::myvec::{Allocator, Global, MyVec};
+use std::{borrow::Borrow, cmp::Ordering::*, mem, ptr};
+
+// Every buffer gets its memory from its own copy of the map's allocator.
+#[derive(Clone)]
+pub struct Node<K, V, A: Allocator = Global> {
+    keys: MyVec<K, A>,
+    edges: MyVec<Node<K, V, A>, A>,
+    vals: MyVec<V, A>,
+    // The number of entries in this node and everything below it.
+    size: usize,
+}
+
+// public funtions
+impl<K, V, A: Allocator> Node<K, V, A> {
+    pub fn search<Q, C>(&self, key: &Q, cmp: &C) -> SearchResult
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        match cmp.search(&self.keys, key) {
+            Ok(i) => Found(i),
+            Err(i) => GoDown(i),
+        }
+    }
+
+    // make a new internal node
+    pub fn new_internal(capacity: usize, alloc: &A) -> Node<K, V, A>
+    where
+        A: Clone,
+    {
+        Node {
+            keys: buffer(capacity, alloc),
+            vals: buffer(capacity, alloc),
+            edges: buffer(capacity + 1, alloc),
+            size: 0,
+        }
+    }
+
+    // make a leaf node
+    pub fn new_leaf(capacity: usize, alloc: &A) -> Node<K, V, A>
+    where
+        A: Clone,
+    {
+        Node {
+            keys: buffer(capacity, alloc),
+            edges: MyVec::new_in(alloc.clone()),
+            vals: buffer(capacity, alloc),
+            size: 0,
+        }
+    }
+
+    /// Make a leaf root from scratch
+    pub fn make_leaf_root(b: usize, alloc: &A) -> Node<K, V, A>
+    where
+        A: Clone,
+    {
+        Node::new_leaf(capacity_from_b(b), alloc)
+    }
+
+    // make an internal root and swap with an old root
+    pub fn make_internal_root(
+        left_and_out: &mut Node<K, V, A>,
+        b: usize,
+        key: K,
+        value: V,
+        right: Node<K, V, A>,
+        pool: &mut NodePool<K, V, A>,
+    ) where
+        A: Clone,
+    {
+        let mut node = pool.take(capacity_from_b(b), true);
+        mem::swap(left_and_out, &mut node);
+        left_and_out.keys.push(key);
+        left_and_out.vals.push(value);
+        left_and_out.edges.push(node);
+        left_and_out.edges.push(right);
+        left_and_out.recount();
+    }
+
+    /// Build a tree from entries in ascending key order, packing every node full except along the
+    /// right edge. Runs of equal keys keep the last value. Returns the root, the number of entries
+    /// and the depth of the tree.
+    pub fn from_sorted_iter<I, C>(
+        b: usize,
+        iter: I,
+        cmp: &C,
+        alloc: &A,
+    ) -> (Node<K, V, A>, usize, usize)
+    where
+        A: Clone,
+        I: IntoIterator<Item = (K, V)>,
+        C: Comparator<K>,
+    {
+        let capacity = capacity_from_b(b);
+        let mut root = Node::new_leaf(capacity, alloc);
+        let mut length = 0;
+        // The nodes along the right edge of the tree, from the root down to the leaf being filled.
+        // Nodes are only ever pushed to and never outgrow the buffers they were made with, so these
+        // pointers, and the ones to the last entry, stay valid until we're done.
+        let mut spine: Vec<*mut Node<K, V, A>> = vec![&mut root];
+        let mut last: Option<(*const K, *mut V)> = None;
+
+        for (key, val) in iter {
+            unsafe {
+                if let Some((last_key, last_val)) = last {
+                    match cmp.compare(&*last_key, &key) {
+                        Less => {}
+                        Equal => {
+                            *last_val = val;
+                            continue;
+                        }
+                        Greater => panic!("keys must be in ascending order"),
+                    }
+                }
+
+                let leaf = spine[spine.len() - 1];
+                if !(*leaf).is_full() {
+                    (*leaf).keys.push(key);
+                    (*leaf).vals.push(val);
+                    let (key_ptr, val_ptr) = (*leaf).unsafe_kv_ptr((*leaf).len() - 1);
+                    last = Some((key_ptr, val_ptr));
+                    length += 1;
+                    continue;
+                }
+
+                // The leaf is full, so the entry goes up into the lowest node on the spine with
+                // room, growing a new root if there is none.
+                let mut depth = spine.len();
+                let level = match spine.iter().rposition(|&node| !(*node).is_full()) {
+                    Some(level) => level,
+                    None => {
+                        let old_root = mem::replace(&mut root, Node::new_internal(capacity, alloc));
+                        root.edges.push(old_root);
+                        spine[0] = &mut root;
+                        depth += 1;
+                        0
+                    }
+                };
+                spine.truncate(level + 1);
+
+                let mut node = spine[level];
+                (*node).keys.push(key);
+                (*node).vals.push(val);
+                let (key_ptr, val_ptr) = (*node).unsafe_kv_ptr((*node).len() - 1);
+                last = Some((key_ptr, val_ptr));
+                length += 1;
+
+                // Hang a fresh, empty path down to a leaf off the right of the new entry.
+                for child_level in level + 1..depth {
+                    let child = if child_level + 1 == depth {
+                        Node::new_leaf(capacity, alloc)
+                    } else {
+                        Node::new_internal(capacity, alloc)
+                    };
+                    (*node).edges.push(child);
+                    node = (*node).edges.last_mut().unwrap();
+                    spine.push(node);
+                }
+            }
+        }
+
+        // Every node is full except those on the right edge, which may even be empty. Top them up
+        // from their left siblings.
+        let depth = spine.len();
+        let mut node = &mut root;
+        while !node.is_leaf() {
+            node.fix_last_edge();
+            node = node.edges.last_mut().unwrap();
+        }
+        root.recount_all();
+        (root, length, depth)
+    }
+
+    /// Move every entry whose key `goes_right` into a new tree of the same depth, and return its
+    /// root. `goes_right` must hold for a suffix of the keys in order.
+    ///
+    /// The nodes along the cut can be left with any number of keys, down to none at all. Those
+    /// on the right edge of this tree are put right by `fix_right_border`, and those on the left
+    /// edge of the new one by `fix_left_border`.
+    pub fn split_off_by<F>(&mut self, goes_right: &F, pool: &mut NodePool<K, V, A>) -> Node<K, V, A>
+    where
+        A: Clone,
+        F: Fn(&K) -> bool,
+    {
+        let index = self.keys.partition_point(|key| !goes_right(key));
+        let mut right = pool.take(self.capacity(), !self.is_leaf());
+        right.keys.extend(self.keys.drain(index..));
+        right.vals.extend(self.vals.drain(index..));
+        if !self.is_leaf() {
+            let straddling = self.edges[index].split_off_by(goes_right, pool);
+            right.edges.push(straddling);
+            right.edges.extend(self.edges.drain(index + 1..));
+        }
+        self.recount();
+        right.recount();
+        right
+    }
+
+    /// Restore the invariants of a tree whose only faults are underfull nodes along its right
+    /// edge, such as what's left after `split_off_by`. The tree may get shallower.
+    pub fn fix_right_border(
+        root: &mut Node<K, V, A>,
+        depth: &mut usize,
+        pool: &mut NodePool<K, V, A>,
+    ) where
+        A: Clone,
+    {
+        Node::fix_top(root, depth, pool);
+        if root.len() > 0 {
+            let mut node = &mut *root;
+            while !node.is_leaf() {
+                node.fix_right_child(pool);
+                node = node.edges.last_mut().unwrap();
+            }
+            Node::fix_top(root, depth, pool);
+        }
+    }
+
+    /// Restore the invariants of a tree whose only faults are underfull nodes along its left
+    /// edge. The tree may get shallower.
+    pub fn fix_left_border(
+        root: &mut Node<K, V, A>,
+        depth: &mut usize,
+        pool: &mut NodePool<K, V, A>,
+    ) where
+        A: Clone,
+    {
+        Node::fix_top(root, depth, pool);
+        if root.len() > 0 {
+            let mut node = &mut *root;
+            while !node.is_leaf() {
+                node.fix_left_child(pool);
+                node = &mut node.edges[0];
+            }
+            Node::fix_top(root, depth, pool);
+        }
+    }
+
+    // Drop empty roots until the root holds a key or is a leaf.
+    fn fix_top(root: &mut Node<K, V, A>, depth: &mut usize, pool: &mut NodePool<K, V, A>) {
+        while root.len() == 0 && !root.is_leaf() {
+            let child = root.pop_edge().unwrap();
+            pool.put(mem::replace(root, child));
+            *depth -= 1;
+        }
+    }
+
+    /// Join two valid trees into one, where every key in `right` is greater than every key in
+    /// `left`. Takes O(log n) time.
+    pub fn join(
+        left: Node<K, V, A>,
+        left_depth: usize,
+        mut right: Node<K, V, A>,
+        mut right_depth: usize,
+        pool: &mut NodePool<K, V, A>,
+    ) -> (Node<K, V, A>, usize)
+    where
+        A: Clone,
+    {
+        if right.size == 0 {
+            return (left, left_depth);
+        }
+        if left.size == 0 {
+            return (right, right_depth);
+        }
+        let b = left.capacity().div_ceil(2);
+        let (key, val) = Node::pop_first(&mut right, &mut right_depth, pool);
+
+        // Hang the shorter tree off the edge of the taller one, at the level where its root fits,
+        // with the popped entry as the separator. Splits ripple up as for an insertion.
+        let taller_is_left = left_depth >= right_depth;
+        let ((mut root, mut depth), (mut shorter, shorter_depth)) = if taller_is_left {
+            ((left, left_depth), (right, right_depth))
+        } else {
+            ((right, right_depth), (left, left_depth))
+        };
+        let mut stack: Vec<*mut Node<K, V, A>> = Vec::with_capacity(depth);
+        let mut node: *mut Node<K, V, A> = &mut root;
+        unsafe {
+            for _ in shorter_depth..depth {
+                stack.push(node);
+                node = if taller_is_left {
+                    (*node).edges.last_mut().unwrap()
+                } else {
+                    &mut (&mut (*node).edges)[0]
+                };
+            }
+            if !taller_is_left {
+                // Put the shorter tree in front, and insert the old first child after it instead.
+                if let Some(&parent) = stack.last() {
+                    mem::swap(&mut (&mut (*parent).edges)[0], &mut shorter);
+                }
+            }
+            let mut key_val = Some((key, val));
+            while let Some((key, val)) = key_val.take() {
+                let Some(parent) = stack.pop() else {
+                    // Either the trees are as tall as each other, or the root split: grow a new one.
+                    Node::make_internal_root(&mut root, b, key, val, shorter, pool);
+                    depth += 1;
+                    break;
+                };
+                let index = if taller_is_left { (*parent).len() } else { 0 };
+                match (*parent).insert_as_internal(index, key, val, shorter, pool) {
+                    Fit => break,
+                    Split(key, val, right) => {
+                        key_val = Some((key, val));
+                        shorter = right;
+                    }
+                }
+            }
+        }
+
+        // The nodes along the seam haven't counted the shorter tree in, and its root may be short
+        // of keys, as may either child of a new root.
+        root.recount_border(taller_is_left);
+        Node::fix_right_border(&mut root, &mut depth, pool);
+        Node::fix_left_border(&mut root, &mut depth, pool);
+        (root, depth)
+    }
+
+    // Take out the smallest entry of a non-empty tree.
+    fn pop_first(
+        root: &mut Node<K, V, A>,
+        depth: &mut usize,
+        pool: &mut NodePool<K, V, A>,
+    ) -> (K, V)
+    where
+        A: Clone,
+    {
+        let mut node = &mut *root;
+        loop {
+            node.size -= 1;
+            if node.is_leaf() {
+                break;
+            }
+            node = &mut node.edges[0];
+        }
+        let entry = (node.keys.remove(0), node.vals.remove(0));
+        Node::fix_left_border(root, depth, pool);
+        entry
+    }
+
+    // Recount the nodes along the right edge of the tree if `right`, or else the left edge,
+    // from the bottom up.
+    fn recount_border(&mut self, right: bool) {
+        if let Some(edge) = if right {
+            self.edges.last_mut()
+        } else {
+            self.edges.first_mut()
+        } {
+            edge.recount_border(right);
+        }
+        self.recount();
+    }
+
+    /// Take the node apart into its keys, values and children.
+    #[allow(clippy::type_complexity)]
+    pub fn into_parts(self) -> (MyVec<K, A>, MyVec<V, A>, MyVec<Node<K, V, A>, A>) {
+        (self.keys, self.vals, self.edges)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// The number of entries in this node and all of its descendants.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    // Account for an entry inserted or removed somewhere below this node.
+    pub fn inc_size(&mut self) {
+        self.size += 1;
+    }
+
+    pub fn dec_size(&mut self) {
+        self.size -= 1;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.keys.capacity()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    pub fn is_underfull(&self) -> bool {
+        self.keys.len() < min_load_from_capacity(self.capacity())
+    }
+
+    pub unsafe fn unsafe_swap(&mut self, index: usize, key: &mut K, val: &mut V) {
+        mem::swap(self.keys.get_unchecked_mut(index), key);
+        mem::swap(self.vals.get_unchecked_mut(index), val);
+    }
+
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    pub fn vals(&self) -> &[V] {
+        &self.vals
+    }
+
+    pub fn key(&self, idx: usize) -> Option<&K> {
+        self.keys.get(idx)
+    }
+
+    pub fn key_mut(&mut self, idx: usize) -> Option<&mut K> {
+        self.keys.get_mut(idx)
+    }
+
+    pub unsafe fn unsafe_key_mut(&mut self, idx: usize) -> &mut K {
+        self.keys.get_unchecked_mut(idx)
+    }
+
+    pub fn val(&self, idx: usize) -> Option<&V> {
+        self.vals.get(idx)
+    }
+
+    pub fn val_mut(&mut self, idx: usize) -> Option<&mut V> {
+        self.vals.get_mut(idx)
+    }
+
+    pub unsafe fn unsafe_val_mut(&mut self, idx: usize) -> &mut V {
+        self.vals.get_unchecked_mut(idx)
+    }
+
+    // The kv pair's addresses stay valid while the node is moved around, since they point into the
+    // key and value buffers rather than the node itself.
+    pub unsafe fn unsafe_kv_ptr(&mut self, idx: usize) -> (*mut K, *mut V) {
+        (self.keys.get_unchecked_mut(idx), self.vals.get_unchecked_mut(idx))
+    }
+
+    // Finds the value for `key` under `node` without making a `&mut` to any node or buffer on the
+    // way, so pointers handed out by earlier calls from the same `node` stay usable alongside it,
+    // even when they point into the same leaf.
+    pub unsafe fn find_val_ptr<Q, C>(mut node: *mut Self, key: &Q, cmp: &C) -> Option<*mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized,
+        C: Comparator<Q>,
+    {
+        loop {
+            match (*node).search(key, cmp) {
+                Found(i) => return Some((*node).vals.as_mut_ptr().add(i)),
+                GoDown(i) if (*node).is_leaf() => return None,
+                GoDown(i) => node = (*node).edges.as_mut_ptr().add(i),
+            }
+        }
+    }
+
+    pub fn edge(&self, idx: usize) -> Option<&Self> {
+        self.edges.get(idx)
+    }
+
+    pub fn edge_mut(&mut self, idx: usize) -> Option<&mut Node<K, V, A>> {
+        self.edges.get_mut(idx)
+    }
+
+    pub unsafe fn unsafe_edge_mut(&mut self, idx: usize) -> &mut Node<K, V, A> {
+        self.edges.get_unchecked_mut(idx)
+    }
+
+    pub fn pop_edge(&mut self) -> Option<Node<K, V, A>> {
+        self.edges.pop()
+    }
+
+    // If the node has any children
+    pub fn is_leaf(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    pub fn insert_as_leaf(
+        &mut self,
+        index: usize,
+        key: K,
+        value: V,
+        pool: &mut NodePool<K, V, A>,
+    ) -> (InsertionResult<K, V, A>, (*mut K, *mut V))
+    where
+        A: Clone,
+    {
+        if !self.is_full() {
+            self.insert_fit_as_leaf(index, key, value);
+            self.size += 1;
+            (Fit, unsafe { self.unsafe_kv_ptr(index) })
+        } else {
+            // The new element can't fit, split
+            let (new_key, new_val, mut new_right) = self.split(pool);
+            // now self is left
+            let left_len = self.len();
+
+            let ptr = if index <= left_len {
+                self.insert_fit_as_leaf(index, key, value);
+                unsafe { self.unsafe_kv_ptr(index) }
+            } else {
+                new_right.insert_fit_as_leaf(index - left_len - 1, key, value);
+                unsafe { new_right.unsafe_kv_ptr(index - left_len - 1) }
+            };
+            self.recount();
+            new_right.recount();
+            (Split(new_key, new_val, new_right), ptr)
+        }
+    }
+
+    pub fn insert_as_internal(
+        &mut self,
+        index: usize,
+        key: K,
+        value: V,
+        right: Node<K, V, A>,
+        pool: &mut NodePool<K, V, A>,
+    ) -> InsertionResult<K, V, A>
+    where
+        A: Clone,
+    {
+        if !self.is_full() {
+            // The child at `index` split into itself, `key` and `right`, which between them hold
+            // what the child used to plus the new entry.
+            self.insert_fit_as_internal(index, key, value, right);
+            self.size += 1;
+            Fit
+        } else {
+            // The new element can't fit.
+            let (new_key, new_val, mut new_right) = self.split(pool);
+            let left_len = self.len();
+            if index <= left_len {
+                self.insert_fit_as_internal(index, key, value, right);
+            } else {
+                new_right.insert_fit_as_internal(index - left_len - 1, key, value, right);
+            }
+            self.recount();
+            new_right.recount();
+            Split(new_key, new_val, new_right)
+        }
+    }
+
+    pub fn remove_as_leaf(&mut self, index: usize) -> (K, V) {
+        self.size -= 1;
+        (self.keys.remove(index), self.vals.remove(index))
+    }
+
+    pub fn handle_underflow(
+        &mut self,
+        underflowed_child_index: usize,
+        pool: &mut NodePool<K, V, A>,
+    ) {
+        assert!(underflowed_child_index <= self.len());
+        if underflowed_child_index > 0 {
+            unsafe { self.handle_underflow_to_left(underflowed_child_index, pool) };
+        } else {
+            unsafe { self.handle_underflow_to_right(underflowed_child_index, pool) };
+        }
+    }
+}
+
+// private functions
+impl<K, V, A: Allocator> Node<K, V, A> {
+    fn insert_fit_as_leaf(&mut self, index: usize, key: K, val: V) {
+        self.keys.insert(index, key);
+        self.vals.insert(index, val);
+    }
+
+    fn insert_fit_as_internal(&mut self, index: usize, key: K, val: V, right: Node<K, V, A>) {
+        self.keys.insert(index, key);
+        self.vals.insert(index, val);
+        self.edges.insert(index + 1, right);
+    }
+
+    // Node is full, so split it into two nodes, and yield the middle-most key-vale par
+    fn split(&mut self, pool: &mut NodePool<K, V, A>) -> (K, V, Node<K, V, A>)
+    where
+        A: Clone,
+    {
+        let mut right = pool.take(self.capacity(), !self.is_leaf());
+        split(&mut self.keys, &mut right.keys);
+        split(&mut self.vals, &mut right.vals);
+        if !self.is_leaf() {
+            split(&mut self.edges, &mut right.edges);
+        }
+
+        let key = self.keys.pop().unwrap();
+        let val = self.vals.pop().unwrap();
+
+        (key, val, right)
+    }
+
+    // Right is underflowed, try to steal from left.
+    // Merge if left is also underflowed.
+    unsafe fn handle_underflow_to_left(
+        &mut self,
+        underflowed_child_index: usize,
+        pool: &mut NodePool<K, V, A>,
+    ) {
+        let left_len = self.edges[underflowed_child_index - 1].len();
+        if left_len > min_load_from_capacity(self.capacity()) {
+            self.steal_to_left(underflowed_child_index);
+        } else {
+            self.merge_children(underflowed_child_index - 1, pool);
+        }
+    }
+
+    unsafe fn handle_underflow_to_right(
+        &mut self,
+        underflowed_child_index: usize,
+        pool: &mut NodePool<K, V, A>,
+    ) {
+        let right_len = self.edges[underflowed_child_index + 1].len();
+        if right_len > min_load_from_capacity(self.capacity()) {
+            self.steal_to_right(underflowed_child_index);
+        } else {
+            self.merge_children(underflowed_child_index, pool);
+        }
+    }
+
+    /// Steal! Stealing is roughly analagous to a binary tree rotation.
+    /// In this case, we're "rotating" right.
+    unsafe fn steal_to_left(&mut self, underflowed_child_index: usize) {
+        // Get the last kv pair from left
+        let (mut key, mut val, edge) = {
+            let left = self.unsafe_edge_mut(underflowed_child_index - 1);
+            match (left.keys.pop(), left.vals.pop(), left.edges.pop()) {
+                (Some(key), Some(val), edge) => (key, val, edge),
+                _ => unreachable!(),
+            }
+        };
+
+        let moved = 1 + edge.as_ref().map_or(0, |edge| edge.size);
+        self.unsafe_edge_mut(underflowed_child_index - 1).size -= moved;
+
+        // swap the parent's seperating kv pair node with left
+        self.unsafe_swap(underflowed_child_index - 1, &mut key, &mut val);
+
+        // put it to the begin of right node
+        let right = self.unsafe_edge_mut(underflowed_child_index);
+        right.keys.insert(0, key);
+        right.vals.insert(0, val);
+        if let Some(edge) = edge {
+            right.edges.insert(0, edge);
+        }
+        right.size += moved;
+    }
+
+    unsafe fn steal_to_right(&mut self, underflowed_child_index: usize) {
+        // Get the first kv pair from right
+        let (mut key, mut val, edge) = {
+            let right = self.unsafe_edge_mut(underflowed_child_index + 1);
+            if right.edges.is_empty() {
+                (right.keys.remove(0), right.vals.remove(0), None)
+            } else {
+                (
+                    right.keys.remove(0),
+                    right.vals.remove(0),
+                    Some(right.edges.remove(0)),
+                )
+            }
+        };
+
+        let moved = 1 + edge.as_ref().map_or(0, |edge| edge.size);
+        self.unsafe_edge_mut(underflowed_child_index + 1).size -= moved;
+
+        // swap the parent's seperating kv pair node.
+        self.unsafe_swap(underflowed_child_index, &mut key, &mut val);
+
+        // put it to the end of left node
+        let left = self.unsafe_edge_mut(underflowed_child_index);
+        left.keys.push(key);
+        left.vals.push(val);
+        if let Some(edge) = edge {
+            left.edges.push(edge);
+        }
+        left.size += moved;
+    }
+
+    unsafe fn merge_children(&mut self, left_index: usize, pool: &mut NodePool<K, V, A>) {
+        let (key, val, mut right) = (
+            self.keys.remove(left_index),
+            self.vals.remove(left_index),
+            self.edges.remove(left_index + 1),
+        );
+        let left = self.unsafe_edge_mut(left_index);
+        left.absorb(key, val, &mut right);
+        pool.put(right);
+    }
+
+    // Bring the last child up to the minimum load by rotating entries through the separating key
+    // from its left sibling, which must have enough to spare.
+    fn fix_last_edge(&mut self)
+    where
+        A: Clone,
+    {
+        let last = self.len();
+        let min_load = min_load_from_capacity(self.capacity());
+        let count = match min_load.checked_sub(self.edges[last].len()) {
+            Some(count) if count > 0 => count,
+            _ => return,
+        };
+        debug_assert!(self.edges[last - 1].len() >= min_load + count);
+        self.bulk_steal_left(last - 1, count);
+    }
+
+    // Rotate the last `count` kv pairs of the left child of kv `index` through it into the front
+    // of the right child, along with their edges.
+    fn bulk_steal_left(&mut self, index: usize, count: usize)
+    where
+        A: Clone,
+    {
+        let (left, right) = self.edges.split_at_mut(index + 1);
+        let (left, right) = (&mut left[index], &mut right[0]);
+        let split_at = left.len() - count;
+
+        // The first of the moved kv pairs becomes the new separator, and the old one goes to the
+        // right node, after the rest of the moved pairs.
+        let mut keys = left.keys.split_off(split_at);
+        let mut vals = left.vals.split_off(split_at);
+        mem::swap(&mut self.keys[index], &mut keys[0]);
+        mem::swap(&mut self.vals[index], &mut vals[0]);
+        keys.rotate_left(1);
+        vals.rotate_left(1);
+        right.keys.splice(0..0, keys);
+        right.vals.splice(0..0, vals);
+        if !right.is_leaf() {
+            let edges = left.edges.split_off(left.edges.len() - count);
+            right.edges.splice(0..0, edges);
+        }
+        left.recount();
+        right.recount();
+    }
+
+    // The mirror image of `bulk_steal_left`: the first `count` kv pairs of the right child go
+    // through kv `index` onto the end of the left child.
+    fn bulk_steal_right(&mut self, index: usize, count: usize) {
+        let (left, right) = self.edges.split_at_mut(index + 1);
+        let (left, right) = (&mut left[index], &mut right[0]);
+
+        // The last of the moved kv pairs becomes the new separator, and the old one goes to the
+        // left node, ahead of the rest of the moved pairs. They're moved straight onto the end of
+        // the left node and put in order there.
+        let split_at = left.len();
+        left.keys.extend(right.keys.drain(..count));
+        left.vals.extend(right.vals.drain(..count));
+        mem::swap(&mut self.keys[index], left.keys.last_mut().unwrap());
+        mem::swap(&mut self.vals[index], left.vals.last_mut().unwrap());
+        left.keys[split_at..].rotate_right(1);
+        left.vals[split_at..].rotate_right(1);
+        if !left.is_leaf() {
+            left.edges.extend(right.edges.drain(..count));
+        }
+        left.recount();
+        right.recount();
+    }
+
+    // Make the last child safe to descend into while fixing the right border: merge it with its
+    // left sibling if they fit in one node, or else top it up to one over the minimum, so that it
+    // can afford a merge further down.
+    fn fix_right_child(&mut self, pool: &mut NodePool<K, V, A>)
+    where
+        A: Clone,
+    {
+        let index = self.len() - 1;
+        let (left_len, right_len) = (self.edges[index].len(), self.edges[index + 1].len());
+        if left_len + 1 + right_len <= self.capacity() {
+            unsafe { self.merge_children(index, pool) };
+        } else {
+            let wanted = min_load_from_capacity(self.capacity()) + 1;
+            if let Some(count @ 1..) = wanted.checked_sub(right_len) {
+                self.bulk_steal_left(index, count);
+            }
+        }
+    }
+
+    // The mirror image of `fix_right_child`, for the first child.
+    fn fix_left_child(&mut self, pool: &mut NodePool<K, V, A>) {
+        let (left_len, right_len) = (self.edges[0].len(), self.edges[1].len());
+        if left_len + 1 + right_len <= self.capacity() {
+            unsafe { self.merge_children(0, pool) };
+        } else {
+            let wanted = min_load_from_capacity(self.capacity()) + 1;
+            if let Some(count @ 1..) = wanted.checked_sub(left_len) {
+                self.bulk_steal_right(0, count);
+            }
+        }
+    }
+
+    // Move everything from `right` into self, leaving `right` empty but with its buffers intact.
+    fn absorb(&mut self, key: K, val: V, right: &mut Node<K, V, A>) {
+        debug_assert!(self.len() + right.len() <= self.capacity());
+
+        self.keys.push(key);
+        self.vals.push(val);
+        self.keys.append(&mut right.keys);
+        self.vals.append(&mut right.vals);
+        self.edges.append(&mut right.edges);
+        self.size += 1 + mem::take(&mut right.size);
+    }
+
+    // Work out the size from scratch, assuming the children's sizes are right.
+    fn recount(&mut self) {
+        self.size = self.len() + self.edges.iter().map(|edge| edge.size).sum::<usize>();
+    }
+
+    fn recount_all(&mut self) {
+        for edge in self.edges.iter_mut() {
+            edge.recount_all();
+        }
+        self.recount();
+    }
+}
+
+// An empty buffer with room for `capacity` elements.
+fn buffer<T, A: Allocator + Clone>(capacity: usize, alloc: &A) -> MyVec<T, A> {
+    let mut buffer = MyVec::new_in(alloc.clone());
+    buffer.reserve_exact(capacity);
+    buffer
+}
+
+// Takes a buffer, and moves half the elements into an empty one.
+fn split<T, A: Allocator>(left: &mut MyVec<T, A>, right: &mut MyVec<T, A>) {
+    debug_assert!(right.is_empty());
+    let len = left.len();
+    let right_len = len / 2;
+    let left_len = len - right_len;
+    right.reserve_exact(left.capacity());
+    unsafe {
+        let left_ptr = left.get_unchecked_mut(left_len) as *mut _;
+        let right_ptr = right.as_mut_ptr();
+        ptr::copy_nonoverlapping(left_ptr, right_ptr, right_len);
+        left.set_len(left_len);
+        right.set_len(right_len);
+    }
+}
+
+pub fn capacity_from_b(b: usize) -> usize {
+    2 * b - 1
+}
+
+fn min_load_from_capacity(capacity: usize) -> usize {
+    // b - 1
+    capacity / 2
+}
+
+/// A stash of emptied nodes. Merges hand their leftover node back here, and splits and new roots
+/// take from it, so heavy insert/remove churn reuses node buffers instead of reallocating them.
+/// New nodes get their buffers from the pool's allocator.
+pub struct NodePool<K, V, A: Allocator = Global> {
+    nodes: Vec<Node<K, V, A>>,
+    limit: usize,
+    alloc: A,
+}
+
+impl<K, V, A: Allocator> NodePool<K, V, A> {
+    /// Make a pool that holds on to at most `limit` nodes.
+    pub fn new(limit: usize, alloc: A) -> NodePool<K, V, A> {
+        NodePool {
+            nodes: Vec::new(),
+            limit,
+            alloc,
+        }
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Take an empty node with room for `capacity` kv pairs, and for edges if it's `internal`.
+    pub fn take(&mut self, capacity: usize, internal: bool) -> Node<K, V, A>
+    where
+        A: Clone,
+    {
+        match self.nodes.pop() {
+            Some(mut node) => {
+                if internal {
+                    node.edges.reserve_exact(capacity + 1);
+                }
+                node
+            }
+            None if internal => Node::new_internal(capacity, &self.alloc),
+            None => Node::new_leaf(capacity, &self.alloc),
+        }
+    }
+
+    /// Stock the pool with up to `count` fresh leaves with room for `capacity` kv pairs, as far as
+    /// the limit allows.
+    pub fn fill(&mut self, capacity: usize, count: usize)
+    where
+        A: Clone,
+    {
+        let count = count.min(self.limit.saturating_sub(self.nodes.len()));
+        self.nodes
+            .extend((0..count).map(|_| Node::new_leaf(capacity, &self.alloc)));
+    }
+
+    /// Free every node in the pool.
+    pub fn clear(&mut self) {
+        self.nodes = Vec::new();
+    }
+
+    /// Give back a node that has been emptied and unlinked from the tree.
+    pub fn put(&mut self, mut node: Node<K, V, A>) {
+        debug_assert!(node.keys.is_empty() && node.vals.is_empty() && node.edges.is_empty());
+        node.size = 0;
+        if self.nodes.len() < self.limit {
+            self.nodes.push(node);
+        }
+    }
+}
+
+pub enum SearchResult {
+    Found(usize),
+    GoDown(usize),
+}
+
+pub enum InsertionResult<K, V, A: Allocator> {
+    Fit,
+    Split(K, V, Node<K, V, A>),
+}
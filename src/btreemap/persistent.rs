@@ -0,0 +1,539 @@
+use super::inline_stack::InlineStack;
+use std::{borrow::Borrow, cmp::Ordering::*, fmt, iter::FusedIterator, mem, sync::Arc};
+
+/// An immutable ordered map. `insert` and `remove` leave the map alone and return a new one, which
+/// shares every node off the changed path with the old map, so keeping old versions around as
+/// snapshots is cheap. Cloning the map is O(1).
+///
+/// It also works as a copy-on-write map: clone it for a snapshot, then change the clone in place
+/// with `insert_in_place`, `remove_in_place` or `get_mut`. Only the paths down to the changed keys
+/// get copied, the first time they're touched.
+///
+/// The nodes are laid out like `BTreeMap`'s, but children are held through `Arc` so they can be
+/// shared, and a node is only copied when a change has to go through it while it's shared.
+pub struct PersistentBTreeMap<K, V> {
+    root: Arc<Node<K, V>>,
+    length: usize,
+    b: usize,
+}
+
+struct Node<K, V> {
+    keys: Vec<K>,
+    vals: Vec<V>,
+    edges: Vec<Arc<Node<K, V>>>,
+}
+
+enum Insertion<K, V> {
+    Fit,
+    Replaced(V),
+    Split(K, V, Arc<Node<K, V>>),
+}
+
+impl<K, V> Clone for PersistentBTreeMap<K, V> {
+    fn clone(&self) -> Self {
+        PersistentBTreeMap {
+            root: Arc::clone(&self.root),
+            length: self.length,
+            b: self.b,
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Node<K, V> {
+    fn clone(&self) -> Self {
+        // The children are shared rather than copied.
+        Node {
+            keys: self.keys.clone(),
+            vals: self.vals.clone(),
+            edges: self.edges.clone(),
+        }
+    }
+}
+
+impl<K: Ord, V> Default for PersistentBTreeMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> PersistentBTreeMap<K, V> {
+    /// Makes a new empty PersistentBTreeMap with a reasonable choice for B.
+    pub fn new() -> PersistentBTreeMap<K, V> {
+        PersistentBTreeMap::with_b(6)
+    }
+
+    /// Makes a new empty PersistentBTreeMap with the given B.
+    pub fn with_b(b: usize) -> PersistentBTreeMap<K, V> {
+        assert!(b > 1, "B must be greater than 1");
+        PersistentBTreeMap {
+            root: Arc::new(Node::new()),
+            length: 0,
+            b,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn find<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let mut node = &*self.root;
+        loop {
+            match node.search(key) {
+                Ok(i) => return Some(&node.vals[i]),
+                Err(i) => node = node.edges.get(i)?,
+            }
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.find(key).is_some()
+    }
+
+    /// Gets an iterator over the entries of the map, sorted by key.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        let mut iter = Iter {
+            stack: InlineStack::new(),
+            length: self.length,
+        };
+        iter.descend(&self.root);
+        iter
+    }
+
+    /// Returns whether both maps are the same version, sharing their whole tree.
+    pub fn ptr_eq(&self, other: &PersistentBTreeMap<K, V>) -> bool {
+        Arc::ptr_eq(&self.root, &other.root)
+    }
+
+    fn capacity(&self) -> usize {
+        2 * self.b - 1
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> PersistentBTreeMap<K, V> {
+    /// Returns a new map with `key` set to `value`, leaving this one as it was.
+    pub fn insert(&self, key: K, value: V) -> PersistentBTreeMap<K, V> {
+        let mut map = self.clone();
+        map.insert_in_place(key, value);
+        map
+    }
+
+    /// Returns a new map without `key`, leaving this one as it was. If `key` isn't in the map the
+    /// new map is just another handle on this one.
+    pub fn remove<Q>(&self, key: &Q) -> PersistentBTreeMap<K, V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let mut map = self.clone();
+        map.remove_in_place(key);
+        map
+    }
+
+    /// Sets `key` to `value` in this version of the map, and returns the old value. Nodes shared
+    /// with other versions are copied before they're changed, so the other versions don't see it.
+    pub fn insert_in_place(&mut self, key: K, value: V) -> Option<V> {
+        let capacity = self.capacity();
+        match Arc::make_mut(&mut self.root).insert(key, value, capacity) {
+            Insertion::Replaced(old) => return Some(old),
+            Insertion::Fit => {}
+            Insertion::Split(key, val, right) => {
+                let left = mem::replace(&mut self.root, Arc::new(Node::new()));
+                let root = Arc::make_mut(&mut self.root);
+                root.keys.push(key);
+                root.vals.push(val);
+                root.edges.push(left);
+                root.edges.push(right);
+            }
+        }
+        self.length += 1;
+        None
+    }
+
+    /// Removes `key` from this version of the map, and returns its entry. Like `insert_in_place`,
+    /// other versions are left alone.
+    pub fn remove_in_place<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        // Don't copy a path down to a key that isn't there.
+        if !self.contains_key(key) {
+            return None;
+        }
+        let min_load = self.capacity() / 2;
+        let root = Arc::make_mut(&mut self.root);
+        let entry = root.remove(key, min_load);
+        if root.keys.is_empty() {
+            if let Some(child) = root.edges.pop() {
+                self.root = child;
+            }
+        }
+        self.length -= 1;
+        entry
+    }
+
+    /// Gets a mutable reference to the value for `key` in this version of the map. The path down
+    /// to it is copied first if it's shared with other versions.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        // As with removal, leave the tree shared when the key isn't there.
+        if !self.contains_key(key) {
+            return None;
+        }
+        let mut node = Arc::make_mut(&mut self.root);
+        loop {
+            match node.search(key) {
+                Ok(i) => return Some(&mut node.vals[i]),
+                Err(i) => node = Arc::make_mut(&mut node.edges[i]),
+            }
+        }
+    }
+}
+
+impl<K, V> Node<K, V> {
+    fn new() -> Node<K, V> {
+        Node {
+            keys: Vec::new(),
+            vals: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        self.keys.binary_search_by(|k| k.borrow().cmp(key))
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Node<K, V> {
+    // Insert below this node, which must already be unshared. A node is allowed to overflow by
+    // one before it's split in two around its middle key.
+    fn insert(&mut self, key: K, value: V, capacity: usize) -> Insertion<K, V> {
+        match self.search(&key) {
+            Ok(i) => return Insertion::Replaced(mem::replace(&mut self.vals[i], value)),
+            Err(i) if self.is_leaf() => {
+                self.keys.insert(i, key);
+                self.vals.insert(i, value);
+            }
+            Err(i) => match Arc::make_mut(&mut self.edges[i]).insert(key, value, capacity) {
+                Insertion::Split(key, val, right) => {
+                    self.keys.insert(i, key);
+                    self.vals.insert(i, val);
+                    self.edges.insert(i + 1, right);
+                }
+                other => return other,
+            },
+        }
+        if self.keys.len() <= capacity {
+            return Insertion::Fit;
+        }
+
+        let mid = self.keys.len() / 2;
+        let right = Node {
+            keys: self.keys.split_off(mid + 1),
+            vals: self.vals.split_off(mid + 1),
+            edges: if self.is_leaf() {
+                Vec::new()
+            } else {
+                self.edges.split_off(mid + 1)
+            },
+        };
+        let key = self.keys.pop().unwrap();
+        let val = self.vals.pop().unwrap();
+        Insertion::Split(key, val, Arc::new(right))
+    }
+
+    // Remove `key`, which must be somewhere below this unshared node, and fix up any child that
+    // drops below `min_load` on the way back up.
+    fn remove<Q>(&mut self, key: &Q, min_load: usize) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Ord,
+    {
+        let entry = match self.search(key) {
+            Ok(i) if self.is_leaf() => return Some((self.keys.remove(i), self.vals.remove(i))),
+            Ok(i) => {
+                // Swap in the greatest entry of the left subtree, which always comes from a leaf.
+                let (key, val) = Arc::make_mut(&mut self.edges[i]).remove_last(min_load);
+                let entry = (
+                    mem::replace(&mut self.keys[i], key),
+                    mem::replace(&mut self.vals[i], val),
+                );
+                self.fix_child(i, min_load);
+                entry
+            }
+            Err(_) if self.is_leaf() => return None,
+            Err(i) => {
+                let entry = Arc::make_mut(&mut self.edges[i]).remove(key, min_load);
+                self.fix_child(i, min_load);
+                entry?
+            }
+        };
+        Some(entry)
+    }
+
+    fn remove_last(&mut self, min_load: usize) -> (K, V) {
+        if self.is_leaf() {
+            return (self.keys.pop().unwrap(), self.vals.pop().unwrap());
+        }
+        let last = self.keys.len();
+        let entry = Arc::make_mut(&mut self.edges[last]).remove_last(min_load);
+        self.fix_child(last, min_load);
+        entry
+    }
+
+    // Top up an underfull child from a sibling with entries to spare, or merge it with one.
+    fn fix_child(&mut self, i: usize, min_load: usize) {
+        if self.edges[i].keys.len() >= min_load {
+            return;
+        }
+        if i > 0 && self.edges[i - 1].keys.len() > min_load {
+            let left = Arc::make_mut(&mut self.edges[i - 1]);
+            let (mut key, mut val) = (left.keys.pop().unwrap(), left.vals.pop().unwrap());
+            let edge = left.edges.pop();
+            mem::swap(&mut self.keys[i - 1], &mut key);
+            mem::swap(&mut self.vals[i - 1], &mut val);
+            let child = Arc::make_mut(&mut self.edges[i]);
+            child.keys.insert(0, key);
+            child.vals.insert(0, val);
+            if let Some(edge) = edge {
+                child.edges.insert(0, edge);
+            }
+        } else if i < self.keys.len() && self.edges[i + 1].keys.len() > min_load {
+            let right = Arc::make_mut(&mut self.edges[i + 1]);
+            let (mut key, mut val) = (right.keys.remove(0), right.vals.remove(0));
+            let edge = (!right.is_leaf()).then(|| right.edges.remove(0));
+            mem::swap(&mut self.keys[i], &mut key);
+            mem::swap(&mut self.vals[i], &mut val);
+            let child = Arc::make_mut(&mut self.edges[i]);
+            child.keys.push(key);
+            child.vals.push(val);
+            child.edges.extend(edge);
+        } else {
+            let left_index = if i > 0 { i - 1 } else { i };
+            let key = self.keys.remove(left_index);
+            let val = self.vals.remove(left_index);
+            let right = Arc::unwrap_or_clone(self.edges.remove(left_index + 1));
+            let left = Arc::make_mut(&mut self.edges[left_index]);
+            left.keys.push(key);
+            left.vals.push(val);
+            left.keys.extend(right.keys);
+            left.vals.extend(right.vals);
+            left.edges.extend(right.edges);
+        }
+    }
+}
+
+/// An iterator over the entries of a PersistentBTreeMap, in key order.
+pub struct Iter<'a, K, V> {
+    // The path to the next entry: each node with the index of the next key to yield in it.
+    stack: InlineStack<(&'a Node<K, V>, usize)>,
+    length: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn descend(&mut self, mut node: &'a Node<K, V>) {
+        loop {
+            self.stack.push((node, 0));
+            match node.edges.first() {
+                Some(next) => node = next,
+                None => return,
+            }
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        while let Some(&mut (node, ref mut index)) = self.stack.last_mut() {
+            let i = *index;
+            if i < node.keys.len() {
+                *index += 1;
+                if let Some(edge) = node.edges.get(i + 1) {
+                    self.descend(edge);
+                }
+                self.length -= 1;
+                return Some((&node.keys[i], &node.vals[i]));
+            }
+            self.stack.pop();
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.length, Some(self.length))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<'a, K: Ord, V> IntoIterator for &'a PersistentBTreeMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for PersistentBTreeMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Ord, V: PartialEq> PartialEq for PersistentBTreeMap<K, V> {
+    fn eq(&self, other: &PersistentBTreeMap<K, V>) -> bool {
+        self.len() == other.len() && (self.ptr_eq(other) || self.iter().eq(other.iter()))
+    }
+}
+
+impl<K: Ord, V: Eq> Eq for PersistentBTreeMap<K, V> {}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V> arbitrary::Arbitrary<'a> for PersistentBTreeMap<K, V>
+where
+    K: Ord + Clone + arbitrary::Arbitrary<'a>,
+    V: Clone + arbitrary::Arbitrary<'a>,
+{
+    /// Like `BTreeMap`'s, picks an arbitrary B along with the entries.
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut map = PersistentBTreeMap::with_b(u.int_in_range(2..=8)?);
+        for entry in u.arbitrary_iter()? {
+            let (key, value) = entry?;
+            map.insert_in_place(key, value);
+        }
+        Ok(map)
+    }
+
+    fn arbitrary_take_rest(mut u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut map = PersistentBTreeMap::with_b(u.int_in_range(2..=8)?);
+        for entry in u.arbitrary_take_rest_iter()? {
+            let (key, value) = entry?;
+            map.insert_in_place(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> FromIterator<(K, V)> for PersistentBTreeMap<K, V> {
+    /// Builds a map from an iterator of pairs. Later duplicates overwrite earlier ones.
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> PersistentBTreeMap<K, V> {
+        let mut map = PersistentBTreeMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Extend<(K, V)> for PersistentBTreeMap<K, V> {
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (key, value) in iter {
+            self.insert_in_place(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PersistentBTreeMap;
+    use crate::test_util::Lcg;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_snapshots() {
+        let mut versions = vec![PersistentBTreeMap::with_b(2)];
+        let mut model = vec![std::collections::BTreeMap::new()];
+        let mut rng = Lcg::new(7);
+        for i in 0..2000 {
+            let key = rng.below(300);
+            let (map, expected) = (versions.last().unwrap(), model.last().unwrap());
+            let (mut map, mut expected) = (map.clone(), expected.clone());
+            if i % 3 == 0 {
+                map = map.remove(&key);
+                expected.remove(&key);
+            } else {
+                map = map.insert(key, i);
+                expected.insert(key, i);
+            }
+            versions.push(map);
+            model.push(expected);
+        }
+        // Every old version still holds exactly what it did when it was made.
+        for (map, expected) in versions.iter().zip(&model) {
+            assert_eq!(map.len(), expected.len());
+            assert!(map.iter().eq(expected.iter()));
+        }
+    }
+
+    #[test]
+    fn test_sharing() {
+        let map: PersistentBTreeMap<_, _> = (0..1000).map(|i| (i, i)).collect();
+        let root_edges = map.root.edges.len();
+        let next = map.insert(0, -1);
+        assert_eq!(map.find(&0), Some(&0));
+        assert_eq!(next.find(&0), Some(&-1));
+        // Only the subtree on the path to the first key was copied.
+        let shared = map
+            .root
+            .edges
+            .iter()
+            .zip(&next.root.edges)
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count();
+        assert_eq!(shared, root_edges - 1);
+
+        assert!(map.remove(&5000).ptr_eq(&map));
+        assert!(!map.remove(&5).ptr_eq(&map));
+        assert_eq!(map.remove(&5).len(), 999);
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let map: PersistentBTreeMap<_, _> = (0..1000).map(|i| (i, i)).collect();
+        let mut snapshot = map.clone();
+        assert_eq!(snapshot.get_mut(&5000), None);
+        assert!(snapshot.ptr_eq(&map));
+
+        *snapshot.get_mut(&999).unwrap() = -1;
+        *snapshot.get_mut(&998).unwrap() += 1;
+        assert_eq!(map.find(&999), Some(&999));
+        assert_eq!(snapshot.find(&999), Some(&-1));
+        assert_eq!(snapshot.find(&998), Some(&999));
+        // Both changes went down the same, already copied, path.
+        let shared = map
+            .root
+            .edges
+            .iter()
+            .zip(&snapshot.root.edges)
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count();
+        assert_eq!(shared, map.root.edges.len() - 1);
+    }
+}
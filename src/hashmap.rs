@@ -0,0 +1,8 @@
+mod cuckoo;
+pub mod indexmap;
+pub mod map;
+pub mod set;
+mod swiss;
+
+pub use self::cuckoo::{CuckooHashMap, CuckooIter};
+pub use self::swiss::{FastHashMap, FastIter};
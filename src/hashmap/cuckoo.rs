@@ -0,0 +1,451 @@
+use crate::myvec::MyVec;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    iter::{Chain, FusedIterator},
+    mem,
+    ops::Index,
+    slice,
+};
+
+// How many times a rebuild tries new seeds before doubling the tables, and how many doublings it
+// gives up after.
+const SEEDS_PER_SIZE: usize = 4;
+const MAX_REBUILD_ATTEMPTS: usize = 16;
+
+/// An unordered map using cuckoo hashing.
+///
+/// Every key has exactly one possible slot in each of two tables, picked by two hash functions,
+/// so a lookup checks two slots and nothing else, no matter how full the map is or how unlucky
+/// the keys are. Inserting pays for that: if both slots are taken, the new entry kicks one of the
+/// occupants out, which moves to its slot in the other table, possibly kicking out another, and so
+/// on. If that chain runs too long, the hash functions are reseeded and the table rebuilt.
+///
+/// The tables are kept at most half full, which is where chains stay short. That makes this a
+/// good fit for maps that are read far more often than written, where the worst case lookup
+/// matters more than memory.
+///
+/// # Panics
+///
+/// Inserting panics if the hasher gives so many keys the same pair of slots that no reseeding
+/// can separate them, as happens with a hasher that ignores its input.
+pub struct CuckooHashMap<K, V, S = RandomState> {
+    // Both tables are always the same size, 0 or a power of two.
+    tables: [MyVec<Option<Bucket<K, V>>>; 2],
+    len: usize,
+    // Fed to the hasher ahead of each key. Changing it picks new hash functions.
+    seed: u64,
+    hash_builder: S,
+}
+
+#[derive(Clone)]
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+impl<K, V> CuckooHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> CuckooHashMap<K, V, S> {
+    /// Makes an empty map that hashes keys with `hash_builder`. Like `new`, this doesn't
+    /// allocate until the first insert.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        CuckooHashMap {
+            tables: [MyVec::new(), MyVec::new()],
+            len: 0,
+            seed: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of entries the map can hold before the tables grow.
+    pub fn capacity(&self) -> usize {
+        self.buckets()
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Removes every entry, keeping the tables.
+    pub fn clear(&mut self) {
+        for table in &mut self.tables {
+            table.iter_mut().for_each(|slot| *slot = None);
+        }
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> CuckooIter<'_, K, V> {
+        CuckooIter {
+            slots: self.tables[0].iter().chain(self.tables[1].iter()),
+            len: self.len,
+        }
+    }
+
+    // The size of each table.
+    fn buckets(&self) -> usize {
+        self.tables[0].len()
+    }
+
+    // The slot a hash picks in each table. The two halves of one 64-bit hash are the two hash
+    // functions.
+    fn slot(&self, table: usize, hash: u64) -> usize {
+        let half = if table == 0 { hash } else { hash >> 32 };
+        half as u32 as usize & (self.buckets() - 1)
+    }
+
+    // Puts `bucket` into the tables, kicking entries along until one lands in an empty slot.
+    // Gives up after a bounded number of moves, handing back whichever entry is left homeless.
+    fn place(&mut self, mut bucket: Bucket<K, V>) -> Result<(), Bucket<K, V>> {
+        // Chains on a half-full table are short with high probability; anything much longer
+        // than log n is almost certainly a cycle.
+        let max_kicks = 8 + 4 * self.buckets().trailing_zeros() as usize;
+        let mut table = 0;
+        for _ in 0..max_kicks {
+            let index = self.slot(table, bucket.hash);
+            match self.tables[table][index].replace(bucket) {
+                None => {
+                    self.len += 1;
+                    return Ok(());
+                }
+                Some(evicted) => bucket = evicted,
+            }
+            table ^= 1;
+        }
+        Err(bucket)
+    }
+
+    // Takes every entry out of the tables, leaving them empty.
+    fn drain_into(&mut self, entries: &mut MyVec<Bucket<K, V>>) {
+        for table in &mut self.tables {
+            entries.extend(table.iter_mut().filter_map(Option::take));
+        }
+        self.len = 0;
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> CuckooHashMap<K, V, S> {
+    /// Makes sure at least `additional` more entries fit before the tables have to grow.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            let buckets = needed
+                .checked_next_power_of_two()
+                .expect("capacity overflow")
+                .max(8);
+            self.rebuild(buckets, None);
+        }
+    }
+
+    /// Inserts a key and value, returning the old value if the key was already there. The key
+    /// already in the map is kept in that case.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.get_mut(&key) {
+            return Some(mem::replace(old, value));
+        }
+        self.reserve(1);
+        let hash = self.hash(&key);
+        if let Err(homeless) = self.place(Bucket { hash, key, value }) {
+            self.rebuild(self.buckets(), Some(homeless));
+        }
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (table, index) = self.find(key)?;
+        let bucket = self.tables[table][index].as_ref().unwrap();
+        Some((&bucket.key, &bucket.value))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (table, index) = self.find(key)?;
+        Some(&mut self.tables[table][index].as_mut().unwrap().value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (table, index) = self.find(key)?;
+        let bucket = self.tables[table][index].take().unwrap();
+        self.len -= 1;
+        Some((bucket.key, bucket.value))
+    }
+
+    fn hash<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write_u64(self.seed);
+        key.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Returns which table the key is in and where. These are the only two slots it can be in.
+    fn find<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let hash = self.hash(key);
+        (0..2)
+            .map(|table| (table, self.slot(table, hash)))
+            .find(|&(table, index)| {
+                matches!(&self.tables[table][index],
+                Some(bucket) if bucket.hash == hash && bucket.key.borrow() == key)
+            })
+    }
+
+    // Puts every entry, plus `extra`, into fresh tables of at least `buckets` slots each, picking
+    // new hash functions until they all fit.
+    fn rebuild(&mut self, mut buckets: usize, extra: Option<Bucket<K, V>>) {
+        let mut pending = MyVec::new();
+        pending.extend(extra);
+        self.drain_into(&mut pending);
+        for attempt in 1.. {
+            assert!(
+                attempt <= MAX_REBUILD_ATTEMPTS,
+                "too many keys hash to the same slots"
+            );
+            self.seed = self.seed.wrapping_add(1);
+            self.tables = [MyVec::new(), MyVec::new()];
+            self.tables
+                .iter_mut()
+                .for_each(|t| t.resize_with(buckets, || None));
+            let mut failed = false;
+            while let Some(mut bucket) = pending.pop() {
+                bucket.hash = self.hash(&bucket.key);
+                if let Err(homeless) = self.place(bucket) {
+                    pending.push(homeless);
+                    self.drain_into(&mut pending);
+                    failed = true;
+                    break;
+                }
+            }
+            if !failed {
+                return;
+            }
+            if attempt % SEEDS_PER_SIZE == 0 {
+                buckets *= 2;
+            }
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for CuckooHashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for CuckooHashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        CuckooHashMap {
+            tables: self.tables.clone(),
+            len: self.len,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for CuckooHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for CuckooHashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for CuckooHashMap<K, V, S> {}
+
+impl<K, Q, V, S> Index<&Q> for CuckooHashMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not in map")
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for CuckooHashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|(k, v)| {
+            self.insert(k, v);
+        });
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V, S> arbitrary::Arbitrary<'a> for CuckooHashMap<K, V, S>
+where
+    K: Hash + Eq + arbitrary::Arbitrary<'a>,
+    V: arbitrary::Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for CuckooHashMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = CuckooHashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+type Slots<'a, K, V> = slice::Iter<'a, Option<Bucket<K, V>>>;
+
+/// An iterator over the entries of a CuckooHashMap, in no particular order.
+pub struct CuckooIter<'a, K, V> {
+    slots: Chain<Slots<'a, K, V>, Slots<'a, K, V>>,
+    len: usize,
+}
+
+impl<'a, K, V> Iterator for CuckooIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let bucket = self.slots.find_map(Option::as_ref)?;
+        self.len -= 1;
+        Some((&bucket.key, &bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for CuckooIter<'_, K, V> {}
+
+impl<K, V> FusedIterator for CuckooIter<'_, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a CuckooHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = CuckooIter<'a, K, V>;
+    fn into_iter(self) -> CuckooIter<'a, K, V> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CuckooHashMap;
+    use std::hash::{BuildHasher, Hasher};
+
+    #[test]
+    fn test_insert_remove() {
+        let mut map = CuckooHashMap::new();
+        for i in 0..5000 {
+            assert_eq!(map.insert(i, i * 2), None);
+        }
+        assert_eq!(map.insert(3, 0), Some(6));
+        assert!(map.capacity() >= map.len() && map.capacity() <= 16384);
+        for i in (0..5000).step_by(3) {
+            assert!(map.remove(&i).is_some());
+        }
+        for i in 0..5000 {
+            let expected = (i % 3 != 0).then_some(i * 2);
+            assert_eq!(map.get(&i).copied(), expected, "{i}");
+        }
+        *map.get_mut(&1).unwrap() += 1;
+        assert_eq!(map[&1], 3);
+        assert_eq!(map.iter().count(), map.len());
+        assert_eq!(map.clone(), map);
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    // Gives every key the same hash, which no seed can help with.
+    struct Constant;
+    impl BuildHasher for Constant {
+        type Hasher = ConstantHasher;
+        fn build_hasher(&self) -> ConstantHasher {
+            ConstantHasher
+        }
+    }
+    struct ConstantHasher;
+    impl Hasher for ConstantHasher {
+        fn finish(&self) -> u64 {
+            42
+        }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    #[should_panic(expected = "too many keys hash to the same slots")]
+    fn test_unplaceable() {
+        let mut map = CuckooHashMap::with_hasher(Constant);
+        // Two keys fit, one in each table. The third has nowhere to go.
+        map.insert(1, ());
+        map.insert(2, ());
+        assert_eq!(map.len(), 2);
+        map.insert(3, ());
+    }
+}
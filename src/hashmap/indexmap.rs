@@ -0,0 +1,512 @@
+mod iter;
+
+pub use self::iter::{IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+
+use crate::myvec::MyVec;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::{Index, IndexMut},
+};
+
+// The index table grows once it's this full. Plain linear probing wants more room than Robin
+// Hood does, but the table only holds a usize per slot, so the room is cheap.
+const MAX_LOAD_NUM: usize = 3;
+const MAX_LOAD_DEN: usize = 4;
+const MIN_CAPACITY: usize = 8;
+
+/// A map that remembers the order its keys were first inserted in.
+///
+/// The entries live in one dense vector, in insertion order, and a separate hash table maps each
+/// key to where its entry is in that vector. Iterating walks the vector, so it's in order and as
+/// fast as iterating a slice, and entries can be looked up by position as well as by key.
+///
+/// Inserting a key that's already there updates its value in place, without moving it. There are
+/// two ways to remove: `swap_remove` moves the last entry into the gap, which is O(1) but
+/// disturbs the order, and `shift_remove` slides everything after the gap down a place, which
+/// keeps the order but is O(n).
+pub struct IndexMap<K, V, S = RandomState> {
+    entries: MyVec<Bucket<K, V>>,
+    // Each slot holds the position in `entries` of a key hashing to that slot or one before it,
+    // with linear probing. The capacity is always 0 or a power of two.
+    indices: MyVec<Option<usize>>,
+    hash_builder: S,
+}
+
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+impl<K, V> IndexMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S> {
+    /// Makes an empty map that hashes keys with `hash_builder`. Like `new`, this doesn't
+    /// allocate until the first insert.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        IndexMap {
+            entries: MyVec::new(),
+            indices: MyVec::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of entries the map can hold before the hash table has to grow.
+    pub fn capacity(&self) -> usize {
+        self.indices.len() / MAX_LOAD_DEN * MAX_LOAD_NUM
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Removes every entry, keeping the hash table.
+    pub fn clear(&mut self) {
+        self.entries.truncate(0);
+        self.indices.iter_mut().for_each(|slot| *slot = None);
+    }
+
+    /// Iterates over the entries in order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut(self.entries.iter_mut())
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.iter())
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.iter())
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// Returns the entry at position `index`, counting in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|b| (&b.key, &b.value))
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries.get_mut(index).map(|b| (&b.key, &mut b.value))
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.get_index(self.len().wrapping_sub(1))
+    }
+
+    /// Removes the last entry and returns it.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        let last = self.len().checked_sub(1)?;
+        Some(self.swap_remove_index(last))
+    }
+
+    /// Removes the entry at `index`, moving the last entry into its place. O(1).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove_index(&mut self, index: usize) -> (K, V) {
+        assert!(index < self.len(), "index out of bounds");
+        self.remove_slot(self.slot_of(index));
+        let last = self.len() - 1;
+        if index != last {
+            let slot = self.slot_of(last);
+            self.indices[slot] = Some(index);
+        }
+        let bucket = self.entries.swap_remove(index);
+        (bucket.key, bucket.value)
+    }
+
+    /// Removes the entry at `index`, moving every entry after it down a place. O(n).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn shift_remove_index(&mut self, index: usize) -> (K, V) {
+        assert!(index < self.len(), "index out of bounds");
+        self.remove_slot(self.slot_of(index));
+        for i in self.indices.iter_mut().flatten() {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+        let bucket = self.entries.remove(index);
+        (bucket.key, bucket.value)
+    }
+
+    fn mask(&self) -> usize {
+        self.indices.len().wrapping_sub(1)
+    }
+
+    // Finds the slot in the hash table pointing at entry `index`. Only needs the stored hash, so
+    // it works without `K: Hash`.
+    fn slot_of(&self, index: usize) -> usize {
+        let mask = self.mask();
+        let mut slot = self.entries[index].hash as usize & mask;
+        while self.indices[slot] != Some(index) {
+            slot = (slot + 1) & mask;
+        }
+        slot
+    }
+
+    // Points the first free slot for `hash` at entry `index`. The table must have room.
+    fn insert_slot(&mut self, hash: u64, index: usize) {
+        let mask = self.mask();
+        let mut slot = hash as usize & mask;
+        while self.indices[slot].is_some() {
+            slot = (slot + 1) & mask;
+        }
+        self.indices[slot] = Some(index);
+    }
+
+    // Empties a slot, then pulls later slots in the same cluster back into the hole wherever
+    // that's still at or after where their hash lands, so no lookup runs into a gap before its
+    // key. Must run while every entry the table points at is still in `entries`.
+    fn remove_slot(&mut self, mut hole: usize) {
+        let mask = self.mask();
+        self.indices[hole] = None;
+        let mut slot = hole;
+        loop {
+            slot = (slot + 1) & mask;
+            let Some(index) = self.indices[slot] else {
+                break;
+            };
+            let home = self.entries[index].hash as usize & mask;
+            if slot.wrapping_sub(home) & mask >= slot.wrapping_sub(hole) & mask {
+                self.indices[hole] = self.indices[slot].take();
+                hole = slot;
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> IndexMap<K, V, S> {
+    /// Makes sure at least `additional` more entries fit before the hash table has to grow.
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+        let needed = self
+            .len()
+            .checked_add(additional)
+            .expect("capacity overflow");
+        if needed > self.capacity() {
+            let slots = (needed * MAX_LOAD_DEN).div_ceil(MAX_LOAD_NUM);
+            let slots = slots
+                .checked_next_power_of_two()
+                .expect("capacity overflow")
+                .max(MIN_CAPACITY);
+            self.resize(slots);
+        }
+    }
+
+    /// Inserts a key and value, returning the old value if the key was already there. A new key
+    /// goes at the end; an existing one keeps both its key and its position.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_full(key, value).1
+    }
+
+    /// Like `insert`, but also returns the position of the entry.
+    pub fn insert_full(&mut self, key: K, value: V) -> (usize, Option<V>) {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find(hash, &key) {
+            let old = mem::replace(&mut self.entries[index].value, value);
+            return (index, Some(old));
+        }
+        self.reserve(1);
+        let index = self.len();
+        self.insert_slot(hash, index);
+        self.entries.push(Bucket { hash, key, value });
+        (index, None)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_full(key).map(|(_, _, v)| v)
+    }
+
+    /// Returns the position of the entry for `key`, along with the entry.
+    pub fn get_full<Q>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        let bucket = &self.entries[index];
+        Some((index, &bucket.key, &bucket.value))
+    }
+
+    /// Returns the position of the entry for `key`, counting in insertion order.
+    pub fn get_index_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hash_builder.hash_one(key), key)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        Some(&mut self.entries[index].value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_index_of(key).is_some()
+    }
+
+    /// Removes the entry for `key`, moving the last entry into its place. O(1).
+    pub fn swap_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        Some(self.swap_remove_index(index).1)
+    }
+
+    /// Removes the entry for `key`, moving every entry after it down a place. O(n).
+    pub fn shift_remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(key)?;
+        Some(self.shift_remove_index(index).1)
+    }
+
+    // Returns the position in `entries` of the key's entry.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let mask = self.mask();
+        let mut slot = hash as usize & mask;
+        while let Some(index) = self.indices[slot] {
+            let bucket = &self.entries[index];
+            if bucket.hash == hash && bucket.key.borrow() == key {
+                return Some(index);
+            }
+            slot = (slot + 1) & mask;
+        }
+        None
+    }
+
+    fn resize(&mut self, slots: usize) {
+        self.indices = MyVec::new();
+        self.indices.resize(slots, None);
+        for index in 0..self.len() {
+            self.insert_slot(self.entries[index].hash, index);
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for IndexMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for IndexMap<K, V, S> {
+    fn clone(&self) -> Self {
+        IndexMap {
+            entries: self.entries.clone(),
+            indices: self.indices.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Bucket<K, V> {
+    fn clone(&self) -> Self {
+        Bucket {
+            hash: self.hash,
+            key: self.key.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for IndexMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Like any other map, two are equal when they hold the same entries, whatever order they're in.
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for IndexMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for IndexMap<K, V, S> {}
+
+impl<K, Q, V, S> Index<&Q> for IndexMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not in map")
+    }
+}
+
+impl<K, V, S> Index<usize> for IndexMap<K, V, S> {
+    type Output = V;
+    fn index(&self, index: usize) -> &V {
+        &self.entries[index].value
+    }
+}
+
+impl<K, V, S> IndexMut<usize> for IndexMap<K, V, S> {
+    fn index_mut(&mut self, index: usize) -> &mut V {
+        &mut self.entries[index].value
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for IndexMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|(k, v)| {
+            self.insert(k, v);
+        });
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V, S> arbitrary::Arbitrary<'a> for IndexMap<K, V, S>
+where
+    K: Hash + Eq + arbitrary::Arbitrary<'a>,
+    V: arbitrary::Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for IndexMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = IndexMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IndexMap;
+    use std::hash::{BuildHasher, Hasher};
+
+    #[test]
+    fn test_order() {
+        let mut map = IndexMap::new();
+        for word in ["d", "a", "c", "b", "e"] {
+            map.insert(word, word.to_uppercase());
+        }
+        assert_eq!(map.insert_full("c", "see".into()), (2, Some("C".into())));
+        assert_eq!(map.keys().copied().collect::<String>(), "dacbe");
+        assert_eq!(map.get_index(2), Some((&"c", &"see".to_string())));
+        assert_eq!(map.get_index_of("b"), Some(3));
+        assert_eq!(map.first().map(|(k, _)| *k), Some("d"));
+        assert_eq!(map[4], "E");
+
+        // swap_remove moves the last entry into the gap; shift_remove keeps the rest in order.
+        assert_eq!(map.swap_remove("a").as_deref(), Some("A"));
+        assert_eq!(map.keys().copied().collect::<String>(), "decb");
+        assert_eq!(map.get_index_of("e"), Some(1));
+        assert_eq!(map.shift_remove("d").as_deref(), Some("D"));
+        assert_eq!(map.keys().copied().collect::<String>(), "ecb");
+        assert_eq!(map.get_index_of("b"), Some(2));
+        assert_eq!(map["c"], "see");
+        assert_eq!(map.pop(), Some(("b", "B".into())));
+
+        let owned: Vec<_> = map.clone().into_iter().rev().collect();
+        assert_eq!(owned, [("c", "see".into()), ("e", "E".into())]);
+        assert_eq!(map.keys().next_back(), Some(&"c"));
+    }
+
+    // Sends every key to one of two slots, so removals have long clusters to patch up.
+    #[derive(Default)]
+    struct Pairs;
+    struct PairsHasher(u64);
+    impl BuildHasher for Pairs {
+        type Hasher = PairsHasher;
+        fn build_hasher(&self) -> PairsHasher {
+            PairsHasher(0)
+        }
+    }
+    impl Hasher for PairsHasher {
+        fn finish(&self) -> u64 {
+            // The low slot is near the end of any table, so clusters wrap around.
+            u64::MAX - self.0 % 2
+        }
+        fn write(&mut self, bytes: &[u8]) {
+            bytes.iter().for_each(|&b| self.0 = self.0 * 31 + b as u64);
+        }
+    }
+
+    #[test]
+    fn test_removal_clusters() {
+        let mut map: IndexMap<u32, u32, Pairs> = (0..40).map(|i| (i, i * 10)).collect();
+        for i in (0..40).step_by(3) {
+            assert_eq!(map.swap_remove(&i), Some(i * 10));
+        }
+        for i in (1..40).step_by(3) {
+            assert_eq!(map.shift_remove(&i), Some(i * 10));
+        }
+        assert_eq!(map.len(), 13);
+        for i in 0..40 {
+            assert_eq!(map.get(&i), (i % 3 == 2).then_some(&(i * 10)), "{i}");
+        }
+        for (index, (k, _)) in map.iter().enumerate() {
+            assert_eq!(map.get_index_of(k), Some(index));
+        }
+    }
+}
@@ -0,0 +1,534 @@
+mod entry;
+mod iter;
+
+pub use self::entry::{Entry, OccupiedEntry, VacantEntry};
+pub(crate) use self::iter::RawExtractIf;
+pub use self::iter::{ExtractIf, IntoIter, Iter, IterMut, Keys, Values, ValuesMut};
+
+use crate::myvec::MyVec;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::Index,
+};
+
+// The table grows once it's this full. Robin Hood keeps probe lengths short even this high up.
+const MAX_LOAD_NUM: usize = 7;
+const MAX_LOAD_DEN: usize = 8;
+const MIN_CAPACITY: usize = 8;
+
+/// An unordered map using open addressing with Robin Hood probing.
+///
+/// Every key has an ideal slot, picked by its hash, and sits either there or some way after it.
+/// On insert, a key that has come further from its ideal slot than the one sitting in its way
+/// takes that slot, and the one it displaced moves on instead. That keeps how far any key is
+/// from home about even across the table, so lookups stay short even at high load, and a lookup
+/// can stop as soon as it passes keys that are closer to home than the one it's after would be.
+///
+/// Removing a key shifts the run of keys after it back by one slot, rather than leaving a
+/// tombstone, so a table never clogs up with deleted entries.
+///
+/// Keys are hashed with `S`, which defaults to the standard library's randomly seeded SipHash,
+/// so that someone choosing the keys can't force them all into the same run of slots.
+pub struct HashMap<K, V, S = RandomState> {
+    // The capacity is always 0 or a power of two.
+    slots: MyVec<Option<Bucket<K, V>>>,
+    len: usize,
+    hash_builder: S,
+}
+
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+impl<K, V> HashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Makes an empty map that hashes keys with `hash_builder`. Like `new`, this doesn't
+    /// allocate until the first insert.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashMap {
+            slots: MyVec::new(),
+            len: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of entries the map can hold without resizing.
+    pub fn capacity(&self) -> usize {
+        self.slots.len() / MAX_LOAD_DEN * MAX_LOAD_NUM
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Removes every entry, keeping the table.
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&self.slots, self.len)
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut::new(&mut self.slots, self.len)
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.iter())
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.iter())
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut(self.iter_mut())
+    }
+
+    /// Keeps only the entries for which `f` returns `true`. Every entry is visited exactly once.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.extract_if(|k, v| !f(k, v)).for_each(drop);
+    }
+
+    /// Returns an iterator that removes and yields the entries for which `f` returns `true`.
+    /// Entries the iterator doesn't get to, because it's dropped early, stay in the map.
+    pub fn extract_if<F: FnMut(&K, &mut V) -> bool>(&mut self, f: F) -> ExtractIf<'_, K, V, F, S> {
+        ExtractIf::new(self.raw_extract_if(), f)
+    }
+
+    pub(crate) fn raw_extract_if(&mut self) -> RawExtractIf<'_, K, V, S> {
+        // Removing an entry pulls the ones after it in its run back a slot. Starting just after
+        // an empty slot means no run wraps around past where we started, so whatever gets pulled
+        // back into a slot is always something not yet visited.
+        let start = self.slots.iter().position(Option::is_none).unwrap_or(0);
+        RawExtractIf::new(self, start)
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len().wrapping_sub(1)
+    }
+
+    // How far the entry in `index` with this hash is from its ideal slot.
+    fn distance(&self, index: usize, hash: u64) -> usize {
+        index.wrapping_sub(hash as usize) & self.mask()
+    }
+
+    // Puts an entry for a key that isn't in the map into the table, which must have room for it,
+    // and returns the slot it ends up in.
+    fn insert_new(&mut self, hash: u64, key: K, value: V) -> usize {
+        let mask = self.mask();
+        let mut carry = Bucket { hash, key, value };
+        let mut index = hash as usize & mask;
+        let mut dist = 0;
+        let mut placed = None;
+        self.len += 1;
+        loop {
+            let their_dist = match &self.slots[index] {
+                None => {
+                    self.slots[index] = Some(carry);
+                    return placed.unwrap_or(index);
+                }
+                Some(bucket) => self.distance(index, bucket.hash),
+            };
+            // Take from the rich: whoever is closer to home gives up the slot.
+            if their_dist < dist {
+                mem::swap(self.slots[index].as_mut().unwrap(), &mut carry);
+                placed.get_or_insert(index);
+                dist = their_dist;
+            }
+            index = (index + 1) & mask;
+            dist += 1;
+        }
+    }
+
+    // Takes the entry out of `index`, and shifts the rest of its run back a slot.
+    fn remove_at(&mut self, mut index: usize) -> (K, V) {
+        let mask = self.mask();
+        let bucket = self.slots[index].take().unwrap();
+        self.len -= 1;
+        loop {
+            let next = (index + 1) & mask;
+            match &self.slots[next] {
+                Some(b) if self.distance(next, b.hash) > 0 => {
+                    self.slots[index] = self.slots[next].take();
+                    index = next;
+                }
+                _ => break,
+            }
+        }
+        (bucket.key, bucket.value)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> HashMap<K, V, S> {
+    /// Makes sure at least `additional` more entries fit before the table has to grow.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.capacity() {
+            let slots = (needed.max(1) * MAX_LOAD_DEN).div_ceil(MAX_LOAD_NUM);
+            let slots = slots
+                .checked_next_power_of_two()
+                .expect("capacity overflow")
+                .max(MIN_CAPACITY);
+            self.resize(slots);
+        }
+    }
+
+    /// Shrinks the table as far as it can while still holding every entry.
+    pub fn shrink_to_fit(&mut self) {
+        let slots = if self.len == 0 {
+            0
+        } else {
+            (self.len * MAX_LOAD_DEN)
+                .div_ceil(MAX_LOAD_NUM)
+                .next_power_of_two()
+                .max(MIN_CAPACITY)
+        };
+        if slots < self.slots.len() {
+            self.resize(slots);
+        }
+    }
+
+    /// Inserts a key and value, returning the old value if the key was already there. The key
+    /// already in the map is kept in that case.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    /// Gets the entry for `key`, for inserting or updating in place with a single lookup.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash_builder.hash_one(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => {
+                // Make room now, so the vacant entry can insert without the table moving under
+                // it. Inserting probes afresh from the hash, so there's no need to search again.
+                self.reserve(1);
+                Entry::Vacant(VacantEntry {
+                    map: self,
+                    hash,
+                    key,
+                })
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        let bucket = self.slots[index].as_ref().unwrap();
+        Some((&bucket.key, &bucket.value))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        Some(&mut self.slots[index].as_mut().unwrap().value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hash_builder.hash_one(key), key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        Some(self.remove_at(index))
+    }
+
+    // Returns the slot holding `key`, if it's there.
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let mask = self.mask();
+        let mut index = hash as usize & mask;
+        let mut dist = 0;
+        loop {
+            let bucket = self.slots[index].as_ref()?;
+            // Had the key been here, it would have taken this slot from anything closer to home.
+            if self.distance(index, bucket.hash) < dist {
+                return None;
+            }
+            if bucket.hash == hash && bucket.key.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & mask;
+            dist += 1;
+        }
+    }
+
+    fn resize(&mut self, slots: usize) {
+        let mut new_slots = MyVec::new();
+        new_slots.resize_with(slots, || None);
+        let old = mem::replace(&mut self.slots, new_slots);
+        self.len = 0;
+        for bucket in old.into_iter().flatten() {
+            self.insert_new(bucket.hash, bucket.key, bucket.value);
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for HashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for HashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        HashMap {
+            slots: self.slots.clone(),
+            len: self.len,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Bucket<K, V> {
+    fn clone(&self) -> Self {
+        Bucket {
+            hash: self.hash,
+            key: self.key.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for HashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for HashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for HashMap<K, V, S> {}
+
+impl<K, Q, V, S> Index<&Q> for HashMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not in map")
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for HashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|(k, v)| {
+            self.insert(k, v);
+        });
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V, S> arbitrary::Arbitrary<'a> for HashMap<K, V, S>
+where
+    K: Hash + Eq + arbitrary::Arbitrary<'a>,
+    V: arbitrary::Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for HashMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = HashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Entry, HashMap};
+    use std::hash::{BuildHasher, Hasher};
+
+    // Sends every key to the same few slots, to make long runs that wrap around the table.
+    #[derive(Clone, Default)]
+    struct Clumpy;
+    struct ClumpyHasher(u64);
+    impl BuildHasher for Clumpy {
+        type Hasher = ClumpyHasher;
+        fn build_hasher(&self) -> ClumpyHasher {
+            ClumpyHasher(0)
+        }
+    }
+    impl Hasher for ClumpyHasher {
+        fn finish(&self) -> u64 {
+            // Lands near the end of any table, so runs wrap.
+            u64::MAX - self.0 % 3
+        }
+        fn write(&mut self, bytes: &[u8]) {
+            bytes.iter().for_each(|&b| self.0 = self.0 * 31 + b as u64);
+        }
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        let mut map = HashMap::new();
+        for i in 0..1000 {
+            assert_eq!(map.insert(i, i * 2), None);
+        }
+        assert_eq!(map.insert(7, 0), Some(14));
+        assert_eq!(map.len(), 1000);
+        for i in (0..1000).step_by(2) {
+            assert_eq!(map.remove(&i), Some(if i == 7 { 0 } else { i * 2 }));
+        }
+        for i in 0..1000 {
+            assert_eq!(map.get(&i).is_some(), i % 2 == 1, "{i}");
+        }
+        assert_eq!(map[&999], 1998);
+        map.shrink_to_fit();
+        assert!(map.capacity() >= 500 && map.capacity() < 1000);
+        assert_eq!(map.iter().count(), 500);
+    }
+
+    #[test]
+    fn test_collisions() {
+        let mut map = HashMap::with_hasher(Clumpy);
+        for i in 0..50u32 {
+            map.insert(i, i);
+        }
+        for i in (0..50).rev().step_by(3) {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+        for i in 0..50 {
+            assert_eq!(map.get(&i), ((50 - 1 - i) % 3 != 0).then_some(&i));
+        }
+
+        map.retain(|k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+        let mut left: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        left.sort();
+        let expected: Vec<_> = (0..50)
+            .filter(|i| i % 2 == 0 && (49 - i) % 3 != 0)
+            .map(|i| (i, i + 1))
+            .collect();
+        assert_eq!(left, expected);
+
+        let mut fours: Vec<_> = map.extract_if(|k, _| k % 4 == 0).map(|(k, _)| k).collect();
+        fours.sort();
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|&(k, _)| k)
+            .filter(|k| k % 4 == 0)
+            .collect();
+        assert_eq!(fours, expected);
+        assert!(map.keys().all(|k| k % 4 == 2));
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for word in "the cat and the hat and the bat".split(' ') {
+            *counts.entry(word).or_default() += 1;
+        }
+        assert_eq!(counts["the"], 3);
+        assert_eq!(counts["and"], 2);
+
+        counts.entry("cat").and_modify(|n| *n += 10).or_insert(0);
+        assert_eq!(counts["cat"], 11);
+        match counts.entry("hat") {
+            Entry::Occupied(entry) => assert_eq!(entry.remove_entry(), ("hat", 1)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match counts.entry("dog") {
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), &"dog");
+                *entry.insert(5) += 1;
+            }
+            Entry::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(counts.get("dog"), Some(&6));
+        assert!(!counts.contains_key("hat"));
+        assert_eq!(counts.len(), 5);
+
+        // Updating a key that's already there never grows the table, even when it's full.
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        while map.len() < map.capacity() || map.is_empty() {
+            map.insert(map.len() as u32, 0);
+        }
+        let capacity = map.capacity();
+        *map.entry(0).or_default() += 1;
+        assert_eq!((map.capacity(), map[&0]), (capacity, 1));
+    }
+}
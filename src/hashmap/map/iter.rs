@@ -0,0 +1,259 @@
+use super::{Bucket, HashMap};
+use crate::myvec::MyVecIntoIter;
+use std::{collections::hash_map::RandomState, iter::FusedIterator, slice};
+
+/// An iterator over the entries of a HashMap, in no particular order.
+pub struct Iter<'a, K, V> {
+    slots: slice::Iter<'a, Option<Bucket<K, V>>>,
+    // How many entries are left, so the iterator can stop without scanning the empty slots at
+    // the end of the table.
+    len: usize,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    pub(super) fn new(slots: &'a [Option<Bucket<K, V>>], len: usize) -> Self {
+        Iter {
+            slots: slots.iter(),
+            len,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let bucket = self.slots.find_map(Option::as_ref)?;
+        self.len -= 1;
+        Some((&bucket.key, &bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            slots: self.slots.clone(),
+            len: self.len,
+        }
+    }
+}
+
+/// An iterator over the entries of a HashMap with mutable references to the values.
+pub struct IterMut<'a, K, V> {
+    slots: slice::IterMut<'a, Option<Bucket<K, V>>>,
+    len: usize,
+}
+
+impl<'a, K, V> IterMut<'a, K, V> {
+    pub(super) fn new(slots: &'a mut [Option<Bucket<K, V>>], len: usize) -> Self {
+        IterMut {
+            slots: slots.iter_mut(),
+            len,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        let bucket = self.slots.find_map(Option::as_mut)?;
+        self.len -= 1;
+        Some((&bucket.key, &mut bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+/// An iterator that moves the entries out of a HashMap, made by `into_iter`.
+pub struct IntoIter<K, V> {
+    slots: MyVecIntoIter<Option<Bucket<K, V>>>,
+    len: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        if self.len == 0 {
+            return None;
+        }
+        let bucket = self.slots.find_map(|slot| slot)?;
+        self.len -= 1;
+        Some((bucket.key, bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+/// An iterator over the keys of a HashMap.
+pub struct Keys<'a, K, V>(pub(super) Iter<'a, K, V>);
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        self.0.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// An iterator over the values of a HashMap.
+pub struct Values<'a, K, V>(pub(super) Iter<'a, K, V>);
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// An iterator over mutable references to the values of a HashMap.
+pub struct ValuesMut<'a, K, V>(pub(super) IterMut<'a, K, V>);
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.0.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V, S> IntoIterator for HashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            len: self.len,
+            slots: self.slots.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a HashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut HashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+// Walks a HashMap removing the entries a predicate picks, for `ExtractIf` here and the one on
+// HashSet. The predicate is passed in on each call so the set can wrap its own.
+pub(crate) struct RawExtractIf<'a, K, V, S> {
+    map: &'a mut HashMap<K, V, S>,
+    // The empty slot the walk started after, and will stop at.
+    start: usize,
+    index: usize,
+}
+
+impl<'a, K, V, S> RawExtractIf<'a, K, V, S> {
+    pub(super) fn new(map: &'a mut HashMap<K, V, S>, start: usize) -> Self {
+        let index = if map.len == 0 {
+            start
+        } else {
+            (start + 1) & map.mask()
+        };
+        RawExtractIf { map, start, index }
+    }
+
+    pub(crate) fn next(&mut self, mut pred: impl FnMut(&K, &mut V) -> bool) -> Option<(K, V)> {
+        while self.index != self.start {
+            let extract = match &mut self.map.slots[self.index] {
+                Some(bucket) => pred(&bucket.key, &mut bucket.value),
+                None => false,
+            };
+            if extract {
+                // Whatever moves back into this slot gets looked at next.
+                return Some(self.map.remove_at(self.index));
+            }
+            self.index = (self.index + 1) & self.map.mask();
+        }
+        None
+    }
+
+    pub(crate) fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len))
+    }
+}
+
+/// An iterator that removes the entries of a HashMap matching a predicate, made by
+/// `extract_if`.
+pub struct ExtractIf<'a, K, V, F, S = RandomState> {
+    raw: RawExtractIf<'a, K, V, S>,
+    pred: F,
+}
+
+impl<'a, K, V, F, S> ExtractIf<'a, K, V, F, S> {
+    pub(super) fn new(raw: RawExtractIf<'a, K, V, S>, pred: F) -> Self {
+        ExtractIf { raw, pred }
+    }
+}
+
+impl<K, V, F, S> Iterator for ExtractIf<'_, K, V, F, S>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        self.raw.next(&mut self.pred)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.raw.size_hint()
+    }
+}
+
+impl<K, V, F, S> FusedIterator for ExtractIf<'_, K, V, F, S> where F: FnMut(&K, &mut V) -> bool {}
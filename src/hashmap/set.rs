@@ -0,0 +1,447 @@
+use super::map::{self, HashMap, Keys, RawExtractIf};
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash},
+    iter::{Chain, FusedIterator},
+};
+
+/// An unordered set, stored as a `HashMap` with `()` for every value.
+///
+/// Besides the usual membership operations, it has the set algebra from the standard library's
+/// `HashSet`: `union`, `intersection`, `difference` and `symmetric_difference` are lazy iterators
+/// borrowing both sets, and `is_subset`, `is_superset` and `is_disjoint` compare two sets without
+/// building anything.
+pub struct HashSet<T, S = RandomState> {
+    map: HashMap<T, (), S>,
+}
+
+impl<T> HashSet<T> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<T, S> HashSet<T, S> {
+    /// Makes an empty set that hashes values with `hash_builder`. Like `new`, this doesn't
+    /// allocate until the first insert.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashSet {
+            map: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the number of values the set can hold without growing.
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Removes every value, keeping the table.
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(self.map.keys())
+    }
+
+    /// Keeps only the values for which `f` returns `true`. Every value is visited exactly once.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.map.retain(|value, _| f(value));
+    }
+
+    /// Returns an iterator that removes and yields the values for which `f` returns `true`.
+    /// Values the iterator doesn't get to, because it's dropped early, stay in the set.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, f: F) -> ExtractIf<'_, T, F, S> {
+        ExtractIf {
+            raw: self.map.raw_extract_if(),
+            pred: f,
+        }
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> HashSet<T, S> {
+    /// Makes sure at least `additional` more values fit before the table has to grow.
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
+
+    /// Shrinks the table as far as it can while still holding every value.
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// Adds a value, returning whether it was new. If an equal value was already there, it's
+    /// kept and `value` is dropped.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.insert(value, ()).is_none()
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.contains_key(value)
+    }
+
+    /// Returns the value in the set equal to `value`.
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.get_key_value(value).map(|(v, _)| v)
+    }
+
+    /// Removes a value, returning whether it was there.
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    /// Removes the value equal to `value` and hands it back.
+    pub fn take<Q>(&mut self, value: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.map.remove_entry(value).map(|(v, _)| v)
+    }
+
+    /// Returns an iterator over the values in either set, each once.
+    pub fn union<'a>(&'a self, other: &'a Self) -> Union<'a, T, S> {
+        // Walk the bigger set in full and only look things up in it for the smaller one.
+        let (big, small) = if self.len() >= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Union(big.iter().chain(small.difference(big)))
+    }
+
+    /// Returns an iterator over the values in both sets.
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> Intersection<'a, T, S> {
+        // Walk the smaller set and look things up in the bigger one.
+        let (small, big) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        Intersection {
+            iter: small.iter(),
+            other: big,
+        }
+    }
+
+    /// Returns an iterator over the values in `self` but not in `other`.
+    pub fn difference<'a>(&'a self, other: &'a Self) -> Difference<'a, T, S> {
+        Difference {
+            iter: self.iter(),
+            other,
+        }
+    }
+
+    /// Returns an iterator over the values in exactly one of the two sets.
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Self) -> SymmetricDifference<'a, T, S> {
+        SymmetricDifference(self.difference(other).chain(other.difference(self)))
+    }
+
+    /// Returns whether every value in `self` is also in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.len() <= other.len() && self.iter().all(|v| other.contains(v))
+    }
+
+    /// Returns whether every value in `other` is also in `self`.
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns whether the two sets have no values in common.
+    pub fn is_disjoint(&self, other: &Self) -> bool {
+        self.intersection(other).next().is_none()
+    }
+}
+
+impl<T, S: Default> Default for HashSet<T, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<T: Clone, S: Clone> Clone for HashSet<T, S> {
+    fn clone(&self) -> Self {
+        HashSet {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for HashSet<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> PartialEq for HashSet<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.is_subset(other)
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> Eq for HashSet<T, S> {}
+
+impl<T: Hash + Eq, S: BuildHasher> Extend<T> for HashSet<T, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.map.extend(iter.into_iter().map(|v| (v, ())));
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T, S> arbitrary::Arbitrary<'a> for HashSet<T, S>
+where
+    T: Hash + Eq + arbitrary::Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher + Default> FromIterator<T> for HashSet<T, S> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = HashSet::default();
+        set.extend(iter);
+        set
+    }
+}
+
+/// An iterator over the values of a HashSet, in no particular order.
+pub struct Iter<'a, T>(Keys<'a, T, ()>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// An iterator that moves the values out of a HashSet, made by `into_iter`.
+pub struct IntoIter<T>(map::IntoIter<T, ()>);
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.0.next().map(|(v, _)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+impl<T, S> IntoIterator for HashSet<T, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter(self.map.into_iter())
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a HashSet<T, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+/// An iterator over the values in both of two HashSets, made by `intersection`.
+pub struct Intersection<'a, T, S = RandomState> {
+    iter: Iter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+impl<'a, T: Hash + Eq, S: BuildHasher> Iterator for Intersection<'a, T, S> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.find(|v| self.other.contains(v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for Intersection<'_, T, S> {}
+
+/// An iterator over the values in one HashSet but not another, made by `difference`.
+pub struct Difference<'a, T, S = RandomState> {
+    iter: Iter<'a, T>,
+    other: &'a HashSet<T, S>,
+}
+
+impl<'a, T: Hash + Eq, S: BuildHasher> Iterator for Difference<'a, T, S> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.find(|v| !self.other.contains(v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for Difference<'_, T, S> {}
+
+/// An iterator over the values in either of two HashSets, made by `union`.
+pub struct Union<'a, T, S = RandomState>(Chain<Iter<'a, T>, Difference<'a, T, S>>);
+
+impl<'a, T: Hash + Eq, S: BuildHasher> Iterator for Union<'a, T, S> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for Union<'_, T, S> {}
+
+/// An iterator over the values in exactly one of two HashSets, made by `symmetric_difference`.
+pub struct SymmetricDifference<'a, T, S = RandomState>(
+    Chain<Difference<'a, T, S>, Difference<'a, T, S>>,
+);
+
+impl<'a, T: Hash + Eq, S: BuildHasher> Iterator for SymmetricDifference<'a, T, S> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> FusedIterator for SymmetricDifference<'_, T, S> {}
+
+/// An iterator that removes the values of a HashSet matching a predicate, made by
+/// `extract_if`.
+pub struct ExtractIf<'a, T, F, S = RandomState> {
+    raw: RawExtractIf<'a, T, (), S>,
+    pred: F,
+}
+
+impl<T, F: FnMut(&T) -> bool, S> Iterator for ExtractIf<'_, T, F, S> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        let pred = &mut self.pred;
+        self.raw.next(|v, _| pred(v)).map(|(v, _)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.raw.size_hint()
+    }
+}
+
+impl<T, F: FnMut(&T) -> bool, S> FusedIterator for ExtractIf<'_, T, F, S> {}
+
+#[cfg(test)]
+mod test {
+    use super::HashSet;
+
+    fn sorted<'a>(iter: impl Iterator<Item = &'a i32>) -> Vec<i32> {
+        let mut v: Vec<_> = iter.copied().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_basics() {
+        let mut set = HashSet::new();
+        assert!(set.insert("a".to_string()));
+        assert!(set.insert("b".to_string()));
+        assert!(!set.insert("a".to_string()));
+        assert_eq!(set.len(), 2);
+        assert!(set.contains("a"));
+        assert_eq!(set.get("b").map(String::as_str), Some("b"));
+        assert_eq!(set.take("b").as_deref(), Some("b"));
+        assert!(!set.remove("b"));
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), ["a"]);
+
+        let mut set: HashSet<i32> = (0..100).collect();
+        set.retain(|v| v % 2 == 0);
+        let mut threes: Vec<_> = set.extract_if(|v| v % 3 == 0).collect();
+        threes.sort();
+        assert_eq!(threes, (0..100).step_by(6).collect::<Vec<_>>());
+        assert_eq!(
+            sorted(set.iter()),
+            (0..100)
+                .filter(|v| v % 2 == 0 && v % 3 != 0)
+                .collect::<Vec<_>>()
+        );
+
+        // Dropping it early leaves the rest alone.
+        let taken = set.extract_if(|_| true).take(5).count();
+        assert_eq!(taken, 5);
+        assert_eq!(set.len(), 33 - 5);
+    }
+
+    #[test]
+    fn test_set_ops() {
+        let a: HashSet<i32> = (0..10).collect();
+        let b: HashSet<i32> = (5..20).collect();
+        assert_eq!(sorted(a.union(&b)), (0..20).collect::<Vec<_>>());
+        assert_eq!(sorted(a.intersection(&b)), (5..10).collect::<Vec<_>>());
+        assert_eq!(sorted(b.intersection(&a)), (5..10).collect::<Vec<_>>());
+        assert_eq!(sorted(a.difference(&b)), (0..5).collect::<Vec<_>>());
+        assert_eq!(sorted(b.difference(&a)), (10..20).collect::<Vec<_>>());
+        assert_eq!(
+            sorted(a.symmetric_difference(&b)),
+            (0..5).chain(10..20).collect::<Vec<_>>()
+        );
+
+        let small: HashSet<i32> = (6..9).collect();
+        assert!(small.is_subset(&a) && small.is_subset(&b));
+        assert!(a.is_superset(&small));
+        assert!(!a.is_subset(&b));
+        assert!(a.is_disjoint(&(10..15).collect()));
+        assert!(!a.is_disjoint(&b));
+        assert_eq!(a, (0..10).rev().collect());
+    }
+}
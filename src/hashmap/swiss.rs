@@ -0,0 +1,555 @@
+mod group;
+
+use self::group::{Group, DELETED, EMPTY};
+use crate::myvec::MyVec;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hash},
+    iter::FusedIterator,
+    mem::{self, MaybeUninit},
+    ops::Index,
+};
+
+// The table grows once it's this full, counting deleted slots.
+const MAX_LOAD_NUM: usize = 7;
+const MAX_LOAD_DEN: usize = 8;
+
+/// An unordered map laid out like Google's SwissTable.
+///
+/// Next to the slots is an array of one-byte control words, one per slot, saying whether the
+/// slot is empty, deleted, or full, and for a full one holding 7 bits of its key's hash. A lookup
+/// loads a whole group of control bytes at once, 16 of them with SSE2, and compares them all
+/// against the hash bits it's after in a couple of instructions, so it only touches the slots
+/// that almost certainly hold its key. Probing moves a group at a time, and stops at the first
+/// group with an empty slot in it.
+///
+/// Compared with `HashMap`, which checks the slots one by one, this does much better on large
+/// tables where each slot visited is a likely cache miss. Removing leaves a tombstone unless the
+/// slot is in a group no probe would have passed through full, and tombstones are cleared out
+/// when the table is rebuilt.
+pub struct FastHashMap<K, V, S = RandomState> {
+    // One byte per slot, followed by copies of the first `Group::WIDTH` of them, so a group can
+    // be loaded starting at any slot without wrapping around.
+    ctrl: MyVec<u8>,
+    // The number of slots is always 0, or a power of two no less than `Group::WIDTH`.
+    slots: MyVec<MaybeUninit<(K, V)>>,
+    len: usize,
+    // How many more empty slots can be filled before the table is rebuilt.
+    growth_left: usize,
+    hash_builder: S,
+}
+
+// The slot a hash starts probing at, and the 7 bits of it the control byte keeps.
+fn h1(hash: u64) -> usize {
+    hash as usize
+}
+
+fn h2(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+fn is_full(ctrl: u8) -> bool {
+    ctrl & 0x80 == 0
+}
+
+fn capacity_of(buckets: usize) -> usize {
+    buckets / MAX_LOAD_DEN * MAX_LOAD_NUM
+}
+
+impl<K, V> FastHashMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> FastHashMap<K, V, S> {
+    /// Makes an empty map that hashes keys with `hash_builder`. Like `new`, this doesn't
+    /// allocate until the first insert.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        FastHashMap {
+            ctrl: MyVec::new(),
+            slots: MyVec::new(),
+            len: 0,
+            growth_left: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of entries the map can hold without rebuilding the table.
+    pub fn capacity(&self) -> usize {
+        capacity_of(self.buckets())
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Removes every entry, keeping the table.
+    pub fn clear(&mut self) {
+        // Empties the table once the entries are dropped, or if one of their destructors panics,
+        // so nothing already dropped is left marked full for the map's own `Drop` to drop again.
+        // Whatever hadn't been dropped yet is leaked.
+        struct Reset<'a, K, V, S>(&'a mut FastHashMap<K, V, S>);
+        impl<K, V, S> Drop for Reset<'_, K, V, S> {
+            fn drop(&mut self) {
+                self.0.ctrl.iter_mut().for_each(|c| *c = EMPTY);
+                self.0.len = 0;
+                self.0.growth_left = self.0.capacity();
+            }
+        }
+        Reset(self).0.drop_entries();
+    }
+
+    pub fn iter(&self) -> FastIter<'_, K, V, S> {
+        FastIter {
+            map: self,
+            index: 0,
+            len: self.len,
+        }
+    }
+
+    fn buckets(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn mask(&self) -> usize {
+        self.buckets().wrapping_sub(1)
+    }
+
+    // Sets the control byte of slot `index`, and its copy at the end if it has one.
+    fn set_ctrl(&mut self, index: usize, ctrl: u8) {
+        let mirror = (index.wrapping_sub(Group::WIDTH) & self.mask()) + Group::WIDTH;
+        self.ctrl[index] = ctrl;
+        self.ctrl[mirror] = ctrl;
+    }
+
+    // The entry in a full slot.
+    fn entry(&self, index: usize) -> &(K, V) {
+        debug_assert!(is_full(self.ctrl[index]));
+        unsafe { self.slots[index].assume_init_ref() }
+    }
+
+    fn entry_mut(&mut self, index: usize) -> &mut (K, V) {
+        debug_assert!(is_full(self.ctrl[index]));
+        unsafe { self.slots[index].assume_init_mut() }
+    }
+
+    // Walks the probe sequence for `hash`, a group at a time, calling `f` with each group and
+    // where it starts until `f` returns something. The stride grows by a group each time, which
+    // visits every group once the number of groups is a power of two.
+    fn probe<T>(&self, hash: u64, mut f: impl FnMut(Group, usize) -> Option<T>) -> T {
+        let mask = self.mask();
+        let mut pos = h1(hash) & mask;
+        let mut stride = 0;
+        loop {
+            if let Some(found) = f(Group::load(&self.ctrl[pos..]), pos) {
+                return found;
+            }
+            stride += Group::WIDTH;
+            pos = (pos + stride) & mask;
+        }
+    }
+
+    // Returns the first empty or deleted slot on the probe sequence for `hash`. The table must
+    // have one.
+    fn find_insert_slot(&self, hash: u64) -> usize {
+        let mask = self.mask();
+        self.probe(hash, |group, pos| {
+            Some((pos + group.match_empty_or_deleted().lowest()?) & mask)
+        })
+    }
+
+    // Takes the entry out of a full slot, leaving a tombstone only if it has to.
+    fn remove_at(&mut self, index: usize) -> (K, V) {
+        let mask = self.mask();
+        // If there's an empty slot within a group's width on both sides, no probe could ever
+        // have found a full group around this slot and carried on past it, so it can go back to
+        // being empty.
+        let before = Group::load(&self.ctrl[index.wrapping_sub(Group::WIDTH) & mask..]);
+        let after = Group::load(&self.ctrl[index..]);
+        let full_run =
+            before.match_empty().trailing_unmatched() + after.match_empty().leading_unmatched();
+        if full_run < Group::WIDTH {
+            self.set_ctrl(index, EMPTY);
+            self.growth_left += 1;
+        } else {
+            self.set_ctrl(index, DELETED);
+        }
+        self.len -= 1;
+        unsafe { self.slots[index].assume_init_read() }
+    }
+
+    fn drop_entries(&mut self) {
+        if mem::needs_drop::<(K, V)>() {
+            for index in 0..self.buckets() {
+                if is_full(self.ctrl[index]) {
+                    unsafe { self.slots[index].assume_init_drop() };
+                }
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> FastHashMap<K, V, S> {
+    /// Makes sure at least `additional` more entries fit before the table has to be rebuilt.
+    pub fn reserve(&mut self, additional: usize) {
+        if additional > self.growth_left {
+            let needed = self.len.checked_add(additional).expect("capacity overflow");
+            // If deleted slots are what's in the way, rebuilding at the same size clears them.
+            let buckets = (needed * MAX_LOAD_DEN)
+                .div_ceil(MAX_LOAD_NUM)
+                .checked_next_power_of_two()
+                .expect("capacity overflow")
+                .max(Group::WIDTH)
+                .max(self.buckets());
+            self.rebuild(buckets);
+        }
+    }
+
+    /// Inserts a key and value, returning the old value if the key was already there. The key
+    /// already in the map is kept in that case.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find(hash, &key) {
+            return Some(mem::replace(&mut self.entry_mut(index).1, value));
+        }
+        self.reserve(1);
+        self.insert_new(hash, (key, value));
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        let (k, v) = self.entry(index);
+        Some((k, v))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        Some(&mut self.entry_mut(index).1)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hash_builder.hash_one(key), key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.find(self.hash_builder.hash_one(key), key)?;
+        Some(self.remove_at(index))
+    }
+
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let mask = self.mask();
+        self.probe(hash, |group, pos| {
+            for bit in group.match_byte(h2(hash)) {
+                let index = (pos + bit) & mask;
+                if self.entry(index).0.borrow() == key {
+                    return Some(Some(index));
+                }
+            }
+            // The key would have gone in the first empty slot it came to.
+            group.match_empty().any().then_some(None)
+        })
+    }
+
+    // Puts an entry for a key that isn't in the map into the table, which must have room.
+    fn insert_new(&mut self, hash: u64, entry: (K, V)) {
+        let index = self.find_insert_slot(hash);
+        if self.ctrl[index] == EMPTY {
+            self.growth_left -= 1;
+        }
+        self.set_ctrl(index, h2(hash));
+        self.slots[index].write(entry);
+        self.len += 1;
+    }
+
+    // Moves every entry into a fresh table of `buckets` slots.
+    fn rebuild(&mut self, buckets: usize) {
+        let mut ctrl = MyVec::new();
+        ctrl.resize(buckets + Group::WIDTH, EMPTY);
+        let mut slots = MyVec::new();
+        slots.resize_with(buckets, MaybeUninit::uninit);
+        let old_ctrl = mem::replace(&mut self.ctrl, ctrl);
+        let mut old_slots = mem::replace(&mut self.slots, slots);
+        self.len = 0;
+        self.growth_left = capacity_of(buckets);
+        for (index, slot) in old_slots.iter_mut().enumerate() {
+            if is_full(old_ctrl[index]) {
+                let entry = unsafe { slot.assume_init_read() };
+                let hash = self.hash_builder.hash_one(&entry.0);
+                self.insert_new(hash, entry);
+            }
+        }
+    }
+}
+
+impl<K, V, S> Drop for FastHashMap<K, V, S> {
+    fn drop(&mut self) {
+        self.drop_entries();
+    }
+}
+
+impl<K, V, S: Default> Default for FastHashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> Clone for FastHashMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        let mut map = FastHashMap::with_hasher(self.hash_builder.clone());
+        map.reserve(self.len);
+        for (k, v) in self {
+            map.insert(k.clone(), v.clone());
+        }
+        map
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for FastHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for FastHashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for FastHashMap<K, V, S> {}
+
+impl<K, Q, V, S> Index<&Q> for FastHashMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not in map")
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for FastHashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|(k, v)| {
+            self.insert(k, v);
+        });
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, K, V, S> arbitrary::Arbitrary<'a> for FastHashMap<K, V, S>
+where
+    K: Hash + Eq + arbitrary::Arbitrary<'a>,
+    V: arbitrary::Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for FastHashMap<K, V, S> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = FastHashMap::default();
+        map.extend(iter);
+        map
+    }
+}
+
+/// An iterator over the entries of a FastHashMap, in no particular order.
+pub struct FastIter<'a, K, V, S = RandomState> {
+    map: &'a FastHashMap<K, V, S>,
+    index: usize,
+    len: usize,
+}
+
+impl<'a, K, V, S> Iterator for FastIter<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        while !is_full(self.map.ctrl[self.index]) {
+            self.index += 1;
+        }
+        let (k, v) = self.map.entry(self.index);
+        self.index += 1;
+        self.len -= 1;
+        Some((k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for FastIter<'_, K, V, S> {}
+
+impl<K, V, S> FusedIterator for FastIter<'_, K, V, S> {}
+
+impl<'a, K, V, S> IntoIterator for &'a FastHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = FastIter<'a, K, V, S>;
+    fn into_iter(self) -> FastIter<'a, K, V, S> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FastHashMap;
+    use std::hash::{BuildHasher, Hasher};
+
+    #[test]
+    fn test_insert_remove() {
+        let mut map = FastHashMap::new();
+        for i in 0..1000 {
+            assert_eq!(map.insert(i, i.to_string()), None);
+        }
+        assert_eq!(map.insert(7, "seven".to_string()).as_deref(), Some("7"));
+        for i in (0..1000).step_by(2) {
+            assert!(map.remove(&i).is_some());
+        }
+        for i in 0..1000 {
+            assert_eq!(map.contains_key(&i), i % 2 == 1, "{i}");
+        }
+        assert_eq!(map[&7], "seven");
+        map.get_mut(&9).unwrap().push('!');
+        assert_eq!(map.get_key_value(&9), Some((&9, &"9!".to_string())));
+        assert_eq!(map.iter().count(), 500);
+        assert_eq!(map.clone(), map);
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+    }
+
+    // Gives every key the same hash, so every lookup probes through the same groups.
+    #[derive(Default)]
+    struct Constant;
+    impl BuildHasher for Constant {
+        type Hasher = ConstantHasher;
+        fn build_hasher(&self) -> ConstantHasher {
+            ConstantHasher
+        }
+    }
+    struct ConstantHasher;
+    impl Hasher for ConstantHasher {
+        fn finish(&self) -> u64 {
+            0x0123_4567_89ab_cdef
+        }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn test_tombstones() {
+        let mut map = FastHashMap::with_hasher(Constant);
+        // Churning through keys that all probe the same way leaves deleted slots behind, which
+        // the table has to reuse or clear out rather than grow forever.
+        for i in 0..500 {
+            map.insert(i, i);
+            if i >= 40 {
+                assert_eq!(map.remove(&(i - 40)), Some(i - 40));
+            }
+        }
+        assert_eq!(map.len(), 40);
+        assert!(map.capacity() < 100);
+        for i in 0..500 {
+            assert_eq!(map.get(&i), (i >= 460).then_some(&i));
+        }
+    }
+
+    #[test]
+    fn test_clear_panicking_drop() {
+        use std::{
+            cell::Cell,
+            panic::{catch_unwind, AssertUnwindSafe},
+        };
+
+        // Counts its drops, and panics on the first one if asked to.
+        struct Noisy<'a>(&'a Cell<usize>, bool);
+        impl Drop for Noisy<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+                if self.1 {
+                    panic!("Noisy dropped");
+                }
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut map = FastHashMap::new();
+        for i in 0..20 {
+            map.insert(i, Noisy(&drops, i == 10));
+        }
+        assert!(catch_unwind(AssertUnwindSafe(|| map.clear())).is_err());
+        assert!(map.is_empty() && map.iter().next().is_none());
+        let dropped = drops.get();
+        drop(map);
+        assert_eq!(drops.get(), dropped);
+        assert!(dropped <= 20);
+    }
+}
@@ -0,0 +1,125 @@
+// A group of control bytes that can be searched all at once. With SSE2 that's 16 bytes in one
+// register; anywhere else it's 8 bytes in a u64, searched with the SWAR zero-byte trick.
+
+pub(super) const EMPTY: u8 = 0b1111_1111;
+pub(super) const DELETED: u8 = 0b1000_0000;
+
+/// The set of slots in a group that matched, lowest first.
+#[derive(Clone, Copy)]
+pub(super) struct BitMask(u64);
+
+impl BitMask {
+    pub(super) fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    pub(super) fn lowest(self) -> Option<usize> {
+        self.any()
+            .then(|| (self.0.trailing_zeros() / BITS_PER_SLOT) as usize)
+    }
+
+    // How many slots at the start of the group didn't match.
+    pub(super) fn leading_unmatched(self) -> usize {
+        self.lowest().unwrap_or(Group::WIDTH)
+    }
+
+    // How many slots at the end of the group didn't match.
+    pub(super) fn trailing_unmatched(self) -> usize {
+        let unused = 64 - Group::WIDTH as u32 * BITS_PER_SLOT;
+        ((self.0.leading_zeros() - unused) / BITS_PER_SLOT) as usize
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        let bit = self.lowest()?;
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+mod imp {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    pub(super) const BITS_PER_SLOT: u32 = 1;
+
+    #[derive(Clone, Copy)]
+    pub(in super::super) struct Group(__m128i);
+
+    impl Group {
+        pub(in super::super) const WIDTH: usize = 16;
+
+        /// Loads the group starting at the front of `ctrl`, which must be at least `WIDTH` long.
+        pub(in super::super) fn load(ctrl: &[u8]) -> Group {
+            assert!(ctrl.len() >= Self::WIDTH);
+            Group(unsafe { _mm_loadu_si128(ctrl.as_ptr().cast()) })
+        }
+
+        pub(in super::super) fn match_byte(self, byte: u8) -> super::BitMask {
+            unsafe {
+                let eq = _mm_cmpeq_epi8(self.0, _mm_set1_epi8(byte as i8));
+                super::BitMask(_mm_movemask_epi8(eq) as u16 as u64)
+            }
+        }
+
+        pub(in super::super) fn match_empty(self) -> super::BitMask {
+            self.match_byte(super::EMPTY)
+        }
+
+        // Both of these are the only control bytes with the top bit set.
+        pub(in super::super) fn match_empty_or_deleted(self) -> super::BitMask {
+            super::BitMask(unsafe { _mm_movemask_epi8(self.0) } as u16 as u64)
+        }
+    }
+}
+
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+)))]
+mod imp {
+    pub(super) const BITS_PER_SLOT: u32 = 8;
+
+    const LO: u64 = 0x0101_0101_0101_0101;
+    const HI: u64 = 0x8080_8080_8080_8080;
+
+    // Read little-endian, so the first byte is the lowest one on any target.
+    #[derive(Clone, Copy)]
+    pub(in super::super) struct Group(u64);
+
+    impl Group {
+        pub(in super::super) const WIDTH: usize = 8;
+
+        /// Loads the group starting at the front of `ctrl`, which must be at least `WIDTH` long.
+        pub(in super::super) fn load(ctrl: &[u8]) -> Group {
+            Group(u64::from_le_bytes(ctrl[..Self::WIDTH].try_into().unwrap()))
+        }
+
+        // This can report a byte that doesn't match, right after one that does, but never
+        // misses one. The caller checks the key anyway.
+        pub(in super::super) fn match_byte(self, byte: u8) -> super::BitMask {
+            let x = self.0 ^ (LO * byte as u64);
+            super::BitMask(x.wrapping_sub(LO) & !x & HI)
+        }
+
+        // EMPTY is the only control byte with both of its top two bits set.
+        pub(in super::super) fn match_empty(self) -> super::BitMask {
+            super::BitMask(self.0 & (self.0 << 1) & HI)
+        }
+
+        pub(in super::super) fn match_empty_or_deleted(self) -> super::BitMask {
+            super::BitMask(self.0 & HI)
+        }
+    }
+}
+
+pub(super) use imp::Group;
+use imp::BITS_PER_SLOT;
@@ -1,3 +1,9 @@
-#![allow(unused)]
-pub mod btreemap;
-pub mod myvec;
+#![allow(unused)]
+pub mod binaryheap;
+pub mod btreemap;
+pub mod hashmap;
+pub mod linkedlist;
+pub mod myvec;
+pub mod stack;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,676 @@
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    marker::PhantomData,
+    mem,
+    ptr::NonNull,
+};
+
+/// A doubly-linked list.
+///
+/// Pushing and popping at either end is O(1), but what a list really buys over a vector is
+/// `CursorMut`: once a cursor is at a spot, it can insert or remove elements there, splice a
+/// whole other list in, or split everything after it off into a list of its own, all in O(1)
+/// no matter how long the lists are.
+pub struct LinkedList<T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    // The list owns its nodes, as far as drop check and variance are concerned.
+    _marker: PhantomData<Box<Node<T>>>,
+}
+
+type Link<T> = Option<NonNull<Node<T>>>;
+
+struct Node<T> {
+    next: Link<T>,
+    prev: Link<T>,
+    elem: T,
+}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> Self {
+        LinkedList {
+            head: None,
+            tail: None,
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        unsafe { self.link_between(None, self.head, LinkedList::single(elem)) }
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        unsafe { self.link_between(self.tail, None, LinkedList::single(elem)) }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.head.map(|node| unsafe { self.unlink(node) })
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        self.tail.map(|node| unsafe { self.unlink(node) })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.head.map(|node| unsafe { &(*node.as_ptr()).elem })
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.head.map(|node| unsafe { &mut (*node.as_ptr()).elem })
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.tail.map(|node| unsafe { &(*node.as_ptr()).elem })
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.tail.map(|node| unsafe { &mut (*node.as_ptr()).elem })
+    }
+
+    /// Moves every element of `other` onto the end of `self`, in O(1).
+    pub fn append(&mut self, other: &mut LinkedList<T>) {
+        unsafe { self.link_between(self.tail, None, mem::take(other)) }
+    }
+
+    /// Splits the list in two at `at`, returning everything from there on. This walks to `at`
+    /// from whichever end is nearer; use a cursor's `split_after` when already there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is past the end.
+    pub fn split_off(&mut self, at: usize) -> LinkedList<T> {
+        assert!(at <= self.len, "index out of bounds");
+        if at == self.len {
+            return LinkedList::new();
+        }
+        let len = self.len;
+        let mut cursor = if at <= len / 2 {
+            let mut cursor = self.cursor_front_mut();
+            (0..at).for_each(|_| cursor.move_next());
+            cursor
+        } else {
+            let mut cursor = self.cursor_back_mut();
+            (at + 1..len).for_each(|_| cursor.move_prev());
+            cursor
+        };
+        let front = cursor.split_before();
+        mem::replace(self, front)
+    }
+
+    pub fn clear(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+
+    pub fn iter(&self) -> LinkedListIter<'_, T> {
+        LinkedListIter {
+            head: self.head,
+            tail: self.tail,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> LinkedListIterMut<'_, T> {
+        LinkedListIterMut {
+            head: self.head,
+            tail: self.tail,
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a cursor pointing at the front element, or at the ghost if the list is empty.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head,
+            index: 0,
+            list: self,
+        }
+    }
+
+    /// Returns a cursor pointing at the back element, or at the ghost if the list is empty.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail,
+            index: self.len.saturating_sub(1),
+            list: self,
+        }
+    }
+
+    fn single(elem: T) -> Self {
+        let node = NonNull::from(Box::leak(Box::new(Node {
+            next: None,
+            prev: None,
+            elem,
+        })));
+        LinkedList {
+            head: Some(node),
+            tail: Some(node),
+            len: 1,
+            _marker: PhantomData,
+        }
+    }
+
+    // Links all of `other` in between `prev` and `next`, which must be adjacent nodes of this
+    // list, with None standing for the end on that side.
+    unsafe fn link_between(&mut self, prev: Link<T>, next: Link<T>, mut other: LinkedList<T>) {
+        let (Some(first), Some(last)) = (other.head.take(), other.tail.take()) else {
+            return;
+        };
+        self.len += mem::replace(&mut other.len, 0);
+        unsafe {
+            (*first.as_ptr()).prev = prev;
+            (*last.as_ptr()).next = next;
+            match prev {
+                Some(prev) => (*prev.as_ptr()).next = Some(first),
+                None => self.head = Some(first),
+            }
+            match next {
+                Some(next) => (*next.as_ptr()).prev = Some(last),
+                None => self.tail = Some(last),
+            }
+        }
+    }
+
+    // Takes `node`, which must be in this list, out of it and frees it.
+    unsafe fn unlink(&mut self, node: NonNull<Node<T>>) -> T {
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        unsafe {
+            match node.prev {
+                Some(prev) => (*prev.as_ptr()).next = node.next,
+                None => self.head = node.next,
+            }
+            match node.next {
+                Some(next) => (*next.as_ptr()).prev = node.prev,
+                None => self.tail = node.prev,
+            }
+        }
+        self.len -= 1;
+        node.elem
+    }
+}
+
+/// A cursor over a LinkedList that can move back and forth and edit the list in place.
+///
+/// Like the BTreeMap cursors, it either points at an element, or at a "ghost" position that sits
+/// after the last element and before the first one. Moving off either end of the list lands on
+/// the ghost, and moving on from the ghost wraps around to the other end.
+pub struct CursorMut<'a, T> {
+    list: &'a mut LinkedList<T>,
+    // The current node, or None at the ghost.
+    current: Link<T>,
+    // The index of the current node, or the length of the list at the ghost.
+    index: usize,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    /// Returns the index of the current element, or None at the ghost position.
+    pub fn index(&self) -> Option<usize> {
+        self.current.map(|_| self.index)
+    }
+
+    /// Returns the current element, or None at the ghost position.
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.current
+            .map(|node| unsafe { &mut (*node.as_ptr()).elem })
+    }
+
+    /// Returns the element after the current one without moving the cursor. At the ghost
+    /// position this is the front of the list.
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        self.next_node()
+            .map(|node| unsafe { &mut (*node.as_ptr()).elem })
+    }
+
+    /// Returns the element before the current one without moving the cursor. At the ghost
+    /// position this is the back of the list.
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        self.prev_node()
+            .map(|node| unsafe { &mut (*node.as_ptr()).elem })
+    }
+
+    pub fn move_next(&mut self) {
+        let at_ghost = self.current.is_none();
+        self.current = self.next_node();
+        self.index = if at_ghost { 0 } else { self.index + 1 };
+    }
+
+    pub fn move_prev(&mut self) {
+        let at_ghost = self.current.is_none();
+        self.current = self.prev_node();
+        self.index = match self.current {
+            None => self.list.len,
+            Some(_) if at_ghost => self.list.len - 1,
+            Some(_) => self.index - 1,
+        };
+    }
+
+    /// Inserts `elem` after the current element, or at the front at the ghost position.
+    pub fn insert_after(&mut self, elem: T) {
+        self.splice_after(LinkedList::single(elem));
+    }
+
+    /// Inserts `elem` before the current element, or at the back at the ghost position.
+    pub fn insert_before(&mut self, elem: T) {
+        self.splice_before(LinkedList::single(elem));
+    }
+
+    /// Removes the current element and returns it, moving the cursor on to the next one. Does
+    /// nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.current?;
+        self.current = unsafe { (*node.as_ptr()).next };
+        // The next element, or the ghost, takes over the index.
+        Some(unsafe { self.list.unlink(node) })
+    }
+
+    /// Moves all of `list` in after the current element, or to the front at the ghost position,
+    /// in O(1).
+    pub fn splice_after(&mut self, list: LinkedList<T>) {
+        let added = list.len;
+        unsafe { self.list.link_between(self.current, self.next_node(), list) };
+        if self.current.is_none() {
+            self.index += added;
+        }
+    }
+
+    /// Moves all of `list` in before the current element, or to the back at the ghost position,
+    /// in O(1).
+    pub fn splice_before(&mut self, list: LinkedList<T>) {
+        let added = list.len;
+        let prev = self.prev_node();
+        unsafe { self.list.link_between(prev, self.current, list) };
+        self.index += added;
+    }
+
+    /// Splits off everything after the current element into a list of its own, in O(1). At the
+    /// ghost position, that's the whole list.
+    pub fn split_after(&mut self) -> LinkedList<T> {
+        let Some(node) = self.current else {
+            self.index = 0;
+            return mem::take(self.list);
+        };
+        let first = match unsafe { (*node.as_ptr()).next.take() } {
+            Some(first) => first,
+            None => return LinkedList::new(),
+        };
+        let split = self.split_at(
+            first,
+            self.list.tail.unwrap(),
+            self.list.len - self.index - 1,
+        );
+        self.list.tail = Some(node);
+        split
+    }
+
+    /// Splits off everything before the current element into a list of its own, in O(1). At
+    /// the ghost position, that's the whole list.
+    pub fn split_before(&mut self) -> LinkedList<T> {
+        let Some(node) = self.current else {
+            self.index = 0;
+            return mem::take(self.list);
+        };
+        let last = match unsafe { (*node.as_ptr()).prev.take() } {
+            Some(last) => last,
+            None => return LinkedList::new(),
+        };
+        let split = self.split_at(self.list.head.unwrap(), last, self.index);
+        self.list.head = Some(node);
+        self.index = 0;
+        split
+    }
+
+    // Cuts the run from `first` to `last`, `len` nodes that end the list or start it, off into
+    // a list of its own. The caller fixes up the node on the other side of the cut.
+    fn split_at(
+        &mut self,
+        first: NonNull<Node<T>>,
+        last: NonNull<Node<T>>,
+        len: usize,
+    ) -> LinkedList<T> {
+        unsafe {
+            (*first.as_ptr()).prev = None;
+            (*last.as_ptr()).next = None;
+        }
+        self.list.len -= len;
+        LinkedList {
+            head: Some(first),
+            tail: Some(last),
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    fn next_node(&self) -> Link<T> {
+        match self.current {
+            Some(node) => unsafe { (*node.as_ptr()).next },
+            None => self.list.head,
+        }
+    }
+
+    fn prev_node(&self) -> Link<T> {
+        match self.current {
+            Some(node) => unsafe { (*node.as_ptr()).prev },
+            None => self.list.tail,
+        }
+    }
+}
+
+impl<T> Drop for LinkedList<T> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Default for LinkedList<T> {
+    fn default() -> Self {
+        LinkedList::new()
+    }
+}
+
+impl<T> Extend<T> for LinkedList<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|elem| self.push_back(elem));
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for LinkedList<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<T> FromIterator<T> for LinkedList<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut list = LinkedList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl<T: Clone> Clone for LinkedList<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for LinkedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for LinkedList<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for LinkedList<T> {}
+
+impl<T: Hash> Hash for LinkedList<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len);
+        self.iter().for_each(|elem| elem.hash(state));
+    }
+}
+
+unsafe impl<T: Send> Send for LinkedList<T> {}
+unsafe impl<T: Sync> Sync for LinkedList<T> {}
+
+/// An iterator over a LinkedList's elements, front to back.
+pub struct LinkedListIter<'a, T> {
+    head: Link<T>,
+    tail: Link<T>,
+    // How many elements are left between `head` and `tail`, so the ends know when they meet.
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for LinkedListIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.head.map(|node| unsafe {
+            self.len -= 1;
+            self.head = (*node.as_ptr()).next;
+            &(*node.as_ptr()).elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for LinkedListIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.tail.map(|node| unsafe {
+            self.len -= 1;
+            self.tail = (*node.as_ptr()).prev;
+            &(*node.as_ptr()).elem
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for LinkedListIter<'_, T> {}
+
+impl<T> FusedIterator for LinkedListIter<'_, T> {}
+
+impl<T> Clone for LinkedListIter<'_, T> {
+    fn clone(&self) -> Self {
+        LinkedListIter { ..*self }
+    }
+}
+
+unsafe impl<T: Sync> Send for LinkedListIter<'_, T> {}
+unsafe impl<T: Sync> Sync for LinkedListIter<'_, T> {}
+
+/// An iterator over mutable references to a LinkedList's elements, front to back.
+pub struct LinkedListIterMut<'a, T> {
+    head: Link<T>,
+    tail: Link<T>,
+    len: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for LinkedListIterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.head.map(|node| unsafe {
+            self.len -= 1;
+            self.head = (*node.as_ptr()).next;
+            &mut (*node.as_ptr()).elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for LinkedListIterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.tail.map(|node| unsafe {
+            self.len -= 1;
+            self.tail = (*node.as_ptr()).prev;
+            &mut (*node.as_ptr()).elem
+        })
+    }
+}
+
+impl<T> ExactSizeIterator for LinkedListIterMut<'_, T> {}
+
+impl<T> FusedIterator for LinkedListIterMut<'_, T> {}
+
+unsafe impl<T: Send> Send for LinkedListIterMut<'_, T> {}
+unsafe impl<T: Sync> Sync for LinkedListIterMut<'_, T> {}
+
+/// An iterator that moves the elements out of a LinkedList, made by `into_iter`.
+pub struct LinkedListIntoIter<T> {
+    list: LinkedList<T>,
+}
+
+impl<T> Iterator for LinkedListIntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.list.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.list.len, Some(self.list.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for LinkedListIntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.list.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for LinkedListIntoIter<T> {}
+
+impl<T> FusedIterator for LinkedListIntoIter<T> {}
+
+impl<T> IntoIterator for LinkedList<T> {
+    type Item = T;
+    type IntoIter = LinkedListIntoIter<T>;
+    fn into_iter(self) -> LinkedListIntoIter<T> {
+        LinkedListIntoIter { list: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a LinkedList<T> {
+    type Item = &'a T;
+    type IntoIter = LinkedListIter<'a, T>;
+    fn into_iter(self) -> LinkedListIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut LinkedList<T> {
+    type Item = &'a mut T;
+    type IntoIter = LinkedListIterMut<'a, T>;
+    fn into_iter(self) -> LinkedListIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LinkedList;
+
+    fn list(elems: &[i32]) -> LinkedList<i32> {
+        elems.iter().copied().collect()
+    }
+
+    fn check(list: &LinkedList<i32>, expected: &[i32]) {
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(
+            list.iter().rev().copied().rev().collect::<Vec<_>>(),
+            expected
+        );
+        assert_eq!(list.len(), expected.len());
+    }
+
+    #[test]
+    fn test_ends() {
+        let mut list = LinkedList::new();
+        list.push_back(2);
+        list.push_front(1);
+        list.push_back(3);
+        *list.back_mut().unwrap() *= 10;
+        check(&list, &[1, 2, 30]);
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_back(), Some(30));
+        assert_eq!(list.pop_back(), Some(2));
+        assert_eq!(list.pop_front(), None);
+        check(&list, &[]);
+
+        let mut list = self::list(&[1, 2, 3, 4, 5]);
+        for at in 0..=5 {
+            let mut front = list.clone();
+            let back = front.split_off(at);
+            check(&front, &[1, 2, 3, 4, 5][..at]);
+            check(&back, &[1, 2, 3, 4, 5][at..]);
+        }
+        list.iter_mut().for_each(|x| *x += 1);
+        let mut other = self::list(&[7]);
+        list.append(&mut other);
+        check(&list, &[2, 3, 4, 5, 6, 7]);
+        check(&other, &[]);
+    }
+
+    #[test]
+    fn test_cursor() {
+        let mut list = list(&[1, 2, 3]);
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(cursor.index(), Some(1));
+        cursor.splice_after(self::list(&[20, 21]));
+        cursor.splice_before(self::list(&[10, 11]));
+        assert_eq!(cursor.index(), Some(3));
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(cursor.peek_prev(), Some(&mut 11));
+        assert_eq!(cursor.peek_next(), Some(&mut 20));
+        check(&list, &[1, 10, 11, 2, 20, 21, 3]);
+
+        // Split the tail off, and the head, leaving just the current element.
+        let mut cursor = list.cursor_front_mut();
+        (0..3).for_each(|_| cursor.move_next());
+        let after = cursor.split_after();
+        let before = cursor.split_before();
+        assert_eq!(cursor.index(), Some(0));
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(2));
+        check(&list, &[]);
+        check(&before, &[1, 10, 11]);
+        check(&after, &[20, 21, 3]);
+
+        // At the ghost, inserting after goes to the front and before goes to the back.
+        let mut list = before;
+        let mut cursor = list.cursor_back_mut();
+        cursor.move_next();
+        cursor.insert_after(0);
+        cursor.insert_before(12);
+        assert_eq!(cursor.index(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.index(), Some(4));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(10));
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.current(), Some(&mut 11));
+        check(&list, &[0, 1, 11, 12]);
+    }
+}
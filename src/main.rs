@@ -1,3 +1,4 @@
+#![allow(unused)]
 use tiny_collections_rs::btreemap::map::BTreeMap;
 
 fn main() {
@@ -46,10 +47,7 @@ where
     <T as IntoIterator>::Item: Ord,
 {
     let mut it = data.into_iter();
-    let mut min = match it.next() {
-        Some(elem) => elem,
-        None => return None,
-    };
+    let mut min = it.next()?;
     for elem in it {
         if elem < min {
             min = elem;
@@ -1,91 +1,1347 @@
 use std::{
     alloc::{self, Layout},
     array::IntoIter,
+    borrow::{Borrow, BorrowMut},
+    cmp::Ordering,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    io,
+    iter::FusedIterator,
     marker::PhantomData,
     mem,
-    mem::ManuallyDrop,
-    ops::{Deref, DerefMut},
+    mem::{ManuallyDrop, MaybeUninit},
+    ops::{Bound, Deref, DerefMut, RangeBounds},
     ptr::{self, NonNull},
     slice,
 };
 
-pub struct MyVec<T> {
-    // Covariant over T
-    ptr: NonNull<T>,
-    cap: usize,
+mod aligned;
+mod allocator;
+mod deque;
+mod growth;
+mod mystring;
+mod radix;
+mod scan;
+mod tinyvec;
+#[cfg(feature = "zeroize")]
+mod zeroize;
+
+pub use self::aligned::{Aligned, AlignedVec};
+pub use self::allocator::{AllocError, Allocator, Global};
+pub use self::deque::{MyVecDeque, MyVecDequeIntoIter, MyVecDequeIter, MyVecDequeIterMut};
+pub use self::growth::{Doubling, FixedIncrement, GrowthPolicy, OneAndHalf, ShrinkOnPop};
+pub use self::mystring::MyString;
+pub use self::radix::RadixKey;
+pub use self::scan::Scan;
+pub use self::tinyvec::{TinyVec, TinyVecIntoIter};
+#[cfg(feature = "zeroize")]
+pub use self::zeroize::Zeroizing;
+
+pub struct MyVec<T, A: Allocator = Global, G: GrowthPolicy = Doubling> {
+    buf: RawMyVec<T, A>,
     len: usize,
-    // Tell the compiler to do drop check on inner type.
-    _t: PhantomData<T>,
+    _growth: PhantomData<fn() -> G>,
 }
 
-// As is
-unsafe impl<T: Send> Send for MyVec<T> {}
-unsafe impl<T: Sync> Sync for MyVec<T> {}
-
 impl<T> MyVec<T> {
     pub fn new() -> Self {
-        assert!(std::mem::size_of::<T>() != 0, "ZST is not supported");
+        Self::new_in(Global)
+    }
+
+    /// Rebuilds a vector from the pieces `into_raw_parts` gave out, or from a buffer allocated
+    /// some other way with the global allocator.
+    ///
+    /// # Safety
+    ///
+    /// See `from_raw_parts_in`, with the global allocator as `alloc`.
+    pub unsafe fn from_raw_parts(ptr: *mut T, len: usize, cap: usize) -> Self {
+        unsafe { Self::from_raw_parts_in(ptr, len, cap, Global) }
+    }
+}
+
+impl<T, G: GrowthPolicy> MyVec<T, Global, G> {
+    /// Converts the vector into a boxed slice, shrinking the buffer to exactly the length first.
+    pub fn into_boxed_slice(mut self) -> Box<[T]> {
+        self.shrink_to_fit();
+        let vec = ManuallyDrop::new(self);
+        // The buffer was allocated with the global allocator and the layout of a `[T]` of this
+        // length, which is what Box expects to free it with.
+        unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(vec.ptr(), vec.len)) }
+    }
+
+    /// Gives up ownership of the elements, returning them as a slice that lives for the rest of
+    /// the program. The buffer is shrunk to exactly the length first, and is never freed.
+    pub fn leak<'a>(self) -> &'a mut [T] {
+        Box::leak(self.into_boxed_slice())
+    }
+}
+
+impl<T, A: Allocator> MyVec<T, A> {
+    /// Makes an empty vector that gets its memory from `alloc`. Like `new`, this doesn't
+    /// allocate until the first push.
+    pub fn new_in(alloc: A) -> Self {
+        Self::new_with(alloc)
+    }
+
+    /// Rebuilds a vector from a pointer, length and capacity, taking ownership of the buffer.
+    ///
+    /// # Safety
+    ///
+    /// - `ptr` must be non-null and aligned for `T`.
+    /// - Unless `T` is zero-sized or `cap` is 0, `ptr` must point to a block `alloc` handed out
+    ///   for `Layout::array::<T>(cap)`, and nothing else may go on to use or free it. For
+    ///   zero-sized `T`, `cap` is ignored.
+    /// - `len` must be at most `cap`, and the first `len` elements must be initialized.
+    pub unsafe fn from_raw_parts_in(ptr: *mut T, len: usize, cap: usize, alloc: A) -> Self {
+        let mut buf = RawMyVec::new_in(alloc);
+        buf.ptr = unsafe { NonNull::new_unchecked(ptr) };
+        if mem::size_of::<T>() != 0 {
+            buf.cap = cap;
+        }
         MyVec {
-            // mem::align_of::<T>() in short
-            ptr: NonNull::dangling(),
+            buf,
+            len,
+            _growth: PhantomData,
+        }
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> MyVec<T, A, G> {
+    // Like `new_in`, for any growth policy. That one only makes vectors with the default policy,
+    // so that the policy never needs spelling out where it isn't used.
+    fn new_with(alloc: A) -> Self {
+        MyVec {
+            buf: RawMyVec::new_in(alloc),
             len: 0,
-            cap: 0,
-            _t: PhantomData,
+            _growth: PhantomData,
+        }
+    }
+
+    /// Switches the vector to growing by `H`'s policy from now on, keeping its buffer.
+    pub fn with_growth<H: GrowthPolicy>(self) -> MyVec<T, A, H> {
+        let vec = ManuallyDrop::new(self);
+        MyVec {
+            buf: unsafe { ptr::read(&vec.buf) },
+            len: vec.len,
+            _growth: PhantomData,
         }
     }
 
+    /// Takes the vector apart into its pointer, length and capacity, without freeing anything.
+    /// The caller becomes responsible for the elements and the buffer, and can hand them back
+    /// with `from_raw_parts`. A vector that never allocated gives a dangling pointer and a
+    /// capacity of 0, and one of zero-sized elements always reports `usize::MAX`.
+    pub fn into_raw_parts(self) -> (*mut T, usize, usize) {
+        let (ptr, len, cap, _) = self.into_raw_parts_with_alloc();
+        (ptr, len, cap)
+    }
+
+    /// Like `into_raw_parts`, but also gives back the allocator.
+    pub fn into_raw_parts_with_alloc(self) -> (*mut T, usize, usize, A) {
+        let vec = ManuallyDrop::new(self);
+        let alloc = unsafe { ptr::read(&vec.buf.alloc) };
+        (vec.ptr(), vec.len, vec.cap(), alloc)
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.buf.ptr.as_ptr()
+    }
+
+    fn cap(&self) -> usize {
+        self.buf.cap
+    }
+
+    /// Drops the elements and overwrites the whole buffer, spare capacity included, with zeroes,
+    /// keeping the allocation. The writes aren't optimized out even if the vector is never used
+    /// again; to also wipe buffers it leaves behind when it grows, give it a `Zeroizing`
+    /// allocator.
+    #[cfg(feature = "zeroize")]
+    pub fn zeroize(&mut self) {
+        self.truncate(0);
+        if mem::size_of::<T>() != 0 {
+            unsafe { zeroize::wipe(self.ptr().cast(), self.cap() * mem::size_of::<T>()) };
+        }
+    }
+
+    /// Returns the allocator the vector gets its memory from.
+    pub fn allocator(&self) -> &A {
+        &self.buf.alloc
+    }
+
+    /// Returns the number of elements the vector can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.cap()
+    }
+
+    /// Returns a pointer to the buffer, without making a slice of the elements on the way. It's
+    /// dangling if the vector hasn't allocated.
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr()
+    }
+
+    /// Like `as_ptr`, for writing through.
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr()
+    }
+
+    /// Returns the unused capacity after the elements, for filling in place before committing
+    /// them with `set_len`.
+    pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        unsafe {
+            slice::from_raw_parts_mut(
+                self.ptr().add(self.len) as *mut MaybeUninit<T>,
+                self.cap() - self.len,
+            )
+        }
+    }
+
+    /// Sets the length without touching the elements.
+    ///
+    /// # Safety
+    ///
+    /// `new_len` must be no more than the capacity, and the elements up to it must be
+    /// initialized. Shortening the vector this way leaks the elements cut off rather than
+    /// dropping them.
+    pub unsafe fn set_len(&mut self, new_len: usize) {
+        debug_assert!(new_len <= self.cap());
+        self.len = new_len;
+    }
+
+    /// Reserves room for at least `additional` more elements, in a single reallocation. The
+    /// capacity grows by at least as much as the growth policy asks for, so with the default
+    /// policy calling this in a loop stays amortized O(1).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.cap() {
+            self.grow_to(self.grown_cap(needed));
+        }
+    }
+
+    /// Reserves room for exactly `additional` more elements, without the extra that `reserve`
+    /// leaves for future pushes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.cap() {
+            self.grow_to(needed);
+        }
+    }
+
+    /// Shrinks the capacity down to the length, freeing the buffer entirely if the vector is
+    /// empty.
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    /// Shrinks the capacity down to `min_capacity`, or the length if that's more. Does nothing if
+    /// the capacity is already that small.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let new_cap = self.len.max(min_capacity);
+        if new_cap < self.cap() {
+            self.buf.shrink_to(new_cap);
+        }
+    }
+
+    /// Like `reserve`, but returns an error instead of panicking or aborting if the memory can't
+    /// be had. The vector is unchanged on failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self
+            .len
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        if needed > self.cap() {
+            self.try_grow_to(self.grown_cap(needed))?;
+        }
+        Ok(())
+    }
+
+    /// Like `push`, but returns an error instead of aborting if the vector needs to grow and the
+    /// memory can't be had. On failure `elem` is dropped; call `try_reserve` first to keep it.
+    pub fn try_push(&mut self, elem: T) -> Result<(), TryReserveError> {
+        if self.len == self.cap() {
+            self.try_reserve(1)?;
+        }
+        unsafe {
+            ptr::write(self.ptr().add(self.len), elem);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Like `push`, but never allocates: if the vector is full, `elem` is handed back instead.
+    pub fn push_within_capacity(&mut self, elem: T) -> Result<(), T> {
+        if self.len == self.cap() {
+            return Err(elem);
+        }
+        unsafe {
+            ptr::write(self.ptr().add(self.len), elem);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
     pub fn push(&mut self, elem: T) {
-        if self.len == self.cap {
+        if self.len == self.cap() {
             self.grow();
         }
 
         unsafe {
-            ptr::write(self.ptr.as_ptr().add(self.len), elem);
+            ptr::write(self.ptr().add(self.len), elem);
         }
 
         // This can't fail, we'll OOM first.
         self.len += 1;
     }
 
-    pub fn pop(&mut self) -> Option<T> {
-        if self.len == 0 {
-            None
-        } else {
-            self.len -= 1;
-            // Copies out the bits from the target address and interpret it as a value of type T.
-            unsafe { Some(ptr::read(self.ptr.as_ptr().add(self.len))) }
-        }
-    }
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            // Copies out the bits from the target address and interpret it as a value of type T.
+            let elem = unsafe { ptr::read(self.ptr().add(self.len)) };
+            if let Some(new_cap) = G::shrink(self.cap(), self.len) {
+                self.shrink_to(new_cap);
+            }
+            Some(elem)
+        }
+    }
+
+    /// Drops every element from `len` on, keeping the capacity. Does nothing if the vector is
+    /// already that short.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(unsafe { self.ptr().add(len) }, self.len - len);
+        // Shorten first, so that if a destructor panics the vector doesn't still claim the
+        // elements that were being dropped.
+        self.len = len;
+        unsafe { ptr::drop_in_place(tail) };
+    }
+
+    /// Resizes the vector to `new_len`, truncating it or filling the new slots with clones of
+    /// `value`.
+    pub fn resize(&mut self, new_len: usize, value: T)
+    where
+        T: Clone,
+    {
+        if new_len <= self.len {
+            self.truncate(new_len);
+            return;
+        }
+        let additional = new_len - self.len;
+        self.reserve(additional);
+        // Clone into all but the last slot, and move `value` itself into that.
+        self.extend(std::iter::repeat_n(value, additional));
+    }
+
+    /// Resizes the vector to `new_len`, truncating it or filling the new slots with what `f`
+    /// returns, called once per slot in order.
+    pub fn resize_with<F: FnMut() -> T>(&mut self, new_len: usize, f: F) {
+        if new_len <= self.len {
+            self.truncate(new_len);
+            return;
+        }
+        let additional = new_len - self.len;
+        self.reserve(additional);
+        self.extend(std::iter::repeat_with(f).take(additional));
+    }
+
+    /// Clones every element of `other` onto the end, reserving room for all of them up front.
+    /// For `Copy` elements, `extend_from_copy_slice` does the same with a single memcpy.
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        self.reserve(other.len());
+        for elem in other {
+            // Count each element as soon as it's written, so a panicking clone leaves the
+            // vector holding everything cloned so far.
+            unsafe { ptr::write(self.ptr().add(self.len), elem.clone()) };
+            self.len += 1;
+        }
+    }
+
+    /// Copies every element of `other` onto the end. Like `extend_from_slice`, but for `Copy`
+    /// elements, so it can copy the whole slice at once instead of cloning one at a time.
+    pub fn extend_from_copy_slice(&mut self, other: &[T])
+    where
+        T: Copy,
+    {
+        self.reserve(other.len());
+        unsafe {
+            ptr::copy_nonoverlapping(other.as_ptr(), self.ptr().add(self.len), other.len());
+        }
+        self.len += other.len();
+    }
+
+    /// Clones the elements in `range` onto the end, reserving room for all of them up front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or ends past the end of the vector.
+    pub fn extend_from_within<R: RangeBounds<usize>>(&mut self, range: R)
+    where
+        T: Clone,
+    {
+        let (start, end) = bounds(range, self.len);
+        self.reserve(end - start);
+        for i in start..end {
+            // Reserving first means the source can't move while it's being read.
+            unsafe {
+                let elem = (*self.ptr().add(i)).clone();
+                ptr::write(self.ptr().add(self.len), elem);
+            }
+            self.len += 1;
+        }
+    }
+
+    /// Moves every element of `other` onto the end of `self`, leaving `other` empty but with its
+    /// capacity.
+    pub fn append<B: Allocator, H: GrowthPolicy>(&mut self, other: &mut MyVec<T, B, H>) {
+        self.reserve(other.len);
+        unsafe {
+            ptr::copy_nonoverlapping(other.ptr(), self.ptr().add(self.len), other.len);
+        }
+        self.len += other.len;
+        other.len = 0;
+    }
+
+    /// Splits the vector in two at `at`, returning a new vector with the elements from `at` on.
+    /// `self` keeps the elements before `at`, and its capacity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length.
+    pub fn split_off(&mut self, at: usize) -> MyVec<T, A, G>
+    where
+        A: Clone,
+    {
+        assert!(at <= self.len, "split_off index out of bounds");
+        let count = self.len - at;
+        let mut other = MyVec::new_with(self.buf.alloc.clone());
+        other.reserve_exact(count);
+        unsafe {
+            ptr::copy_nonoverlapping(self.ptr().add(at), other.ptr(), count);
+        }
+        self.len = at;
+        other.len = count;
+        other
+    }
+
+    /// Removes the elements in `range` and returns an iterator over them. The elements after the
+    /// range move down to fill the gap when the iterator is dropped, whether or not it was run to
+    /// the end.
+    ///
+    /// If the iterator is leaked instead, the drained elements and everything after them are
+    /// leaked too, and the vector is left holding just the elements before the range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or ends past the end of the vector.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> MyVecDrain<'_, T, A, G> {
+        let len = self.len;
+        let (start, end) = bounds(range, self.len);
+
+        // Until the drain is dropped, the vector only owns what's before the range. If the drain
+        // is leaked, the vector never sees the moved-out elements, or the tail it forgot.
+        self.len = start;
+        unsafe {
+            MyVecDrain {
+                iter: RawValIter::new(self.ptr().add(start), end - start),
+                vec: NonNull::from(&mut *self),
+                tail_start: end,
+                tail_len: len - end,
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    /// Keeps only the elements for which `f` returns `true`, in their original order.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retain_mut(|elem| f(elem));
+    }
+
+    /// Like `retain`, but `f` may also modify the elements it looks at.
+    ///
+    /// This is a single pass: each kept element moves down over the removed ones as soon as it's
+    /// been checked, rather than the tail shifting once per removal. If `f` panics, the elements
+    /// it hasn't seen yet are kept, and the vector is left holding everything it had decided to
+    /// keep followed by them.
+    pub fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        // Closes the gap left by the removed elements, however the pass ends. Until then the
+        // vector claims nothing, so a panic can't expose a removed element twice.
+        struct Retain<'a, T, A: Allocator, G: GrowthPolicy> {
+            vec: &'a mut MyVec<T, A, G>,
+            len: usize,
+            checked: usize,
+            removed: usize,
+        }
+
+        impl<T, A: Allocator, G: GrowthPolicy> Drop for Retain<'_, T, A, G> {
+            fn drop(&mut self) {
+                unsafe {
+                    if self.removed > 0 {
+                        let base = self.vec.ptr();
+                        ptr::copy(
+                            base.add(self.checked),
+                            base.add(self.checked - self.removed),
+                            self.len - self.checked,
+                        );
+                    }
+                    self.vec.len = self.len - self.removed;
+                }
+            }
+        }
+
+        let len = self.len;
+        self.len = 0;
+        let mut pass = Retain {
+            vec: self,
+            len,
+            checked: 0,
+            removed: 0,
+        };
+        while pass.checked < len {
+            let cur = unsafe { &mut *pass.vec.ptr().add(pass.checked) };
+            if f(cur) {
+                if pass.removed > 0 {
+                    unsafe {
+                        let base = pass.vec.ptr();
+                        ptr::copy_nonoverlapping(
+                            base.add(pass.checked),
+                            base.add(pass.checked - pass.removed),
+                            1,
+                        );
+                    }
+                }
+                pass.checked += 1;
+            } else {
+                // Count it as checked before dropping it, so that if its destructor panics the
+                // guard doesn't keep it.
+                pass.checked += 1;
+                pass.removed += 1;
+                unsafe { ptr::drop_in_place(cur) };
+            }
+        }
+    }
+
+    /// Returns an iterator that removes and yields the elements for which `f` returns `true`,
+    /// keeping the rest in their original order.
+    ///
+    /// Like `retain_mut`, this is a single pass that moves each kept element down as soon as it's
+    /// been checked. If the iterator is dropped early, the elements it hasn't checked yet are
+    /// kept. If it's leaked, the vector is left empty and every element it held, kept or not, is
+    /// leaked with it.
+    pub fn extract_if<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> MyVecExtractIf<'_, T, F, A, G> {
+        let len = self.len;
+        self.len = 0;
+        MyVecExtractIf {
+            vec: self,
+            len,
+            checked: 0,
+            removed: 0,
+            f,
+        }
+    }
+
+    /// Replaces the elements in `range` with the items of `replace_with`, and returns an
+    /// iterator over the removed elements. As with `drain`, the replacement happens when the
+    /// iterator is dropped, whether or not it was run to the end.
+    ///
+    /// The elements after the range move at most once. Items that fit in the range's place are
+    /// written straight in, and any left over are collected first so the tail moves exactly as
+    /// far as it has to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or ends past the end of the vector.
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> MyVecSplice<'_, I::IntoIter, A, G>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+    {
+        MyVecSplice {
+            drain: self.drain(range),
+            replace_with: replace_with.into_iter(),
+        }
+    }
+
+    /// Removes runs of equal elements, keeping the first of each run. On a sorted vector this
+    /// leaves no duplicates at all.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+
+    /// Removes runs of elements that map to the same key, keeping the first of each run.
+    pub fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F) {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    /// Removes runs of elements that `same_bucket` says belong together, keeping the first of
+    /// each run. `same_bucket(a, b)` is called with each element `a` and the last element kept
+    /// before it, `b`, and removes `a` if it returns `true`.
+    ///
+    /// Like `retain_mut`, this is a single pass, and if `same_bucket` panics the vector keeps
+    /// everything it hasn't checked yet.
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same_bucket: F) {
+        // Elements before `write` are kept, and elements from `read` on haven't been checked.
+        // Dropping this moves the unchecked ones down behind the kept ones.
+        struct Dedup<'a, T, A: Allocator, G: GrowthPolicy> {
+            vec: &'a mut MyVec<T, A, G>,
+            len: usize,
+            read: usize,
+            write: usize,
+        }
+
+        impl<T, A: Allocator, G: GrowthPolicy> Drop for Dedup<'_, T, A, G> {
+            fn drop(&mut self) {
+                unsafe {
+                    if self.read != self.write {
+                        let base = self.vec.ptr();
+                        ptr::copy(
+                            base.add(self.read),
+                            base.add(self.write),
+                            self.len - self.read,
+                        );
+                    }
+                    self.vec.len = self.write + (self.len - self.read);
+                }
+            }
+        }
+
+        let len = self.len;
+        if len <= 1 {
+            return;
+        }
+        self.len = 0;
+        let mut pass = Dedup {
+            vec: self,
+            len,
+            read: 1,
+            write: 1,
+        };
+        while pass.read < len {
+            let base = pass.vec.ptr();
+            let (cur, prev) =
+                unsafe { (&mut *base.add(pass.read), &mut *base.add(pass.write - 1)) };
+            if same_bucket(cur, prev) {
+                pass.read += 1;
+                unsafe { ptr::drop_in_place(cur) };
+            } else {
+                if pass.read != pass.write {
+                    unsafe { ptr::copy_nonoverlapping(cur, base.add(pass.write), 1) };
+                }
+                pass.read += 1;
+                pass.write += 1;
+            }
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len, "index out of bounds");
+        if self.cap() == self.len {
+            self.grow();
+        }
+
+        unsafe {
+            ptr::copy(
+                self.ptr().add(index),
+                self.ptr().add(index + 1),
+                self.len - index,
+            );
+            ptr::write(self.ptr().add(index), elem);
+            self.len += 1;
+        }
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        // Note: '<' because it's *not* valid to remove after everything
+        assert!(index < self.len, "index out of bounds");
+        unsafe {
+            self.len -= 1;
+            let result = ptr::read(self.ptr().add(index));
+            ptr::copy(
+                self.ptr().add(index + 1),
+                self.ptr().add(index),
+                self.len - index,
+            );
+            result
+        }
+    }
+
+    /// Inserts `elem` into a sorted vector, after any elements equal to it, so the vector stays
+    /// sorted. Returns the index it went in at.
+    ///
+    /// Finding the spot is O(log n), but the insert still shifts everything after it. If the
+    /// vector isn't sorted the spot is unspecified, but `elem` is still inserted somewhere.
+    pub fn insert_sorted(&mut self, elem: T) -> usize
+    where
+        T: Ord,
+    {
+        self.insert_sorted_by(elem, T::cmp)
+    }
+
+    /// Like `insert_sorted`, for a vector sorted by `compare`.
+    pub fn insert_sorted_by<F: FnMut(&T, &T) -> Ordering>(
+        &mut self,
+        elem: T,
+        mut compare: F,
+    ) -> usize {
+        let index = self.partition_point(|x| compare(x, &elem) != Ordering::Greater);
+        self.insert(index, elem);
+        index
+    }
+
+    /// Inserts `elem` into a sorted vector unless an equal element is already there, so the
+    /// vector stays sorted and free of duplicates.
+    ///
+    /// Returns `Ok` with the index `elem` went in at, or `Err` with the index of the equal
+    /// element, in which case `elem` is dropped. The indices are the same ones `binary_search`
+    /// would give.
+    pub fn binary_search_insert(&mut self, elem: T) -> Result<usize, usize>
+    where
+        T: Ord,
+    {
+        match self.binary_search(&elem) {
+            Ok(index) => Err(index),
+            Err(index) => {
+                self.insert(index, elem);
+                Ok(index)
+            }
+        }
+    }
+
+    /// Removes and returns the element at `index`, moving the last element into its place. This
+    /// is O(1), but doesn't keep the order; use `remove` for that.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len, "index out of bounds");
+        unsafe {
+            self.len -= 1;
+            let base = self.ptr();
+            let result = ptr::read(base.add(index));
+            // When removing the last element this copies it onto itself, which is fine, since
+            // `ptr::copy` allows overlap.
+            ptr::copy(base.add(self.len), base.add(index), 1);
+            result
+        }
+    }
+
+    // The capacity to grow to when at least `needed` is wanted.
+    fn grown_cap(&self, needed: usize) -> usize {
+        G::grow(self.cap(), needed).max(needed)
+    }
+
+    fn grow(&mut self) {
+        // A MyVec of zero-sized types is only full once its length would overflow.
+        assert!(mem::size_of::<T>() != 0, "capacity overflow");
+        // This can't overflow, since self.cap <= isize::MAX.
+        self.grow_to(self.grown_cap(self.cap() + 1));
+    }
+
+    fn grow_to(&mut self, new_cap: usize) {
+        self.buf.grow_to(new_cap);
+    }
+
+    fn try_grow_to(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        self.buf.try_grow_to(new_cap)
+    }
+}
+
+// Resolves `range` against a length of `len`.
+fn bounds<R: RangeBounds<usize>>(range: R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1).expect("range start overflows"),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.checked_add(1).expect("range end overflows"),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range starts after it ends");
+    assert!(end <= len, "range out of bounds");
+    (start, end)
+}
+
+/// The error returned when a MyVec can't get the memory it asked for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The capacity asked for doesn't fit in `isize::MAX` bytes.
+    CapacityOverflow,
+    /// The allocator couldn't provide a buffer with this layout.
+    AllocError { layout: Layout },
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => write!(f, "capacity overflow"),
+            TryReserveError::AllocError { layout } => {
+                write!(f, "failed to allocate {} bytes", layout.size())
+            }
+        }
+    }
+}
+
+impl Error for TryReserveError {}
+
+// Implemet Deref and DerefMut, so we can have len, first, last, indexing, slicing, sorting,
+// iter, iter_mut, and all other sorts of bells and whistles provided by slice. Sweet!
+// All we need is slice::from_raw_parts. It will correctly handle empty slices for us. '
+// Later once we set up zero-sized type support it will also Just Work for those too.
+impl<T, A: Allocator, G: GrowthPolicy> Deref for MyVec<T, A, G> {
+    type Target = [T];
+    fn deref(&self) -> &Self::Target {
+        unsafe { slice::from_raw_parts(self.ptr(), self.len) }
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> DerefMut for MyVec<T, A, G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { slice::from_raw_parts_mut(self.ptr(), self.len) }
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> AsRef<[T]> for MyVec<T, A, G> {
+    fn as_ref(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> AsMut<[T]> for MyVec<T, A, G> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> Borrow<[T]> for MyVec<T, A, G> {
+    fn borrow(&self) -> &[T] {
+        self
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> BorrowMut<[T]> for MyVec<T, A, G> {
+    fn borrow_mut(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+/// Takes over the Vec's buffer rather than copying it. Both allocate with the global allocator
+/// and the layout of a `[T]` of their capacity, so the buffer is one MyVec can free.
+impl<T> From<Vec<T>> for MyVec<T> {
+    fn from(vec: Vec<T>) -> Self {
+        let mut vec = ManuallyDrop::new(vec);
+        unsafe { MyVec::from_raw_parts(vec.as_mut_ptr(), vec.len(), vec.capacity()) }
+    }
+}
+
+/// Hands the buffer over to a Vec without copying it, the reverse of `From<Vec<T>>`.
+impl<T, G: GrowthPolicy> From<MyVec<T, Global, G>> for Vec<T> {
+    fn from(vec: MyVec<T, Global, G>) -> Self {
+        let (ptr, len, cap) = vec.into_raw_parts();
+        unsafe { Vec::from_raw_parts(ptr, len, cap) }
+    }
+}
+
+impl<T: Clone> From<&[T]> for MyVec<T> {
+    fn from(slice: &[T]) -> Self {
+        let mut vec = MyVec::new();
+        vec.reserve_exact(slice.len());
+        vec.extend_from_slice(slice);
+        vec
+    }
+}
+
+pub struct MyVecIntoIter<T, A: Allocator = Global> {
+    // Only kept so the buffer is freed when the iterator is dropped.
+    _buf: RawMyVec<T, A>,
+    iter: RawValIter<T>,
+}
+
+impl<T, A: Allocator> Iterator for MyVecIntoIter<T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    // Both leave dropping whatever is skipped over to Drop, rather than reading it all out.
+    fn count(self) -> usize {
+        self.len()
+    }
+
+    fn last(mut self) -> Option<T> {
+        self.next_back()
+    }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for MyVecIntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back()
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for MyVecIntoIter<T, A> {}
+
+impl<T, A: Allocator> FusedIterator for MyVecIntoIter<T, A> {}
+
+impl<T, A: Allocator> Drop for MyVecIntoIter<T, A> {
+    fn drop(&mut self) {
+        // destroy the remaining elements, and let RawMyVec free the buffer
+        for _ in &mut *self {}
+    }
+}
+
+/// An iterator that takes a range of elements out of a MyVec, made by `MyVec::drain`.
+pub struct MyVecDrain<'a, T, A: Allocator = Global, G: GrowthPolicy = Doubling> {
+    vec: NonNull<MyVec<T, A, G>>,
+    // The elements still to be yielded.
+    iter: RawValIter<T>,
+    // Where the elements after the range are, and how many there are.
+    tail_start: usize,
+    tail_len: usize,
+    _marker: PhantomData<&'a mut MyVec<T, A, G>>,
+}
+
+unsafe impl<T: Send, A: Allocator + Send, G: GrowthPolicy> Send for MyVecDrain<'_, T, A, G> {}
+unsafe impl<T: Sync, A: Allocator + Sync, G: GrowthPolicy> Sync for MyVecDrain<'_, T, A, G> {}
+
+impl<T, A: Allocator, G: GrowthPolicy> Iterator for MyVecDrain<'_, T, A, G> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> DoubleEndedIterator for MyVecDrain<'_, T, A, G> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back()
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> Drop for MyVecDrain<'_, T, A, G> {
+    fn drop(&mut self) {
+        // Moves the tail down behind what the vector kept, even if dropping the elements that
+        // weren't yielded panics.
+        struct MoveTail<'r, 'a, T, A: Allocator, G: GrowthPolicy>(&'r mut MyVecDrain<'a, T, A, G>);
+
+        impl<T, A: Allocator, G: GrowthPolicy> Drop for MoveTail<'_, '_, T, A, G> {
+            fn drop(&mut self) {
+                unsafe {
+                    let vec = self.0.vec.as_mut();
+                    let start = vec.len;
+                    if self.0.tail_start != start {
+                        ptr::copy(
+                            vec.ptr().add(self.0.tail_start),
+                            vec.ptr().add(start),
+                            self.0.tail_len,
+                        );
+                    }
+                    vec.len = start + self.0.tail_len;
+                }
+            }
+        }
+
+        let remaining = self.iter.take_remaining();
+        let guard = MoveTail(self);
+        unsafe { ptr::drop_in_place(remaining) };
+        drop(guard);
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> ExactSizeIterator for MyVecDrain<'_, T, A, G> {}
+
+impl<T, A: Allocator, G: GrowthPolicy> FusedIterator for MyVecDrain<'_, T, A, G> {}
+
+impl<T, A: Allocator, G: GrowthPolicy> MyVecDrain<'_, T, A, G> {
+    /// Writes items from `replace_with` into the gap between the end of the vector and the tail,
+    /// and returns whether that filled it.
+    unsafe fn fill<I: Iterator<Item = T>>(&mut self, replace_with: &mut I) -> bool {
+        let vec = unsafe { self.vec.as_mut() };
+        while vec.len < self.tail_start {
+            match replace_with.next() {
+                Some(item) => {
+                    unsafe { ptr::write(vec.ptr().add(vec.len), item) };
+                    vec.len += 1;
+                }
+                None => return false,
+            }
+        }
+        true
+    }
+
+    /// Moves the tail `additional` places further along, growing the buffer if needed.
+    unsafe fn move_tail(&mut self, additional: usize) {
+        let vec = unsafe { self.vec.as_mut() };
+        let needed = (self.tail_start + self.tail_len)
+            .checked_add(additional)
+            .expect("capacity overflow");
+        if needed > vec.cap() {
+            vec.grow_to(vec.grown_cap(needed));
+        }
+        let new_tail_start = self.tail_start + additional;
+        unsafe {
+            ptr::copy(
+                vec.ptr().add(self.tail_start),
+                vec.ptr().add(new_tail_start),
+                self.tail_len,
+            );
+        }
+        self.tail_start = new_tail_start;
+    }
+}
+
+pub struct MyVecSplice<'a, I: Iterator + 'a, A: Allocator = Global, G: GrowthPolicy = Doubling> {
+    drain: MyVecDrain<'a, I::Item, A, G>,
+    replace_with: I,
+}
+
+impl<I: Iterator, A: Allocator, G: GrowthPolicy> Iterator for MyVecSplice<'_, I, A, G> {
+    type Item = I::Item;
+    fn next(&mut self) -> Option<I::Item> {
+        self.drain.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.drain.size_hint()
+    }
+}
+
+impl<I: Iterator, A: Allocator, G: GrowthPolicy> DoubleEndedIterator for MyVecSplice<'_, I, A, G> {
+    fn next_back(&mut self) -> Option<I::Item> {
+        self.drain.next_back()
+    }
+}
+
+impl<I: Iterator, A: Allocator, G: GrowthPolicy> ExactSizeIterator for MyVecSplice<'_, I, A, G> {}
+
+impl<I: Iterator, A: Allocator, G: GrowthPolicy> FusedIterator for MyVecSplice<'_, I, A, G> {}
+
+impl<I: Iterator, A: Allocator, G: GrowthPolicy> Drop for MyVecSplice<'_, I, A, G> {
+    fn drop(&mut self) {
+        self.drain.by_ref().for_each(drop);
+        // Growing the buffer would leave the drain pointing into the old one. Nothing is left to
+        // yield, so point it somewhere harmless.
+        self.drain.iter = unsafe { RawValIter::new(NonNull::dangling().as_ptr(), 0) };
+
+        unsafe {
+            if self.drain.tail_len == 0 {
+                self.drain.vec.as_mut().extend(self.replace_with.by_ref());
+                return;
+            }
+            if !self.drain.fill(&mut self.replace_with) {
+                return;
+            }
+            // The gap is full but there may be more, so find out exactly how much before moving
+            // the tail out of the way.
+            let rest: MyVec<I::Item> = self.replace_with.by_ref().collect();
+            if !rest.is_empty() {
+                self.drain.move_tail(rest.len());
+                let filled = self.drain.fill(&mut rest.into_iter());
+                debug_assert!(filled);
+            }
+        }
+        // Dropping the drain moves the tail back down if the items didn't fill the gap.
+    }
+}
+
+/// An iterator that removes the elements matching a predicate, made by `MyVec::extract_if`.
+pub struct MyVecExtractIf<'a, T, F, A: Allocator = Global, G: GrowthPolicy = Doubling> {
+    // Claims nothing until the iterator is dropped.
+    vec: &'a mut MyVec<T, A, G>,
+    len: usize,
+    checked: usize,
+    removed: usize,
+    f: F,
+}
+
+impl<T, F: FnMut(&mut T) -> bool, A: Allocator, G: GrowthPolicy> Iterator
+    for MyVecExtractIf<'_, T, F, A, G>
+{
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        while self.checked < self.len {
+            unsafe {
+                let base = self.vec.ptr();
+                let cur = base.add(self.checked);
+                // If `f` panics, `checked` hasn't moved past this element yet, so it's kept.
+                let extract = (self.f)(&mut *cur);
+                self.checked += 1;
+                if extract {
+                    self.removed += 1;
+                    return Some(ptr::read(cur));
+                }
+                if self.removed > 0 {
+                    ptr::copy_nonoverlapping(cur, cur.sub(self.removed), 1);
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.len - self.checked))
+    }
+}
+
+impl<T, F, A: Allocator, G: GrowthPolicy> Drop for MyVecExtractIf<'_, T, F, A, G> {
+    fn drop(&mut self) {
+        unsafe {
+            if self.removed > 0 {
+                let base = self.vec.ptr();
+                ptr::copy(
+                    base.add(self.checked),
+                    base.add(self.checked - self.removed),
+                    self.len - self.checked,
+                );
+            }
+            self.vec.len = self.len - self.removed;
+        }
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> Extend<T> for MyVec<T, A, G> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        while let Some(elem) = iter.next() {
+            if self.len == self.cap() {
+                // Take the iterator's word for how much is left, but only ever as a lower bound.
+                let (lower, _) = iter.size_hint();
+                self.reserve(lower.saturating_add(1));
+            }
+            unsafe { ptr::write(self.ptr().add(self.len), elem) };
+            self.len += 1;
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a, A: Allocator, G: GrowthPolicy> Extend<&'a T> for MyVec<T, A, G> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T> FromIterator<T> for MyVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut vec = MyVec::new();
+        // The hint is only trusted up to its lower bound, and a hint that's too low just means
+        // extend grows the buffer as usual.
+        vec.reserve_exact(iter.size_hint().0);
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> IntoIterator for MyVec<T, A, G> {
+    type Item = T;
+    type IntoIter = MyVecIntoIter<T, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let vec = ManuallyDrop::new(self);
+        // Can't destructure MyVec since it's Drop, so move the buffer out from under it.
+        unsafe {
+            MyVecIntoIter {
+                iter: RawValIter::new(vec.ptr(), vec.len),
+                _buf: ptr::read(&vec.buf),
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator, G: GrowthPolicy> Drop for MyVec<T, A, G> {
+    fn drop(&mut self) {
+        // call 'destructors' for all elements in the vector, all at once rather than popping
+        // them, which could have the growth policy reallocating a buffer about to be freed
+        self.truncate(0);
+        // deallocation is handled by RawMyVec
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for MyVec<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut vec = MyVec::new();
+        for elem in u.arbitrary_iter()? {
+            vec.push(elem?);
+        }
+        Ok(vec)
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let mut vec = MyVec::new();
+        for elem in u.arbitrary_take_rest_iter()? {
+            vec.push(elem?);
+        }
+        Ok(vec)
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone, G: GrowthPolicy> Clone for MyVec<T, A, G> {
+    fn clone(&self) -> Self {
+        let mut vec = MyVec::new_with(self.buf.alloc.clone());
+        vec.reserve_exact(self.len);
+        vec.extend_from_slice(self);
+        vec
+    }
+
+    /// Reuses `self`'s buffer if it's big enough, and clones onto the elements already there
+    /// rather than dropping them and cloning afresh.
+    fn clone_from(&mut self, source: &Self) {
+        self.truncate(source.len);
+        let (init, tail) = source.split_at(self.len);
+        self.clone_from_slice(init);
+        self.extend_from_slice(tail);
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator, G: GrowthPolicy> fmt::Debug for MyVec<T, A, G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+macro_rules! impl_slice_eq {
+    ($([$($vars:tt)*] $lhs:ty, $rhs:ty;)*) => {$(
+        impl<$($vars)* T: PartialEq<U>, U> PartialEq<$rhs> for $lhs {
+            fn eq(&self, other: &$rhs) -> bool {
+                self[..] == other[..]
+            }
+        }
+    )*};
+}
+
+impl_slice_eq! {
+    [A: Allocator, G: GrowthPolicy, B: Allocator, H: GrowthPolicy,] MyVec<T, A, G>, MyVec<U, B, H>;
+    [A: Allocator, G: GrowthPolicy,] MyVec<T, A, G>, [U];
+    ['a, A: Allocator, G: GrowthPolicy,] MyVec<T, A, G>, &'a [U];
+    [A: Allocator, G: GrowthPolicy,] MyVec<T, A, G>, Vec<U>;
+    [A: Allocator, G: GrowthPolicy, const N: usize,] MyVec<T, A, G>, [U; N];
+    [B: Allocator, H: GrowthPolicy,] Vec<T>, MyVec<U, B, H>;
+    [B: Allocator, H: GrowthPolicy,] [T], MyVec<U, B, H>;
+    ['a, B: Allocator, H: GrowthPolicy,] &'a [T], MyVec<U, B, H>;
+}
+
+impl<T: Eq, A: Allocator, G: GrowthPolicy> Eq for MyVec<T, A, G> {}
+
+impl<T: PartialOrd, A: Allocator, G: GrowthPolicy> PartialOrd for MyVec<T, A, G> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, A: Allocator, G: GrowthPolicy> Ord for MyVec<T, A, G> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+// Hashes the same as a slice of the same elements, so a MyVec can be looked up by slice.
+impl<T: Hash, A: Allocator, G: GrowthPolicy> Hash for MyVec<T, A, G> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+/// Appends everything written, so a byte vector can be used as an in-memory sink. Writes never
+/// fail short of running out of memory.
+impl<A: Allocator, G: GrowthPolicy> io::Write for MyVec<u8, A, G> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_copy_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        self.reserve(len);
+        for buf in bufs {
+            self.extend_from_copy_slice(buf);
+        }
+        Ok(len)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.extend_from_copy_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T> Default for MyVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The allocation behind a MyVec: a buffer with room for `cap` elements, and nothing about
+/// which of them are initialized. Growing, shrinking and freeing it all happen here.
+struct RawMyVec<T, A: Allocator = Global> {
+    // Covariant over T
+    ptr: NonNull<T>,
+    cap: usize,
+    alloc: A,
+    // Tell the compiler to do drop check on inner type.
+    _t: PhantomData<T>,
+}
 
-    pub fn insert(&mut self, index: usize, elem: T) {
-        assert!(index <= self.len, "index out of bounds");
-        if self.cap == self.len {
-            self.grow();
-        }
+// As is
+unsafe impl<T: Send, A: Allocator + Send> Send for RawMyVec<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for RawMyVec<T, A> {}
 
-        unsafe {
-            ptr::copy(
-                self.ptr.as_ptr().add(index),
-                self.ptr.as_ptr().add(index + 1),
-                self.len - index,
-            );
-            ptr::write(self.ptr.as_ptr().add(index), elem);
-            self.len += 1;
+impl<T, A: Allocator> RawMyVec<T, A> {
+    fn new_in(alloc: A) -> Self {
+        // Zero-sized types never need an allocation, so a MyVec of them starts out with all the
+        // capacity it will ever have, and only counts its elements.
+        let cap = if mem::size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            0
+        };
+        RawMyVec {
+            // mem::align_of::<T>() in short
+            ptr: NonNull::dangling(),
+            cap,
+            alloc,
+            _t: PhantomData,
         }
     }
 
-    pub fn remove(&mut self, index: usize) -> T {
-        // Note: '<' because it's *not* valid to remove after everything
-        assert!(index < self.len, "index out of bounds");
-        unsafe {
-            self.len -= 1;
-            let result = ptr::read(self.ptr.as_ptr().add(index));
-            ptr::copy(
-                self.ptr.as_ptr().add(index + 1),
-                self.ptr.as_ptr().add(index),
-                self.len - index,
-            );
-            result
+    // Reallocate the buffer to hold exactly `new_cap` elements, which must be more than it holds
+    // now.
+    fn grow_to(&mut self, new_cap: usize) {
+        match self.try_grow_to(new_cap) {
+            Ok(()) => {}
+            Err(TryReserveError::CapacityOverflow) => panic!("capacity overflow"),
+            // platform-specific OOM handler
+            Err(TryReserveError::AllocError { layout }) => alloc::handle_alloc_error(layout),
         }
     }
 
@@ -97,72 +1353,125 @@ impl<T> MyVec<T> {
     // memory first. But on on 32-bit targets, particularly those with extensions to use more of
     // the address space (PAE x86 or x32), it's theoretically possible to successfully allocate
     // more than isize::MAX bytes of memory.
-    fn grow(&mut self) {
-        let (new_cap, new_layout) = if self.cap == 0 {
-            // If self.cap is 0, we allocate 1 element.
-            (1, Layout::array::<T>(1).unwrap())
-        } else {
-            // Can't overflow since self.cap <= isize::MAX.
-            let new_cap = 2 * self.cap;
-            // 'Layout::array' checks that the number of bytes is <= usize::MAX,
-            let new_layout = Layout::array::<T>(new_cap).unwrap();
-            (new_cap, new_layout)
-        };
+    fn try_grow_to(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        // 'Layout::array' checks that the number of bytes is <= usize::MAX,
+        let new_layout =
+            Layout::array::<T>(new_cap).map_err(|_| TryReserveError::CapacityOverflow)?;
 
         // However since this is a tutorial, we're not going to be particularly optimal here, and
         // just unconditionally check, rather than use clever platform-specific cfgs.
-        assert!(
-            new_layout.size() <= isize::MAX as usize,
-            "Allocation to large"
-        );
+        if new_layout.size() > isize::MAX as usize {
+            return Err(TryReserveError::CapacityOverflow);
+        }
 
         let new_ptr = if self.cap == 0 {
-            unsafe { alloc::alloc(new_layout) }
+            self.alloc.allocate(new_layout)
         } else {
             let old_layout = Layout::array::<T>(self.cap).unwrap();
-            let old_ptr = self.ptr.as_ptr() as *mut u8;
-            unsafe { alloc::realloc(old_ptr, old_layout, new_layout.size()) }
+            unsafe { self.alloc.grow(self.ptr.cast(), old_layout, new_layout) }
         };
 
-        // If allocation failes, 'new_ptr' will be null, in which case we abort.
-        self.ptr = match NonNull::new(new_ptr as *mut T) {
-            Some(p) => p,
-            // platform-specific OOM handler
-            None => alloc::handle_alloc_error(new_layout),
-        };
+        // If allocation fails, the old buffer is left as it was.
+        self.ptr = new_ptr
+            .map_err(|AllocError| TryReserveError::AllocError { layout: new_layout })?
+            .cast();
         self.cap = new_cap;
+        Ok(())
     }
-}
 
-// Implemet Deref and DerefMut, so we can have len, first, last, indexing, slicing, sorting,
-// iter, iter_mut, and all other sorts of bells and whistles provided by slice. Sweet!
-// All we need is slice::from_raw_parts. It will correctly handle empty slices for us. '
-// Later once we set up zero-sized type support it will also Just Work for those too.
-impl<T> Deref for MyVec<T> {
-    type Target = [T];
-    fn deref(&self) -> &Self::Target {
-        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    // Reallocate the buffer to hold exactly `new_cap` elements, which must be fewer than it
+    // holds now, freeing it entirely if that's none.
+    fn shrink_to(&mut self, new_cap: usize) {
+        if mem::size_of::<T>() == 0 {
+            return;
+        }
+        let old_layout = Layout::array::<T>(self.cap).unwrap();
+        let old_ptr = self.ptr.cast::<u8>();
+        if new_cap == 0 {
+            unsafe { self.alloc.deallocate(old_ptr, old_layout) };
+            self.ptr = NonNull::dangling();
+        } else {
+            let new_layout = Layout::array::<T>(new_cap).unwrap();
+            self.ptr = match unsafe { self.alloc.shrink(old_ptr, old_layout, new_layout) } {
+                Ok(p) => p.cast(),
+                Err(AllocError) => alloc::handle_alloc_error(new_layout),
+            };
+        }
+        self.cap = new_cap;
     }
 }
 
-impl<T> DerefMut for MyVec<T> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+impl<T, A: Allocator> Drop for RawMyVec<T, A> {
+    fn drop(&mut self) {
+        if self.cap != 0 && mem::size_of::<T>() != 0 {
+            let layout = Layout::array::<T>(self.cap).unwrap();
+            unsafe {
+                self.alloc.deallocate(self.ptr.cast(), layout);
+            }
+        }
     }
 }
 
-pub struct MyVecIntoIter<T> {
-    buf: NonNull<T>,
-    cap: usize,
+/// A cursor over elements that are read out by value, from either end. MyVecIntoIter and
+/// MyVecDrain both walk their elements with one of these, and only differ in who owns the buffer.
+struct RawValIter<T> {
     start: *const T,
     end: *const T,
 }
 
-impl<T> Iterator for MyVecIntoIter<T> {
+// It only hands out the elements by value, so it's as thread-safe as they are.
+unsafe impl<T: Send> Send for RawValIter<T> {}
+unsafe impl<T: Sync> Sync for RawValIter<T> {}
+
+impl<T> RawValIter<T> {
+    // unsafe to construct because it has no associated lifetimes. This is necessary to store a
+    // RawValIter in the same struct as its actual allocation. OK since it's a private
+    // implementation detail. Takes a raw pointer rather than a slice, since a MyVecDrain drops
+    // what it doesn't yield, and a pointer from a shared borrow can't be written through.
+    unsafe fn new(start: *mut T, len: usize) -> Self {
+        RawValIter {
+            start,
+            end: if mem::size_of::<T>() == 0 {
+                // Offsetting by zero-sized elements wouldn't move the pointer, so count bytes.
+                start.wrapping_byte_add(len)
+            } else if len == 0 {
+                // if `len = 0`, then this is not actually allocated memory. Need to avoid
+                // offsetting because that will give wrong information to LLVM via GEP.
+                start
+            } else {
+                unsafe { start.add(len) }
+            },
+        }
+    }
+
+    fn len(&self) -> usize {
+        let elem_size = mem::size_of::<T>().max(1);
+        (self.end as usize - self.start as usize) / elem_size
+    }
+
+    /// Gives up the elements not yet read, returning them for the caller to drop.
+    fn take_remaining(&mut self) -> *mut [T] {
+        let first = if mem::size_of::<T>() == 0 {
+            NonNull::dangling().as_ptr()
+        } else {
+            self.start as *mut T
+        };
+        let remaining = ptr::slice_from_raw_parts_mut(first, self.len());
+        self.start = self.end;
+        remaining
+    }
+}
+
+impl<T> Iterator for RawValIter<T> {
     type Item = T;
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next(&mut self) -> Option<T> {
         if self.start == self.end {
             None
+        } else if mem::size_of::<T>() == 0 {
+            // Zero-sized elements can't be told apart by address, so `start` and `end` just count
+            // them off a byte at a time, and any aligned pointer is fine to read them from.
+            self.start = self.start.wrapping_byte_add(1);
+            unsafe { Some(ptr::read(NonNull::dangling().as_ptr())) }
         } else {
             unsafe {
                 let result = ptr::read(self.start);
@@ -173,15 +1482,18 @@ impl<T> Iterator for MyVecIntoIter<T> {
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let len = (self.end as usize - self.start as usize) / mem::size_of::<T>();
+        let len = self.len();
         (len, Some(len))
     }
 }
 
-impl<T> DoubleEndedIterator for MyVecIntoIter<T> {
-    fn next_back(&mut self) -> Option<Self::Item> {
+impl<T> DoubleEndedIterator for RawValIter<T> {
+    fn next_back(&mut self) -> Option<T> {
         if self.start == self.end {
             None
+        } else if mem::size_of::<T>() == 0 {
+            self.end = self.end.wrapping_byte_sub(1);
+            unsafe { Some(ptr::read(NonNull::dangling().as_ptr())) }
         } else {
             unsafe {
                 self.end = self.end.offset(-1);
@@ -191,103 +1503,825 @@ impl<T> DoubleEndedIterator for MyVecIntoIter<T> {
     }
 }
 
-impl<T> Drop for MyVecIntoIter<T> {
-    fn drop(&mut self) {
-        // destroy the remaining elements
-        for _ in &mut *self {}
-        let layout = Layout::array::<T>(self.cap).unwrap();
-        unsafe {
-            alloc::dealloc(self.buf.as_ptr() as *mut u8, layout);
+#[cfg(test)]
+mod test {
+    use super::{AllocError, Allocator, Global, MyVec, TryReserveError};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_try_reserve() {
+        let mut vec = MyVec::new();
+        assert_eq!(vec.try_push(1u64), Ok(()));
+        assert_eq!(vec.try_reserve(100), Ok(()));
+        assert!(vec.capacity() >= 101);
+        assert_eq!(
+            vec.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(
+            vec.try_reserve(isize::MAX as usize / 8),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        // Asking for more than any allocator can give fails without aborting. Miri stops the
+        // program instead of failing the allocation, so leave it out there.
+        #[cfg(not(miri))]
+        assert!(matches!(
+            vec.try_reserve(isize::MAX as usize / 8 - 1),
+            Err(TryReserveError::AllocError { .. })
+        ));
+        assert_eq!(&vec[..], [1]);
+
+        let mut units = MyVec::new();
+        units.len = usize::MAX;
+        assert_eq!(units.try_push(()), Err(TryReserveError::CapacityOverflow));
+        units.len = 0;
+    }
+
+    #[test]
+    fn test_shrink() {
+        let mut vec = MyVec::new();
+        vec.reserve_exact(100);
+        for i in 0..10 {
+            vec.push(i.to_string());
         }
+        vec.shrink_to(50);
+        assert_eq!(vec.capacity(), 50);
+        vec.shrink_to(60);
+        assert_eq!(vec.capacity(), 50);
+        vec.shrink_to_fit();
+        assert_eq!(vec.capacity(), 10);
+        assert_eq!(vec[9], "9");
+        while vec.pop().is_some() {}
+        vec.shrink_to_fit();
+        assert_eq!(vec.capacity(), 0);
+        vec.push("again".to_string());
+        assert_eq!(vec[..], ["again"]);
     }
-}
 
-impl<T> IntoIterator for MyVec<T> {
-    type Item = T;
-    type IntoIter = MyVecIntoIter<T>;
+    #[test]
+    fn test_truncate() {
+        let mut vec = MyVec::new();
+        for i in 0..10 {
+            vec.push(i.to_string());
+        }
+        let cap = vec.capacity();
+        vec.truncate(20);
+        assert_eq!(vec.len(), 10);
+        vec.truncate(3);
+        assert_eq!(vec[..], ["0", "1", "2"]);
+        assert_eq!(vec.capacity(), cap);
+        vec.truncate(0);
+        assert!(vec.is_empty());
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        let vec = ManuallyDrop::new(self);
+    #[test]
+    fn test_drain() {
+        let make = || {
+            let mut vec = MyVec::new();
+            for i in 0..10 {
+                vec.push(i.to_string());
+            }
+            vec
+        };
 
-        // Can't destructure Vec since it's Drop
-        let ptr = vec.ptr;
-        let cap = vec.cap;
-        let len = vec.len;
+        let mut vec = make();
+        let drained: Vec<_> = vec.drain(2..5).collect();
+        assert_eq!(drained, ["2", "3", "4"]);
+        assert_eq!(vec[..], ["0", "1", "5", "6", "7", "8", "9"]);
 
-        unsafe {
-            MyVecIntoIter {
-                buf: ptr,
-                cap,
-                start: ptr.as_ptr(),
-                end: if cap == 0 {
-                    // can't offset this pointer, it's not allocated
-                    ptr.as_ptr()
-                } else {
-                    ptr.as_ptr().add(len)
-                },
-            }
+        let mut drain = vec.drain(1..=5);
+        assert_eq!(drain.len(), 5);
+        assert_eq!(drain.next_back().as_deref(), Some("8"));
+        assert_eq!(drain.next().as_deref(), Some("1"));
+        drop(drain);
+        assert_eq!(vec[..], ["0", "9"]);
+        assert_eq!(vec.drain(..).count(), 2);
+        assert!(vec.is_empty());
+
+        // A leaked drain leaves only what came before the range.
+        let mut vec = MyVec::new();
+        for i in 0..10 {
+            vec.push(i);
         }
+        std::mem::forget(vec.drain(3..6));
+        assert_eq!(vec[..], [0, 1, 2]);
+
+        let mut units = MyVec::new();
+        for _ in 0..5 {
+            units.push(());
+        }
+        assert_eq!(units.drain(1..3).count(), 2);
+        assert_eq!(units.len(), 3);
     }
-}
 
-impl<T> Drop for MyVec<T> {
-    fn drop(&mut self) {
-        if self.cap != 0 {
-            // call 'destructors' for all elements in the vector
-            #[allow(clippy::redundant_pattern_matching)]
-            while let Some(_) = self.pop() {}
-            let layout = Layout::array::<T>(self.cap).unwrap();
-            unsafe {
-                alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_drain_out_of_bounds() {
+        let mut vec = MyVec::new();
+        vec.push(1);
+        vec.drain(0..2);
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut vec = MyVec::new();
+        vec.reserve(10);
+        assert_eq!(vec.capacity(), 10);
+        for i in 0..10 {
+            vec.push(i);
+        }
+        assert_eq!(vec.capacity(), 10);
+        vec.reserve(1);
+        assert_eq!(vec.capacity(), 20);
+        vec.reserve_exact(15);
+        assert_eq!(vec.capacity(), 25);
+        vec.reserve(5);
+        assert_eq!(vec.capacity(), 25);
+        assert_eq!(&vec[..], &(0..10).collect::<Vec<_>>()[..]);
+
+        let mut units = MyVec::new();
+        units.push(());
+        units.reserve(usize::MAX - 1);
+        assert_eq!(units.capacity(), usize::MAX);
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let mut vec = MyVec::new();
+        for _ in 0..10 {
+            vec.push(());
+        }
+        vec.insert(3, ());
+        assert_eq!(vec.remove(0), ());
+        assert_eq!(vec.pop(), Some(()));
+        assert_eq!(vec.len(), 9);
+        let mut iter = vec.into_iter();
+        assert_eq!(iter.size_hint(), (9, Some(9)));
+        assert_eq!(iter.next_back(), Some(()));
+        assert_eq!(iter.count(), 8);
+
+        // Every element is still dropped, whether popped, iterated or left behind.
+        thread_local!(static DROPS: Cell<usize> = const { Cell::new(0) });
+        struct Counted;
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.with(|drops| drops.set(drops.get() + 1));
             }
         }
+        let mut vec = MyVec::new();
+        for _ in 0..6 {
+            vec.push(Counted);
+        }
+        vec.pop();
+        let mut iter = vec.into_iter();
+        iter.next();
+        drop(iter);
+        assert_eq!(DROPS.with(Cell::get), 6);
     }
-}
 
-impl<T> Default for MyVec<T> {
-    fn default() -> Self {
-        Self::new()
+    #[test]
+    fn test_retain() {
+        let mut vec = MyVec::new();
+        for i in 0..10 {
+            vec.push(i.to_string());
+        }
+        vec.retain(|s| s.parse::<i32>().unwrap() % 3 != 0);
+        assert_eq!(vec[..], ["1", "2", "4", "5", "7", "8"]);
+        vec.retain_mut(|s| {
+            s.push('!');
+            s != "5!"
+        });
+        assert_eq!(vec[..], ["1!", "2!", "4!", "7!", "8!"]);
+        vec.retain(|_| false);
+        assert!(vec.is_empty());
     }
-}
 
-pub struct RawMyVec<T> {
-    ptr: NonNull<T>,
-    cap: usize,
-}
+    #[test]
+    fn test_retain_panic() {
+        let mut vec = MyVec::new();
+        for i in 0..8 {
+            vec.push(i.to_string());
+        }
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vec.retain(|s| match s.as_str() {
+                "5" => panic!("predicate panicked"),
+                s => s.parse::<i32>().unwrap() % 2 == 0,
+            })
+        }));
+        assert!(result.is_err());
+        // What was removed stays removed, and nothing from "5" on was touched.
+        assert_eq!(vec[..], ["0", "2", "4", "5", "6", "7"]);
+    }
 
-impl<T> RawMyVec<T> {
-    fn new() -> Self {
-        assert!(std::mem::size_of::<T>() != 0, "ZST is not supported");
-        RawMyVec {
-            ptr: NonNull::dangling(),
-            cap: 0,
+    #[test]
+    fn test_extend() {
+        let mut vec = MyVec::new();
+        vec.extend((0..3).map(|i| i.to_string()));
+        vec.extend_from_slice(&["3".to_string(), "4".to_string()]);
+        assert_eq!(vec[..], ["0", "1", "2", "3", "4"]);
+        // An iterator that claims to have nothing left still gets everything added.
+        vec.extend((5..40).filter(|_| true).map(|i| i.to_string()));
+        assert_eq!(vec.len(), 40);
+        assert_eq!(vec[39], "39");
+
+        let mut nums = MyVec::new();
+        nums.extend_from_copy_slice(&[1u16, 2, 3]);
+        nums.extend(&[4, 5]);
+        nums.extend_from_copy_slice(&[]);
+        assert_eq!(nums[..], [1, 2, 3, 4, 5]);
+
+        let mut units = MyVec::new();
+        units.extend_from_copy_slice(&[(); 3]);
+        units.extend_from_slice(&[(); 2]);
+        assert_eq!(units.len(), 5);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let vec: MyVec<String> = (0..5).map(|i| i.to_string()).collect();
+        assert_eq!(vec[..], ["0", "1", "2", "3", "4"]);
+        assert_eq!(vec.capacity(), 5);
+
+        // Iterators whose hints are wrong either way still collect correctly.
+        struct Liar(std::ops::Range<u32>, (usize, Option<usize>));
+        impl Iterator for Liar {
+            type Item = u32;
+            fn next(&mut self) -> Option<u32> {
+                self.0.next()
+            }
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.1
+            }
         }
+        let vec: MyVec<u32> = Liar(0..100, (0, Some(1))).collect();
+        assert!(vec.iter().copied().eq(0..100));
+        let vec: MyVec<u32> = Liar(0..3, (50, None)).collect();
+        assert_eq!(vec[..], [0, 1, 2]);
     }
 
-    fn grow(&mut self) {
-        let (new_cap, new_layout) = if self.cap == 0 {
-            (1, Layout::array::<T>(1).unwrap())
-        } else {
-            let new_cap = 2 * self.cap;
-            (new_cap, Layout::array::<T>(new_cap).unwrap())
+    #[test]
+    fn test_clone() {
+        let vec: MyVec<String> = (0..5).map(|i| i.to_string()).collect();
+        let mut copy = vec.clone();
+        assert_eq!(copy[..], vec[..]);
+        assert_eq!(copy.capacity(), 5);
+
+        let mut big: MyVec<String> = (0..20).map(|_| String::new()).collect();
+        let cap = big.capacity();
+        big.clone_from(&vec);
+        assert_eq!(big[..], vec[..]);
+        assert_eq!(big.capacity(), cap);
+
+        copy.truncate(2);
+        copy.clone_from(&vec);
+        assert_eq!(copy[..], vec[..]);
+    }
+
+    #[test]
+    fn test_std_traits() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let vec: MyVec<i32> = (1..4).collect();
+        assert_eq!(format!("{vec:?}"), "[1, 2, 3]");
+        assert_eq!(vec, vec![1, 2, 3]);
+        assert_eq!(vec, [1, 2, 3]);
+        assert_eq!(vec, &[1, 2, 3][..]);
+        assert_eq!(vec![1, 2, 3], vec);
+        assert_ne!(vec, (1..3).collect::<MyVec<_>>());
+
+        let other: MyVec<i32> = [1, 3].into_iter().collect();
+        assert!(vec < other);
+        assert_eq!(vec.cmp(&vec.clone()), std::cmp::Ordering::Equal);
+        assert!(MyVec::<f64>::new().partial_cmp(&MyVec::new()).is_some());
+
+        let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            value(&mut hasher);
+            hasher.finish()
         };
+        assert_eq!(hash(&|h| vec.hash(h)), hash(&|h| [1, 2, 3][..].hash(h)));
+    }
 
-        if new_layout.size() > isize::MAX as usize {
-            panic!("Allocation too large");
+    #[test]
+    fn test_swap_remove() {
+        let mut vec: MyVec<String> = (0..5).map(|i| i.to_string()).collect();
+        assert_eq!(vec.swap_remove(1), "1");
+        assert_eq!(vec, ["0", "4", "2", "3"]);
+        assert_eq!(vec.swap_remove(3), "3");
+        assert_eq!(vec, ["0", "4", "2"]);
+        assert_eq!(vec.swap_remove(0), "0");
+        assert_eq!(vec, ["2", "4"]);
+    }
+
+    #[test]
+    fn test_splice() {
+        let make = || (0..6).map(|i| i.to_string()).collect::<MyVec<_>>();
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let mut vec = make();
+        let removed: Vec<_> = vec.splice(1..3, strings(&["a"])).collect();
+        assert_eq!(removed, ["1", "2"]);
+        assert_eq!(vec, ["0", "a", "3", "4", "5"]);
+
+        let mut vec = make();
+        let removed: Vec<_> = vec.splice(1..2, strings(&["a", "b", "c", "d"])).collect();
+        assert_eq!(removed, ["1"]);
+        assert_eq!(vec, ["0", "a", "b", "c", "d", "2", "3", "4", "5"]);
+
+        // Items with no size hint, into an empty range, with the splice dropped unread.
+        let mut vec = make();
+        vec.splice(2..2, strings(&["a", "b"]).into_iter().filter(|_| true));
+        assert_eq!(vec, ["0", "1", "a", "b", "2", "3", "4", "5"]);
+
+        let mut vec = make();
+        let mut splice = vec.splice(4.., strings(&["a", "b", "c"]));
+        assert_eq!(splice.next_back().as_deref(), Some("5"));
+        drop(splice);
+        assert_eq!(vec, ["0", "1", "2", "3", "a", "b", "c"]);
+
+        let mut vec = make();
+        vec.splice(.., None);
+        assert!(vec.is_empty());
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut vec: MyVec<String> = (0..6).map(|i| i.to_string()).collect();
+        let tail = std::thread::spawn({
+            let tail = vec.split_off(4);
+            move || tail
+        })
+        .join()
+        .unwrap();
+        assert_eq!(vec, ["0", "1", "2", "3"]);
+        assert_eq!(tail, ["4", "5"]);
+        assert_eq!(tail.capacity(), 2);
+
+        assert!(vec.split_off(4).is_empty());
+        let all = vec.split_off(0);
+        assert!(vec.is_empty());
+        assert_eq!(all.len(), 4);
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut vec: MyVec<String> = ["a", "a", "b", "c", "c", "c", "a"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        vec.dedup();
+        assert_eq!(vec, ["a", "b", "c", "a"]);
+
+        let mut nums: MyVec<i32> = [1, 3, 5, 2, 4, 7, 7, 6].into_iter().collect();
+        nums.dedup_by_key(|n| *n % 2);
+        assert_eq!(nums, [1, 2, 7, 6]);
+
+        // Fold each run into its first element.
+        let mut counts: MyVec<(char, u32)> = "aaabbc".chars().map(|c| (c, 1)).collect();
+        counts.dedup_by(|a, b| {
+            let same = a.0 == b.0;
+            if same {
+                b.1 += a.1;
+            }
+            same
+        });
+        assert_eq!(counts, [('a', 3), ('b', 2), ('c', 1)]);
+
+        let mut empty = MyVec::<()>::new();
+        empty.dedup();
+        let mut units: MyVec<()> = std::iter::repeat_n((), 5).collect();
+        units.dedup();
+        assert_eq!(units.len(), 1);
+    }
+
+    #[test]
+    fn test_append() {
+        let mut vec: MyVec<String> = (0..3).map(|i| i.to_string()).collect();
+        let mut other: MyVec<String> = (3..5).map(|i| i.to_string()).collect();
+        let cap = other.capacity();
+        vec.append(&mut other);
+        assert_eq!(vec, ["0", "1", "2", "3", "4"]);
+        assert!(other.is_empty());
+        assert_eq!(other.capacity(), cap);
+        vec.append(&mut other);
+        assert_eq!(vec.len(), 5);
+    }
+
+    #[test]
+    fn test_into_boxed_slice() {
+        let mut vec = MyVec::new();
+        vec.reserve(10);
+        vec.extend((0..3).map(|i| i.to_string()));
+        let boxed = vec.into_boxed_slice();
+        assert_eq!(*boxed, ["0", "1", "2"]);
+        assert!(MyVec::<String>::new().into_boxed_slice().is_empty());
+        assert_eq!(MyVec::from_iter([(), ()]).into_boxed_slice().len(), 2);
+
+        let leaked: &'static mut [u8] = MyVec::from_iter([1, 2]).leak();
+        leaked[0] = 3;
+        assert_eq!(leaked, [3, 2]);
+        // Hand it back so the test doesn't leak.
+        drop(unsafe { Box::from_raw(leaked) });
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut vec = MyVec::new();
+        vec.resize(3, "x".to_string());
+        assert_eq!(vec, ["x", "x", "x"]);
+        vec.resize(1, String::new());
+        assert_eq!(vec, ["x"]);
+
+        let mut next = 0;
+        let mut nums = MyVec::new();
+        nums.resize_with(4, || {
+            next += 1;
+            next
+        });
+        assert_eq!(nums, [1, 2, 3, 4]);
+        nums.resize_with(2, || unreachable!());
+        assert_eq!(nums, [1, 2]);
+    }
+
+    #[test]
+    fn test_allocator() {
+        use std::alloc::Layout;
+        use std::ptr::NonNull;
+
+        // Counts the bytes it has out, and refuses to go over a limit.
+        struct Tracked {
+            live: Cell<usize>,
+            limit: usize,
         }
 
-        let new_ptr = if self.cap == 0 {
-            unsafe { alloc::alloc(new_layout) }
-        } else {
-            let old_layout = Layout::array::<T>(self.cap).unwrap();
-            let old_ptr = self.ptr.as_ptr() as *mut u8;
-            unsafe { alloc::realloc(old_ptr, old_layout, new_layout.size()) }
+        unsafe impl Allocator for Tracked {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                if self.live.get() + layout.size() > self.limit {
+                    return Err(AllocError);
+                }
+                self.live.set(self.live.get() + layout.size());
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.live.set(self.live.get() - layout.size());
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        let tracked = Tracked {
+            live: Cell::new(0),
+            limit: 1024,
+        };
+        let mut vec = MyVec::new_in(&tracked);
+        vec.extend(0u64..10);
+        assert_eq!(tracked.live.get(), vec.capacity() * 8);
+        let tail = vec.split_off(5);
+        vec.shrink_to_fit();
+        assert_eq!(tracked.live.get(), 10 * 8);
+        assert!(matches!(
+            vec.try_reserve(200),
+            Err(TryReserveError::AllocError { .. })
+        ));
+        assert_eq!(vec, [0, 1, 2, 3, 4]);
+
+        let mut iter = tail.into_iter();
+        assert_eq!(iter.next(), Some(5));
+        drop(iter);
+        assert_eq!(tracked.live.get(), 5 * 8);
+        drop(vec);
+        assert_eq!(tracked.live.get(), 0);
+    }
+
+    #[test]
+    fn test_into_iter_send() {
+        fn assert_send<S: Send>(_: &S) {}
+        let vec: MyVec<String> = (0..4).map(|i| i.to_string()).collect();
+        let mut iter = vec.into_iter();
+        assert_eq!(iter.next().as_deref(), Some("0"));
+        assert_send(&iter);
+        let rest = std::thread::spawn(move || iter.collect::<Vec<_>>())
+            .join()
+            .unwrap();
+        assert_eq!(rest, ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_into_iter_exact_size() {
+        fn check<I: ExactSizeIterator + DoubleEndedIterator + std::iter::FusedIterator>(
+            mut iter: I,
+            mut len: usize,
+        ) {
+            assert_eq!(iter.len(), len);
+            while len > 0 {
+                if len.is_multiple_of(2) {
+                    assert!(iter.next().is_some());
+                } else {
+                    assert!(iter.next_back().is_some());
+                }
+                len -= 1;
+                assert_eq!(iter.len(), len);
+                assert_eq!(iter.size_hint(), (len, Some(len)));
+            }
+            assert!(iter.next().is_none());
+            assert!(iter.next().is_none());
+        }
+
+        let make = || (0..7).map(|i| i.to_string()).collect::<MyVec<_>>();
+        check(make().into_iter(), 7);
+        check(MyVec::from_iter([(); 5]).into_iter(), 5);
+        check(make().drain(2..5), 3);
+
+        let mut iter = make().into_iter();
+        iter.next();
+        assert_eq!(iter.count(), 6);
+        assert_eq!(make().into_iter().last().as_deref(), Some("6"));
+        assert_eq!(MyVec::<String>::new().into_iter().last(), None);
+    }
+
+    #[test]
+    fn test_raw_parts() {
+        let mut vec: MyVec<String> = (0..3).map(|i| i.to_string()).collect();
+        vec.reserve_exact(5);
+        let (ptr, len, cap) = vec.into_raw_parts();
+        assert_eq!((len, cap), (3, 8));
+        let mut vec = unsafe {
+            // Hand ownership of the last element over separately.
+            let last = std::ptr::read(ptr.add(2));
+            assert_eq!(last, "2");
+            MyVec::from_raw_parts(ptr, len - 1, cap)
         };
+        assert_eq!(vec, ["0", "1"]);
+        vec.push("pushed".to_string());
+        assert_eq!(vec.capacity(), 8);
 
-        self.ptr = match NonNull::new(new_ptr as *mut T) {
-            Some(p) => p,
-            None => alloc::handle_alloc_error(new_layout),
+        // A buffer from somewhere else, with the same layout.
+        let mut std_vec = std::mem::ManuallyDrop::new(vec![1u32, 2, 3]);
+        let vec = unsafe {
+            MyVec::from_raw_parts(std_vec.as_mut_ptr(), std_vec.len(), std_vec.capacity())
         };
-        self.cap = new_cap;
+        assert_eq!(vec, [1, 2, 3]);
+
+        let (ptr, len, cap) = MyVec::<u8>::new().into_raw_parts();
+        assert_eq!((len, cap), (0, 0));
+        assert!(unsafe { MyVec::from_raw_parts(ptr, len, cap) }.is_empty());
+        let (ptr, len, cap) = MyVec::from_iter([(); 3]).into_raw_parts();
+        assert_eq!((len, cap), (3, usize::MAX));
+        assert_eq!(unsafe { MyVec::from_raw_parts(ptr, len, 0) }.len(), 3);
+    }
+
+    #[test]
+    fn test_growth_policy() {
+        use super::{FixedIncrement, OneAndHalf};
+
+        let mut caps = Vec::new();
+        let mut vec = MyVec::new().with_growth::<OneAndHalf>();
+        for i in 0..20 {
+            vec.push(i);
+            if caps.last() != Some(&vec.capacity()) {
+                caps.push(vec.capacity());
+            }
+        }
+        assert_eq!(caps, [1, 2, 3, 4, 6, 9, 13, 19, 28]);
+
+        let mut vec = MyVec::new().with_growth::<FixedIncrement<100>>();
+        vec.extend(0..150);
+        assert_eq!(vec.capacity(), 150);
+        vec.reserve(60);
+        assert_eq!(vec.capacity(), 250);
+        // A policy never gives less than was asked for.
+        vec.reserve(1000);
+        assert_eq!(vec.capacity(), 1150);
+        let tail = vec.split_off(100);
+        assert_eq!(tail.len(), 50);
+
+        let mut vec = vec.with_growth::<super::Doubling>();
+        vec.shrink_to_fit();
+        vec.push(0);
+        assert_eq!(vec.capacity(), 200);
+    }
+
+    #[test]
+    fn test_shrink_on_pop() {
+        use super::ShrinkOnPop;
+
+        let mut vec = MyVec::new().with_growth::<ShrinkOnPop>();
+        vec.extend((0..1000).map(|i| i.to_string()));
+        let peak = vec.capacity();
+        while vec.len() > 10 {
+            vec.pop();
+        }
+        assert!(
+            vec.capacity() < 64,
+            "capacity {} kept after popping",
+            vec.capacity()
+        );
+        assert!(vec.capacity() < peak);
+        assert_eq!(vec[9], "9");
+
+        // Going back and forth at a boundary doesn't reallocate every time.
+        let cap = vec.capacity();
+        for _ in 0..10 {
+            vec.push(String::new());
+            vec.pop();
+        }
+        assert_eq!(vec.capacity(), cap);
+
+        // Plain vectors never shrink on their own.
+        let mut plain: MyVec<i32> = (0..100).collect();
+        plain.truncate(1);
+        plain.pop();
+        assert_eq!(plain.capacity(), 100);
+    }
+
+    #[test]
+    fn test_extend_from_within() {
+        let mut vec: MyVec<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+        vec.extend_from_within(1..);
+        assert_eq!(vec, ["a", "b", "c", "b", "c"]);
+        vec.extend_from_within(..=0);
+        vec.extend_from_within(2..2);
+        assert_eq!(vec, ["a", "b", "c", "b", "c", "a"]);
+
+        // An LZ-style back-reference longer than its distance, copied in steps.
+        let mut out: MyVec<u8> = b"ab".iter().copied().collect();
+        let (distance, mut length) = (2, 5);
+        while length > 0 {
+            let start = out.len() - distance;
+            let run = length.min(distance);
+            out.extend_from_within(start..start + run);
+            length -= run;
+        }
+        assert_eq!(out, *b"abababa");
+    }
+
+    #[test]
+    #[should_panic(expected = "range out of bounds")]
+    fn test_extend_from_within_out_of_bounds() {
+        let mut vec: MyVec<u8> = (0..3).collect();
+        vec.extend_from_within(2..4);
+    }
+
+    #[test]
+    fn test_extract_if() {
+        let mut vec: MyVec<i32> = (1..=10).collect();
+        let evens: MyVec<i32> = vec.extract_if(|x| *x % 2 == 0).collect();
+        assert_eq!(evens, [2, 4, 6, 8, 10]);
+        assert_eq!(vec, [1, 3, 5, 7, 9]);
+
+        // Dropped early: the unchecked elements stay, after the kept ones.
+        let mut vec: MyVec<String> = (1..=6).map(|x| x.to_string()).collect();
+        let mut iter = vec.extract_if(|s| s.as_str() != "1");
+        assert_eq!(iter.next().as_deref(), Some("2"));
+        assert_eq!(iter.next().as_deref(), Some("3"));
+        drop(iter);
+        assert_eq!(vec, ["1", "4", "5", "6"]);
+
+        // A panicking predicate keeps the element it was looking at.
+        let mut vec: MyVec<String> = (1..=5).map(|x| x.to_string()).collect();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            vec.extract_if(|s| {
+                assert_ne!(s.as_str(), "4");
+                s.as_str() == "2"
+            })
+            .for_each(drop);
+        }));
+        assert!(result.is_err());
+        assert_eq!(vec, ["1", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn test_insert_sorted() {
+        let mut vec = MyVec::new();
+        for x in [5, 1, 4, 1, 3] {
+            vec.insert_sorted(x);
+        }
+        assert_eq!(vec, [1, 1, 3, 4, 5]);
+        assert_eq!(vec.insert_sorted(3), 3);
+
+        // Equal elements go after the ones already there.
+        let mut vec: MyVec<(i32, char)> = MyVec::new();
+        for pair in [(2, 'a'), (1, 'b'), (2, 'c'), (1, 'd')] {
+            vec.insert_sorted_by(pair, |a, b| a.0.cmp(&b.0));
+        }
+        assert_eq!(vec, [(1, 'b'), (1, 'd'), (2, 'a'), (2, 'c')]);
+
+        let mut set = MyVec::new();
+        assert_eq!(set.binary_search_insert(2), Ok(0));
+        assert_eq!(set.binary_search_insert(1), Ok(0));
+        assert_eq!(set.binary_search_insert(3), Ok(2));
+        assert_eq!(set.binary_search_insert(2), Err(1));
+        assert_eq!(set, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_spare_capacity() {
+        let mut vec: MyVec<u8> = MyVec::new();
+        vec.extend_from_slice(b"ab");
+        vec.reserve(4);
+        let spare = vec.spare_capacity_mut();
+        assert!(spare.len() >= 4);
+        for (slot, &byte) in spare.iter_mut().zip(b"cdef") {
+            slot.write(byte);
+        }
+        unsafe { vec.set_len(6) };
+        assert_eq!(vec, *b"abcdef");
+
+        let mut full: MyVec<u8> = MyVec::new();
+        assert!(full.spare_capacity_mut().is_empty());
+    }
+
+    #[test]
+    fn test_push_within_capacity() {
+        let mut vec = MyVec::new();
+        assert_eq!(vec.push_within_capacity(1), Err(1));
+        vec.reserve_exact(2);
+        assert_eq!(vec.push_within_capacity(1), Ok(()));
+        assert_eq!(vec.push_within_capacity(2), Ok(()));
+        assert_eq!(vec.push_within_capacity(3), Err(3));
+        assert_eq!(vec, [1, 2]);
+        assert_eq!(vec.capacity(), 2);
+    }
+
+    #[test]
+    fn test_io_write() {
+        use std::io::{IoSlice, Write};
+
+        let mut out: MyVec<u8> = MyVec::new();
+        let n = 1;
+        write!(out, "{n}-two").unwrap();
+        out.write_all(b", ").unwrap();
+        let written = out
+            .write_vectored(&[IoSlice::new(b"thr"), IoSlice::new(b"ee")])
+            .unwrap();
+        assert_eq!(written, 5);
+        out.flush().unwrap();
+        assert_eq!(out, *b"1-two, three");
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        use super::Zeroizing;
+        use std::alloc::Layout;
+        use std::ptr::NonNull;
+
+        let mut vec: MyVec<u8> = b"secret".iter().copied().collect();
+        let cap = vec.capacity();
+        vec.zeroize();
+        assert!(vec.is_empty());
+        assert_eq!(vec.capacity(), cap);
+        let spare = vec.spare_capacity_mut();
+        assert!(spare.iter().all(|b| unsafe { b.assume_init() } == 0));
+
+        // Checks every block is all zeroes by the time it's freed.
+        struct Check;
+        unsafe impl Allocator for Check {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+                Global.allocate(layout)
+            }
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe {
+                    let bytes = std::slice::from_raw_parts(ptr.as_ptr(), layout.size());
+                    assert!(bytes.iter().all(|&b| b == 0));
+                    Global.deallocate(ptr, layout);
+                }
+            }
+        }
+
+        let mut vec = MyVec::new_in(Zeroizing(Check));
+        for i in 1..=100u32 {
+            vec.push(i);
+        }
+        vec.truncate(3);
+        vec.shrink_to_fit();
+        assert_eq!(vec, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_conversions() {
+        use std::collections::HashSet;
+
+        let std_vec: Vec<String> = (0..5).map(|x| x.to_string()).collect();
+        let ptr = std_vec.as_ptr();
+        let vec = MyVec::from(std_vec);
+        assert_eq!(vec.as_ptr(), ptr);
+        assert_eq!(vec, ["0", "1", "2", "3", "4"]);
+        let std_vec = Vec::from(vec);
+        assert_eq!(std_vec.as_ptr(), ptr);
+        assert_eq!(std_vec, ["0", "1", "2", "3", "4"]);
+
+        let zsts = Vec::from(MyVec::from(vec![(); 3]));
+        assert_eq!(zsts.len(), 3);
+        assert_eq!(Vec::from(MyVec::<u8>::new()), Vec::<u8>::new());
+
+        let mut vec = MyVec::from(&[3, 1, 2][..]);
+        vec.as_mut().sort();
+        assert_eq!(vec.as_ref(), [1, 2, 3]);
+
+        // Borrow<[T]> lets a set of MyVecs be searched with a plain slice.
+        let set: HashSet<MyVec<i32>> = [vec].into_iter().collect();
+        assert!(set.contains(&[1, 2, 3][..]));
     }
 }
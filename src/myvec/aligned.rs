@@ -0,0 +1,95 @@
+use std::{alloc::Layout, ptr::NonNull};
+
+use super::{AllocError, Allocator, Global, MyVec};
+
+/// A MyVec whose buffer is aligned to `ALIGN` bytes, for SIMD kernels and direct I/O. Make one
+/// with `AlignedVec::<T, ALIGN>::new_in(Aligned::default())`.
+///
+/// The alignment holds once the vector has allocated, and across every reallocation after that.
+/// Before then, and always for zero-sized `T`, the pointer is only aligned for `T`, but there's
+/// nothing behind it to read.
+pub type AlignedVec<T, const ALIGN: usize, A = Global> = MyVec<T, Aligned<ALIGN, A>>;
+
+/// An allocator that hands out blocks from `A` aligned to at least `ALIGN` bytes, which must be
+/// a power of two.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aligned<const ALIGN: usize, A = Global>(pub A);
+
+impl<const ALIGN: usize, A> Aligned<ALIGN, A> {
+    const VALID: () = assert!(ALIGN.is_power_of_two(), "alignment must be a power of two");
+
+    // The layout actually used for a block asked for with `layout`. Every method maps layouts
+    // the same way, so `A` always sees the layout a block was allocated with. Rounding a huge
+    // layout up to `ALIGN` can overflow, which is reported like any other failed allocation.
+    fn layout(layout: Layout) -> Result<Layout, AllocError> {
+        let () = Self::VALID;
+        layout.align_to(ALIGN).map_err(|_| AllocError)
+    }
+}
+
+unsafe impl<const ALIGN: usize, A: Allocator> Allocator for Aligned<ALIGN, A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.0.allocate(Self::layout(layout)?)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // The block was allocated with this layout, so it mapped fine then and does now.
+        let layout = Self::layout(layout).unwrap();
+        unsafe { self.0.deallocate(ptr, layout) }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let (old_layout, new_layout) = (Self::layout(old_layout)?, Self::layout(new_layout)?);
+        unsafe { self.0.grow(ptr, old_layout, new_layout) }
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<u8>, AllocError> {
+        let (old_layout, new_layout) = (Self::layout(old_layout)?, Self::layout(new_layout)?);
+        unsafe { self.0.shrink(ptr, old_layout, new_layout) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Aligned, AlignedVec};
+    use super::super::TryReserveError;
+
+    #[test]
+    fn test_aligned() {
+        let mut vec = AlignedVec::<u8, 4096>::new_in(Aligned::default());
+        for i in 0..10_000 {
+            vec.push(i as u8);
+            assert_eq!(vec.as_ptr() as usize % 4096, 0);
+        }
+        vec.truncate(10);
+        vec.shrink_to_fit();
+        assert_eq!(vec.as_ptr() as usize % 4096, 0);
+        assert_eq!(vec, (0..10).collect::<Vec<u8>>()[..]);
+
+        // Asking for less than the type's own alignment changes nothing.
+        let mut vec = AlignedVec::<u64, 1>::new_in(Aligned::default());
+        vec.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(vec.as_ptr() as usize % 8, 0);
+    }
+
+    #[test]
+    fn test_aligned_try_reserve() {
+        // The array layout is valid, but rounding it up to the alignment overflows.
+        let mut vec = AlignedVec::<u8, 4096>::new_in(Aligned::default());
+        assert!(matches!(
+            vec.try_reserve(usize::MAX / 2),
+            Err(TryReserveError::AllocError { .. })
+        ));
+        assert_eq!(vec.capacity(), 0);
+    }
+}
@@ -0,0 +1,513 @@
+use super::RawMyVec;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    mem::{self, MaybeUninit},
+    ops::{Index, IndexMut},
+    ptr, slice,
+};
+
+/// A double-ended queue, kept as a ring in a MyVec-style buffer whose capacity is always a power
+/// of two, so that wrapping an index around is a mask rather than a division.
+///
+/// Pushing and popping at either end is amortized O(1). The elements are in at most two runs,
+/// the one starting at `head` and the one that wrapped around to the start of the buffer;
+/// `as_slices` gives both, and `make_contiguous` moves them into one.
+pub struct MyVecDeque<T> {
+    buf: RawMyVec<T>,
+    // Where the front element is.
+    head: usize,
+    len: usize,
+}
+
+impl<T> MyVecDeque<T> {
+    pub fn new() -> Self {
+        MyVecDeque {
+            buf: RawMyVec::new_in(super::Global),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of elements the deque can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.cap()
+    }
+
+    /// Reserves room for at least `additional` more elements.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len.checked_add(additional).expect("capacity overflow");
+        if needed > self.cap() {
+            let new_cap = needed
+                .checked_next_power_of_two()
+                .expect("capacity overflow");
+            self.grow_to(new_cap);
+        }
+    }
+
+    pub fn push_back(&mut self, elem: T) {
+        if self.len == self.cap() {
+            self.grow();
+        }
+        unsafe { ptr::write(self.ptr().add(self.wrap_add(self.head, self.len)), elem) };
+        self.len += 1;
+    }
+
+    pub fn push_front(&mut self, elem: T) {
+        if self.len == self.cap() {
+            self.grow();
+        }
+        self.head = self.wrap_sub(self.head, 1);
+        unsafe { ptr::write(self.ptr().add(self.head), elem) };
+        self.len += 1;
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { ptr::read(self.ptr().add(self.wrap_add(self.head, self.len))) })
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let elem = unsafe { ptr::read(self.ptr().add(self.head)) };
+        self.head = self.wrap_add(self.head, 1);
+        self.len -= 1;
+        Some(elem)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(0)
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len.wrapping_sub(1))
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.get_mut(self.len.wrapping_sub(1))
+    }
+
+    /// Returns the element `index` places from the front.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        (index < self.len).then(|| unsafe { &*self.ptr().add(self.wrap_add(self.head, index)) })
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        (index < self.len).then(|| unsafe { &mut *self.ptr().add(self.wrap_add(self.head, index)) })
+    }
+
+    /// Drops every element, keeping the buffer.
+    pub fn clear(&mut self) {
+        let (front, back) = self.as_mut_slices();
+        let (front, back): (*mut [T], *mut [T]) = (front, back);
+        // Forget the elements first, so a panicking destructor can't lead to them being dropped
+        // again.
+        self.head = 0;
+        self.len = 0;
+        // Drops `back` even if dropping `front` panics.
+        struct DropBack<T>(*mut [T]);
+        impl<T> Drop for DropBack<T> {
+            fn drop(&mut self) {
+                unsafe { ptr::drop_in_place(self.0) }
+            }
+        }
+        let _back = DropBack(back);
+        unsafe { ptr::drop_in_place(front) };
+    }
+
+    /// Returns the elements as two slices, the first starting at the front and the second
+    /// ending at the back. The second is empty unless the elements wrap around the buffer.
+    pub fn as_slices(&self) -> (&[T], &[T]) {
+        let (front, back) = self.ranges();
+        unsafe {
+            (
+                slice::from_raw_parts(self.ptr().add(front.0), front.1),
+                slice::from_raw_parts(self.ptr(), back),
+            )
+        }
+    }
+
+    pub fn as_mut_slices(&mut self) -> (&mut [T], &mut [T]) {
+        let (front, back) = self.ranges();
+        unsafe {
+            (
+                slice::from_raw_parts_mut(self.ptr().add(front.0), front.1),
+                slice::from_raw_parts_mut(self.ptr(), back),
+            )
+        }
+    }
+
+    /// Moves the elements so they're in a single run, and returns it. This is O(capacity) if
+    /// they wrap around the buffer, and O(1) otherwise.
+    pub fn make_contiguous(&mut self) -> &mut [T] {
+        if self.ranges().1 != 0 {
+            // Rotating the whole buffer moves the slot at `head` to the start, and everything
+            // after it follows in order, wrapped-around elements included. The empty slots just
+            // come along for the ride.
+            let cap = self.cap();
+            unsafe {
+                slice::from_raw_parts_mut(self.ptr() as *mut MaybeUninit<T>, cap)
+                    .rotate_left(self.head)
+            };
+            self.head = 0;
+        }
+        self.as_mut_slices().0
+    }
+
+    pub fn iter(&self) -> MyVecDequeIter<'_, T> {
+        let (front, back) = self.as_slices();
+        MyVecDequeIter {
+            front: front.iter(),
+            back: back.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> MyVecDequeIterMut<'_, T> {
+        let (front, back) = self.as_mut_slices();
+        MyVecDequeIterMut {
+            front: front.iter_mut(),
+            back: back.iter_mut(),
+        }
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.buf.ptr.as_ptr()
+    }
+
+    fn cap(&self) -> usize {
+        self.buf.cap
+    }
+
+    // Wraps an index into the buffer. Zero-sized elements all live at the same dangling
+    // pointer, so for those every index is 0.
+    fn wrap(&self, index: usize) -> usize {
+        if mem::size_of::<T>() == 0 {
+            0
+        } else {
+            index & (self.cap().wrapping_sub(1))
+        }
+    }
+
+    fn wrap_add(&self, index: usize, n: usize) -> usize {
+        self.wrap(index.wrapping_add(n))
+    }
+
+    fn wrap_sub(&self, index: usize, n: usize) -> usize {
+        self.wrap(index.wrapping_sub(n))
+    }
+
+    // The start and length of the run at the front, and the length of the wrapped-around run,
+    // which always starts at 0.
+    fn ranges(&self) -> ((usize, usize), usize) {
+        let to_end = self.cap() - self.head;
+        if self.len <= to_end {
+            ((self.head, self.len), 0)
+        } else {
+            ((self.head, to_end), self.len - to_end)
+        }
+    }
+
+    fn grow(&mut self) {
+        // A deque of zero-sized types is only full once its length would overflow.
+        assert!(mem::size_of::<T>() != 0, "capacity overflow");
+        let new_cap = if self.cap() == 0 {
+            4
+        } else {
+            self.cap().checked_mul(2).expect("capacity overflow")
+        };
+        self.grow_to(new_cap);
+    }
+
+    // Reallocates to `new_cap`, a power of two bigger than the capacity, and fixes up the ring.
+    fn grow_to(&mut self, new_cap: usize) {
+        let old_cap = self.cap();
+        let (_, wrapped) = self.ranges();
+        self.buf.grow_to(new_cap);
+        // The run that wrapped around to the start now belongs right after the old end, and the
+        // buffer at least doubled, so there's room for it there.
+        if wrapped > 0 {
+            unsafe { ptr::copy_nonoverlapping(self.ptr(), self.ptr().add(old_cap), wrapped) };
+        }
+    }
+}
+
+impl<T> Drop for MyVecDeque<T> {
+    fn drop(&mut self) {
+        // RawMyVec frees the buffer.
+        self.clear();
+    }
+}
+
+impl<T> Default for MyVecDeque<T> {
+    fn default() -> Self {
+        MyVecDeque::new()
+    }
+}
+
+impl<T> Index<usize> for MyVecDeque<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &T {
+        self.get(index).expect("index out of bounds")
+    }
+}
+
+impl<T> IndexMut<usize> for MyVecDeque<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        self.get_mut(index).expect("index out of bounds")
+    }
+}
+
+impl<T> Extend<T> for MyVecDeque<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|elem| self.push_back(elem));
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for MyVecDeque<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<T> FromIterator<T> for MyVecDeque<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut deque = MyVecDeque::new();
+        deque.extend(iter);
+        deque
+    }
+}
+
+impl<T: Clone> Clone for MyVecDeque<T> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyVecDeque<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq> PartialEq for MyVecDeque<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for MyVecDeque<T> {}
+
+impl<T: Hash> Hash for MyVecDeque<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Where the ring wraps mustn't change the hash, so hash element by element rather than
+        // slice by slice.
+        state.write_usize(self.len);
+        self.iter().for_each(|elem| elem.hash(state));
+    }
+}
+
+/// An iterator over a MyVecDeque's elements, front to back.
+pub struct MyVecDequeIter<'a, T> {
+    front: slice::Iter<'a, T>,
+    back: slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for MyVecDequeIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.front.next().or_else(|| self.back.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.len() + self.back.len();
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for MyVecDequeIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.back.next_back().or_else(|| self.front.next_back())
+    }
+}
+
+impl<T> ExactSizeIterator for MyVecDequeIter<'_, T> {}
+
+impl<T> FusedIterator for MyVecDequeIter<'_, T> {}
+
+impl<T> Clone for MyVecDequeIter<'_, T> {
+    fn clone(&self) -> Self {
+        MyVecDequeIter {
+            front: self.front.clone(),
+            back: self.back.clone(),
+        }
+    }
+}
+
+/// An iterator over mutable references to a MyVecDeque's elements, front to back.
+pub struct MyVecDequeIterMut<'a, T> {
+    front: slice::IterMut<'a, T>,
+    back: slice::IterMut<'a, T>,
+}
+
+impl<'a, T> Iterator for MyVecDequeIterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.front.next().or_else(|| self.back.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.front.len() + self.back.len();
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for MyVecDequeIterMut<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.back.next_back().or_else(|| self.front.next_back())
+    }
+}
+
+impl<T> ExactSizeIterator for MyVecDequeIterMut<'_, T> {}
+
+impl<T> FusedIterator for MyVecDequeIterMut<'_, T> {}
+
+/// An iterator that moves the elements out of a MyVecDeque, made by `into_iter`.
+pub struct MyVecDequeIntoIter<T> {
+    deque: MyVecDeque<T>,
+}
+
+impl<T> Iterator for MyVecDequeIntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.deque.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.deque.len, Some(self.deque.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for MyVecDequeIntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.deque.pop_back()
+    }
+}
+
+impl<T> ExactSizeIterator for MyVecDequeIntoIter<T> {}
+
+impl<T> FusedIterator for MyVecDequeIntoIter<T> {}
+
+impl<T> IntoIterator for MyVecDeque<T> {
+    type Item = T;
+    type IntoIter = MyVecDequeIntoIter<T>;
+    fn into_iter(self) -> MyVecDequeIntoIter<T> {
+        MyVecDequeIntoIter { deque: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MyVecDeque<T> {
+    type Item = &'a T;
+    type IntoIter = MyVecDequeIter<'a, T>;
+    fn into_iter(self) -> MyVecDequeIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut MyVecDeque<T> {
+    type Item = &'a mut T;
+    type IntoIter = MyVecDequeIterMut<'a, T>;
+    fn into_iter(self) -> MyVecDequeIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MyVecDeque;
+
+    #[test]
+    fn test_ring() {
+        let mut deque = MyVecDeque::new();
+        // A sliding window that keeps wrapping around, growing part way through.
+        for i in 0..100 {
+            deque.push_back(i.to_string());
+            if deque.len() > 3 + i / 20 {
+                assert_eq!(deque.pop_front(), Some((i - 3 - i / 20).to_string()));
+            }
+            assert_eq!(deque.back().unwrap(), &i.to_string());
+        }
+        assert_eq!(deque, (93..100).map(|i| i.to_string()).collect());
+        assert!(deque.capacity().is_power_of_two());
+        deque.pop_front();
+        deque.pop_front();
+
+        deque.push_front("94".to_string());
+        deque[1].push('!');
+        assert_eq!(deque.front().unwrap(), "94");
+        assert_eq!(deque.get(1).unwrap(), "95!");
+        assert_eq!(deque.iter().rev().nth(1).unwrap(), "98");
+        assert_eq!(deque.pop_back().unwrap(), "99");
+        assert_eq!(deque.len(), 5);
+
+        let (front, back) = deque.as_slices();
+        assert_eq!(front.len() + back.len(), 5);
+        deque.make_contiguous();
+        assert_eq!(deque.as_slices().0, ["94", "95!", "96", "97", "98"]);
+        assert!(deque.as_slices().1.is_empty());
+
+        let mut iter = deque.into_iter();
+        assert_eq!(iter.next_back().unwrap(), "98");
+        assert_eq!(iter.len(), 4);
+    }
+
+    #[test]
+    fn test_grow_wrapped() {
+        // Fill the ring from the front so it wraps, then grow it.
+        let mut deque: MyVecDeque<i32> = MyVecDeque::new();
+        for i in 0..4 {
+            deque.push_front(i);
+        }
+        deque.push_back(-1);
+        deque.iter_mut().for_each(|x| *x *= 10);
+        assert_eq!(
+            deque.iter().copied().collect::<Vec<_>>(),
+            [30, 20, 10, 0, -10]
+        );
+        assert_eq!(deque.make_contiguous(), [30, 20, 10, 0, -10]);
+
+        let mut zsts = MyVecDeque::new();
+        zsts.push_front(());
+        zsts.push_back(());
+        assert_eq!(zsts.pop_front(), Some(()));
+        assert_eq!(zsts.iter().count(), 1);
+    }
+}
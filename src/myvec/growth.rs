@@ -0,0 +1,67 @@
+use std::marker::PhantomData;
+
+/// How much a MyVec grows its buffer by when it runs out of room.
+///
+/// Doubling keeps pushes amortized O(1) at the cost of up to half the buffer sitting unused, which
+/// matters more the bigger the vector gets. A gentler factor trades a few more reallocations for
+/// less slack, and a fixed increment bounds the slack outright but makes pushes O(n) amortized.
+pub trait GrowthPolicy {
+    /// Returns the capacity to grow a buffer holding `cap` elements to, when at least `needed`
+    /// are wanted. `needed` is always more than `cap`, and anything less than `needed` is
+    /// treated as `needed`.
+    fn grow(cap: usize, needed: usize) -> usize;
+
+    /// Returns a smaller capacity to shrink a buffer holding `cap` elements to, now that `len`
+    /// are left, or `None` to keep it. Called after every pop. The default never shrinks.
+    fn shrink(cap: usize, len: usize) -> Option<usize> {
+        let _ = (cap, len);
+        None
+    }
+}
+
+/// Doubles the capacity each time. This is the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Doubling;
+
+impl GrowthPolicy for Doubling {
+    fn grow(cap: usize, needed: usize) -> usize {
+        needed.max(cap.saturating_mul(2))
+    }
+}
+
+/// Grows the capacity by half each time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OneAndHalf;
+
+impl GrowthPolicy for OneAndHalf {
+    fn grow(cap: usize, needed: usize) -> usize {
+        needed.max(cap.saturating_add(cap / 2))
+    }
+}
+
+/// Grows the capacity by `K` elements each time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedIncrement<const K: usize>;
+
+impl<const K: usize> GrowthPolicy for FixedIncrement<K> {
+    fn grow(cap: usize, needed: usize) -> usize {
+        needed.max(cap.saturating_add(K))
+    }
+}
+
+/// Grows as `P` does, and also halves the capacity whenever a pop leaves the vector less than a
+/// quarter full. A vector that briefly held a lot doesn't keep all that memory forever, and the
+/// gap between the two thresholds keeps a push and pop at the boundary from reallocating each
+/// time, so both stay amortized O(1) with the default `P`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ShrinkOnPop<P = Doubling>(PhantomData<P>);
+
+impl<P: GrowthPolicy> GrowthPolicy for ShrinkOnPop<P> {
+    fn grow(cap: usize, needed: usize) -> usize {
+        P::grow(cap, needed)
+    }
+
+    fn shrink(cap: usize, len: usize) -> Option<usize> {
+        (len < cap / 4).then_some(cap / 2)
+    }
+}
@@ -0,0 +1,278 @@
+use super::MyVec;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{Deref, DerefMut},
+    str,
+};
+
+/// A growable UTF-8 string, stored as a `MyVec<u8>` that always holds valid UTF-8.
+///
+/// Every method that takes a byte index panics unless it falls on a char boundary, the same as
+/// slicing a `str` does, so the bytes can never be split mid-character.
+#[derive(Clone, Default)]
+pub struct MyString {
+    vec: MyVec<u8>,
+}
+
+impl MyString {
+    pub fn new() -> Self {
+        MyString { vec: MyVec::new() }
+    }
+
+    /// Returns the number of bytes the string can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.vec.capacity()
+    }
+
+    /// Reserves room for at least `additional` more bytes.
+    pub fn reserve(&mut self, additional: usize) {
+        self.vec.reserve(additional);
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Every method that writes to `vec` keeps it valid UTF-8.
+        unsafe { str::from_utf8_unchecked(&self.vec) }
+    }
+
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { str::from_utf8_unchecked_mut(&mut self.vec) }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.vec
+    }
+
+    /// Gives up the string's bytes without copying them.
+    pub fn into_bytes(self) -> MyVec<u8> {
+        self.vec
+    }
+
+    pub fn push(&mut self, ch: char) {
+        self.vec
+            .extend_from_copy_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.vec.extend_from_copy_slice(s.as_bytes());
+    }
+
+    /// Removes and returns the last char, or `None` if the string is empty.
+    pub fn pop(&mut self) -> Option<char> {
+        let ch = self.chars().next_back()?;
+        self.vec.truncate(self.len() - ch.len_utf8());
+        Some(ch)
+    }
+
+    /// Inserts `ch` at byte index `idx`, shifting everything after it along.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is past the end or isn't on a char boundary.
+    pub fn insert(&mut self, idx: usize, ch: char) {
+        self.insert_str(idx, ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Inserts `s` at byte index `idx`, shifting everything after it along once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is past the end or isn't on a char boundary.
+    pub fn insert_str(&mut self, idx: usize, s: &str) {
+        assert!(self.is_char_boundary(idx), "not a char boundary");
+        self.vec.splice(idx..idx, s.bytes());
+    }
+
+    /// Shortens the string to `new_len` bytes. Does nothing if it's already that short.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_len` is shorter than the string and isn't on a char boundary.
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len() {
+            assert!(self.is_char_boundary(new_len), "not a char boundary");
+            self.vec.truncate(new_len);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vec.truncate(0);
+    }
+}
+
+impl Deref for MyString {
+    type Target = str;
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl DerefMut for MyString {
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl From<&str> for MyString {
+    fn from(s: &str) -> Self {
+        let mut string = MyString::new();
+        string.vec.reserve_exact(s.len());
+        string.push_str(s);
+        string
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for MyString {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        <&str as arbitrary::Arbitrary>::arbitrary(u).map(MyString::from)
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        <&str as arbitrary::Arbitrary>::arbitrary_take_rest(u).map(MyString::from)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        <&str as arbitrary::Arbitrary>::size_hint(depth)
+    }
+}
+
+impl Extend<char> for MyString {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        // Every char is at least a byte.
+        self.reserve(iter.size_hint().0);
+        iter.for_each(|ch| self.push(ch));
+    }
+}
+
+impl<'a> Extend<&'a str> for MyString {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|s| self.push_str(s));
+    }
+}
+
+impl FromIterator<char> for MyString {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut string = MyString::new();
+        string.extend(iter);
+        string
+    }
+}
+
+impl<'a> FromIterator<&'a str> for MyString {
+    fn from_iter<I: IntoIterator<Item = &'a str>>(iter: I) -> Self {
+        let mut string = MyString::new();
+        string.extend(iter);
+        string
+    }
+}
+
+impl fmt::Write for MyString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+
+    fn write_char(&mut self, ch: char) -> fmt::Result {
+        self.push(ch);
+        Ok(())
+    }
+}
+
+impl fmt::Display for MyString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for MyString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for MyString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl PartialEq<str> for MyString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for MyString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Eq for MyString {}
+
+impl PartialOrd for MyString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MyString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+// Hashes like a `str`, so a MyString and the `str` it equals hash the same.
+impl Hash for MyString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MyString;
+    use std::fmt::Write;
+
+    #[test]
+    fn test_edit() {
+        let mut s = MyString::from("héllo");
+        s.push(' ');
+        s.push_str("wörld");
+        assert_eq!(s, "héllo wörld");
+        assert_eq!(s.len(), 13);
+
+        s.insert_str(0, "¡");
+        s.insert(s.len(), '!');
+        assert_eq!(s, "¡héllo wörld!");
+
+        assert_eq!(s.pop(), Some('!'));
+        s.truncate(8);
+        assert_eq!(s, "¡héllo");
+        write!(s, " {}", 42).unwrap();
+        assert_eq!(s.to_uppercase(), "¡HÉLLO 42");
+
+        s.clear();
+        assert_eq!(s.pop(), None);
+        assert!(s.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn test_truncate_mid_char() {
+        let mut s = MyString::from("é");
+        s.truncate(1);
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let s: MyString = "abc".chars().rev().collect();
+        assert_eq!(s, "cba");
+        let s: MyString = ["ab", "", "ç"].into_iter().collect();
+        assert_eq!(s.as_bytes(), "abç".as_bytes());
+        assert_eq!(format!("{s:?} {s}"), "\"abç\" abç");
+    }
+}
@@ -0,0 +1,148 @@
+use super::{Allocator, GrowthPolicy, MyVec};
+
+/// Integer keys `sort_radix` can sort by. The sort counts at most eight bytes of a key, so this is
+/// sealed to the primitive integers, which all fit.
+pub trait RadixKey: Copy + private::Sealed {
+    /// How many bytes of `radix` are significant.
+    const BYTES: usize;
+
+    /// Maps the key to an unsigned integer with the same ordering.
+    fn radix(self) -> u64;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_radix_key {
+    ($($t:ty),*; $($s:ty => $u:ty),*) => {
+        $(impl private::Sealed for $t {})*
+        $(impl private::Sealed for $s {})*
+        $(impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+            fn radix(self) -> u64 {
+                self as u64
+            }
+        })*
+        // Flipping the sign bit puts the negative numbers before the rest, in order.
+        $(impl RadixKey for $s {
+            const BYTES: usize = std::mem::size_of::<$s>();
+            fn radix(self) -> u64 {
+                (self as $u ^ (1 << (<$u>::BITS - 1))) as u64
+            }
+        })*
+    };
+}
+
+impl_radix_key!(u8, u16, u32, u64, usize; i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize);
+
+// Below this, a comparison sort wins.
+const MIN_LEN: usize = 64;
+
+fn sort_by_key<T: Copy, K: RadixKey>(v: &mut [T], key: impl Fn(&T) -> K) {
+    let digit = |x: &T, d: usize| (key(x).radix() >> (8 * d)) as u8 as usize;
+    let len = v.len();
+    let mut counts = [[0; 256]; 8];
+    let counts = &mut counts[..K::BYTES];
+    for x in v.iter() {
+        for (d, count) in counts.iter_mut().enumerate() {
+            count[digit(x, d)] += 1;
+        }
+    }
+
+    let mut scratch = MyVec::<T>::from(&*v);
+    // Whether the latest pass left the elements in `scratch`.
+    let mut in_scratch = false;
+    for (d, count) in counts.iter().enumerate() {
+        // A digit every element shares can't change the order.
+        if count.contains(&len) {
+            continue;
+        }
+        let mut offsets = [0; 256];
+        for b in 1..256 {
+            offsets[b] = offsets[b - 1] + count[b - 1];
+        }
+        let (src, dst) = if in_scratch {
+            (&scratch[..], &mut *v)
+        } else {
+            (&*v, &mut scratch[..])
+        };
+        for x in src {
+            let b = digit(x, d);
+            dst[offsets[b]] = *x;
+            offsets[b] += 1;
+        }
+        in_scratch = !in_scratch;
+    }
+    if in_scratch {
+        v.copy_from_slice(&scratch);
+    }
+}
+
+impl<T: Copy, A: Allocator, G: GrowthPolicy> MyVec<T, A, G> {
+    /// Sorts the vector with an LSD radix sort, a byte of the key at a time.
+    ///
+    /// This is O(n) per key byte rather than O(n log n), so for large vectors it beats `sort`,
+    /// at the cost of a scratch copy of the vector. Short vectors just use `sort_unstable`.
+    pub fn sort_radix(&mut self)
+    where
+        T: RadixKey + Ord,
+    {
+        if self.len < MIN_LEN {
+            self.sort_unstable();
+        } else {
+            sort_by_key(self, |&x| x);
+        }
+    }
+
+    /// Like `sort_radix`, but sorts by the key `f` gives for each element. The sort is stable.
+    pub fn sort_radix_by_key<K: RadixKey + Ord, F: Fn(&T) -> K>(&mut self, f: F) {
+        if self.len < MIN_LEN {
+            self.sort_by_key(f);
+        } else {
+            sort_by_key(self, f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::MyVec;
+    use crate::test_util::Lcg;
+
+    // Full-width 32-bit numbers, from two of the generator's 16-bit draws.
+    fn numbers(n: usize) -> impl Iterator<Item = u32> {
+        let mut rng = Lcg::new(3);
+        (0..n).map(move |_| rng.below(1 << 16) << 16 | rng.below(1 << 16))
+    }
+
+    #[test]
+    fn test_sort_radix() {
+        for n in [0, 10, 1000] {
+            let mut vec: MyVec<i32> = numbers(n).map(|x| x as i32).collect();
+            let mut expected: Vec<i32> = vec.to_vec();
+            expected.sort();
+            vec.sort_radix();
+            assert_eq!(vec, expected[..]);
+
+            let mut vec: MyVec<u16> = numbers(n).map(|x| x as u16 & 0xff).collect();
+            let mut expected: Vec<u16> = vec.to_vec();
+            expected.sort();
+            vec.sort_radix();
+            assert_eq!(vec, expected[..]);
+        }
+    }
+
+    #[test]
+    fn test_sort_radix_by_key() {
+        // Sorting by a small key keeps pairs with equal keys in their original order.
+        let mut vec: MyVec<(i8, usize)> = numbers(500)
+            .enumerate()
+            .map(|(i, x)| (x as i8 >> 4, i))
+            .collect();
+        let mut expected = vec.to_vec();
+        expected.sort_by_key(|p| p.0);
+        vec.sort_radix_by_key(|p| p.0);
+        assert_eq!(vec, expected[..]);
+    }
+}
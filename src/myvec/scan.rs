@@ -0,0 +1,169 @@
+use super::{Allocator, GrowthPolicy, MyVec};
+use std::mem;
+
+/// The integer types MyVec has a fast `contains` and `position` for.
+///
+/// Rather than comparing one element at a time, these compare a whole register of elements at
+/// once, four registers to an iteration. With SSE2 that's 16-byte registers, so 64 bytes at a
+/// time; anywhere else it's 64-bit words searched with the usual SWAR zero-lane trick, so 32
+/// bytes. `std::simd` would cover every target the same way, but it isn't stable.
+pub trait Scan: Copy + Eq + private::Sealed {
+    #[doc(hidden)]
+    fn splat(self) -> u64;
+    // The lowest and highest bit of every lane.
+    #[doc(hidden)]
+    const LO: u64;
+    #[doc(hidden)]
+    const HI: u64;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_scan {
+    ($($t:ty),*) => {$(
+        impl private::Sealed for $t {}
+
+        impl Scan for $t {
+            fn splat(self) -> u64 {
+                (self as u64).wrapping_mul(Self::LO)
+            }
+            const LO: u64 = u64::MAX / <$t>::MAX as u64;
+            const HI: u64 = Self::LO << (<$t>::BITS - 1);
+        }
+    )*};
+}
+
+impl_scan!(u8, u16, u32, u64);
+
+const WORDS: usize = 4;
+
+/// Returns the index of the first element of `haystack` equal to `needle`.
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+))]
+fn position<T: Scan>(haystack: &[T], needle: T) -> Option<usize> {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    let size = mem::size_of::<T>();
+    let per_reg = mem::size_of::<__m128i>() / size;
+    let per_chunk = WORDS * per_reg;
+    // The movemask bit for the first byte of every lane.
+    let lanes = 0xffff / ((1 << size) - 1);
+    let splat = unsafe { _mm_set1_epi64x(needle.splat() as i64) };
+    let mut chunks = haystack.chunks_exact(per_chunk);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let regs = chunk.as_ptr().cast::<__m128i>();
+        for r in 0..WORDS {
+            let eq = unsafe { _mm_cmpeq_epi8(_mm_loadu_si128(regs.add(r)), splat) };
+            let mut found = unsafe { _mm_movemask_epi8(eq) } as u32;
+            // A lane matches only if all of its bytes did, so fold each lane's bits down onto
+            // its first one.
+            let mut shift = 1;
+            while shift < size {
+                found &= found >> shift;
+                shift *= 2;
+            }
+            found &= lanes;
+            if found != 0 {
+                let j = found.trailing_zeros() as usize / size;
+                return Some(i * per_chunk + r * per_reg + j);
+            }
+        }
+    }
+    let rest = chunks.remainder();
+    let j = rest.iter().position(|&x| x == needle)?;
+    Some(haystack.len() - rest.len() + j)
+}
+
+/// Returns the index of the first element of `haystack` equal to `needle`.
+#[cfg(not(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    target_feature = "sse2"
+)))]
+fn position<T: Scan>(haystack: &[T], needle: T) -> Option<usize> {
+    let per_chunk = WORDS * mem::size_of::<u64>() / mem::size_of::<T>();
+    let splat = needle.splat();
+    let mut chunks = haystack.chunks_exact(per_chunk);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let words = chunk.as_ptr().cast::<u64>();
+        let mut found = 0;
+        for w in 0..WORDS {
+            // Equal lanes are zero after the xor, and only a zero lane can end up with its high
+            // bit set here.
+            let x = unsafe { words.add(w).read_unaligned() } ^ splat;
+            found |= x.wrapping_sub(T::LO) & !x & T::HI;
+        }
+        if found != 0 {
+            // Word order depends on endianness, so find the exact spot the simple way.
+            let j = chunk.iter().position(|&x| x == needle).unwrap();
+            return Some(i * per_chunk + j);
+        }
+    }
+    let rest = chunks.remainder();
+    let j = rest.iter().position(|&x| x == needle)?;
+    Some(haystack.len() - rest.len() + j)
+}
+
+impl<T: Scan, A: Allocator, G: GrowthPolicy> MyVec<T, A, G> {
+    /// Returns whether the vector holds `x`. This shadows the slice method of the same name for
+    /// the element types that have a faster way.
+    pub fn contains(&self, x: &T) -> bool {
+        self.position(x).is_some()
+    }
+
+    /// Returns the index of the first element equal to `x`.
+    pub fn position(&self, x: &T) -> Option<usize> {
+        position(self, *x)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::MyVec;
+
+    #[test]
+    fn test_position() {
+        fn check<T: super::Scan + TryFrom<usize> + std::fmt::Debug>(max: T) {
+            let vec: MyVec<T> = (0..200).filter_map(|i| T::try_from(i % 100).ok()).collect();
+            for i in 0..100 {
+                let x = T::try_from(i).ok().unwrap();
+                assert_eq!(vec.position(&x), Some(i), "{x:?}");
+                assert!(vec.contains(&x));
+            }
+            assert_eq!(vec.position(&max), None);
+            assert!(!MyVec::<T>::new().contains(&max));
+        }
+        check(u8::MAX);
+        check(u16::MAX);
+        check(u32::MAX);
+        check(u64::MAX);
+
+        // Lanes that differ only in their high bit, or that would borrow from a neighbour.
+        let vec: MyVec<u8> = [0x80, 0x01, 0x00, 0x81]
+            .iter()
+            .cycle()
+            .take(64)
+            .copied()
+            .collect();
+        assert_eq!(vec.position(&0x00), Some(2));
+        assert_eq!(vec.position(&0x7f), None);
+        let mut vec: MyVec<u32> = [1 << 31; 40].iter().copied().collect();
+        vec[37] = 0;
+        assert_eq!(vec.position(&0), Some(37));
+        // Bytes that match across the boundary between two lanes.
+        let mut vec: MyVec<u16> = [0x0100, 0x0001].iter().cycle().take(64).copied().collect();
+        assert_eq!(vec.position(&0x0101), None);
+        vec[50] = 0x0101;
+        assert_eq!(vec.position(&0x0101), Some(50));
+
+        // Types without a fast path still get the slice method.
+        let vec: MyVec<String> = ["a".to_string()].into_iter().collect();
+        assert!(vec.contains(&"a".to_string()));
+    }
+}
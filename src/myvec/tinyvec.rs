@@ -0,0 +1,761 @@
+use super::{
+    bounds, MyVec, MyVecDrain, MyVecExtractIf, MyVecIntoIter, MyVecSplice, TryReserveError,
+};
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    mem::{ManuallyDrop, MaybeUninit},
+    ops::{Deref, DerefMut, RangeBounds},
+    ptr, slice,
+};
+
+/// A vector that keeps up to `N` elements inline, and only moves them to a heap-allocated MyVec
+/// once it outgrows that. Vectors that usually stay small never allocate at all.
+///
+/// Once spilled, it stays on the heap until `shrink_to_fit` finds the elements fit inline again.
+///
+/// The API follows MyVec's. `drain`, `splice` and `extract_if` hand out MyVec's own iterators, so
+/// they move inline elements to the heap first.
+pub struct TinyVec<T, const N: usize> {
+    data: Data<T, N>,
+}
+
+enum Data<T, const N: usize> {
+    // The first `len` slots are initialized.
+    Inline {
+        len: usize,
+        buf: [MaybeUninit<T>; N],
+    },
+    Heap(MyVec<T>),
+}
+
+impl<T, const N: usize> TinyVec<T, N> {
+    pub fn new() -> Self {
+        TinyVec {
+            data: Data::Inline {
+                len: 0,
+                buf: [const { MaybeUninit::uninit() }; N],
+            },
+        }
+    }
+
+    /// Returns whether the elements have moved to the heap.
+    pub fn spilled(&self) -> bool {
+        matches!(self.data, Data::Heap(_))
+    }
+
+    /// Returns the number of elements the vector can hold without reallocating. This is `N`
+    /// until it spills.
+    pub fn capacity(&self) -> usize {
+        match &self.data {
+            Data::Inline { .. } => N,
+            Data::Heap(vec) => vec.capacity(),
+        }
+    }
+
+    /// Returns the unused capacity after the elements, for filling in place before committing
+    /// them with `set_len`.
+    pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>] {
+        match &mut self.data {
+            Data::Inline { len, buf } => &mut buf[*len..],
+            Data::Heap(vec) => vec.spare_capacity_mut(),
+        }
+    }
+
+    /// Sets the length without touching the elements.
+    ///
+    /// # Safety
+    ///
+    /// The same as for `MyVec::set_len`: `new_len` must be no more than the capacity, and the
+    /// elements up to it must be initialized.
+    pub unsafe fn set_len(&mut self, new_len: usize) {
+        debug_assert!(new_len <= self.capacity());
+        match &mut self.data {
+            Data::Inline { len, .. } => *len = new_len,
+            Data::Heap(vec) => unsafe { vec.set_len(new_len) },
+        }
+    }
+
+    /// Reserves room for at least `additional` more elements, moving to the heap if they won't
+    /// fit inline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows.
+    pub fn reserve(&mut self, additional: usize) {
+        match &mut self.data {
+            Data::Inline { len, .. } => {
+                let needed = len.checked_add(additional).expect("capacity overflow");
+                if needed > N {
+                    self.spill(needed.max(2 * N));
+                }
+            }
+            Data::Heap(vec) => vec.reserve(additional),
+        }
+    }
+
+    /// Reserves room for exactly `additional` more elements, moving to the heap if they won't
+    /// fit inline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        match &mut self.data {
+            Data::Inline { len, .. } => {
+                let needed = len.checked_add(additional).expect("capacity overflow");
+                if needed > N {
+                    self.spill(needed);
+                }
+            }
+            Data::Heap(vec) => vec.reserve_exact(additional),
+        }
+    }
+
+    /// Like `reserve`, but returns an error instead of panicking or aborting if the memory can't
+    /// be had. The vector is unchanged on failure.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        match &mut self.data {
+            Data::Inline { len, .. } => {
+                let needed = len
+                    .checked_add(additional)
+                    .ok_or(TryReserveError::CapacityOverflow)?;
+                if needed > N {
+                    let mut vec = MyVec::new();
+                    vec.try_reserve(needed.max(2 * N))?;
+                    self.spill_into(vec);
+                }
+                Ok(())
+            }
+            Data::Heap(vec) => vec.try_reserve(additional),
+        }
+    }
+
+    // Moves the inline elements into a MyVec with room for `cap`.
+    fn spill(&mut self, cap: usize) {
+        if self.spilled() {
+            return;
+        }
+        let mut vec = MyVec::new();
+        vec.reserve_exact(cap);
+        self.spill_into(vec);
+    }
+
+    // Moves the inline elements into `vec`, which must be empty with room for them all.
+    fn spill_into(&mut self, mut vec: MyVec<T>) {
+        let Data::Inline { len, buf } = &mut self.data else {
+            return;
+        };
+        debug_assert!(vec.is_empty() && vec.capacity() >= *len);
+        unsafe {
+            ptr::copy_nonoverlapping(buf.as_ptr() as *const T, vec.ptr(), *len);
+            vec.len = *len;
+        }
+        // The elements belong to `vec` now, and the inline buffer never drops anything itself.
+        self.data = Data::Heap(vec);
+    }
+
+    pub fn push(&mut self, elem: T) {
+        if let Data::Inline { len, buf } = &mut self.data {
+            if *len < N {
+                buf[*len].write(elem);
+                *len += 1;
+                return;
+            }
+            self.spill(2 * N.max(1));
+        }
+        let Data::Heap(vec) = &mut self.data else {
+            unreachable!()
+        };
+        vec.push(elem);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.data {
+            Data::Inline { len, buf } => {
+                if *len == 0 {
+                    None
+                } else {
+                    *len -= 1;
+                    Some(unsafe { buf[*len].assume_init_read() })
+                }
+            }
+            Data::Heap(vec) => vec.pop(),
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length.
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(index <= self.len(), "index out of bounds");
+        self.push(elem);
+        self[index..].rotate_right(1);
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.len(), "index out of bounds");
+        self[index..].rotate_left(1);
+        self.pop().unwrap()
+    }
+
+    /// Removes and returns the element at `index`, moving the last element into its place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        assert!(index < self.len(), "index out of bounds");
+        let last = self.len() - 1;
+        self.swap(index, last);
+        self.pop().unwrap()
+    }
+
+    /// Drops every element from `len` on, keeping the capacity.
+    pub fn truncate(&mut self, new_len: usize) {
+        match &mut self.data {
+            Data::Inline { len, buf } => {
+                if new_len >= *len {
+                    return;
+                }
+                let tail = ptr::slice_from_raw_parts_mut(
+                    unsafe { (buf.as_mut_ptr() as *mut T).add(new_len) },
+                    *len - new_len,
+                );
+                // Shorten first, as MyVec does, so a panicking destructor can't cause a double
+                // drop.
+                *len = new_len;
+                unsafe { ptr::drop_in_place(tail) };
+            }
+            Data::Heap(vec) => vec.truncate(new_len),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Keeps only the elements for which `f` returns `true`, in their original order.
+    pub fn retain<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        match &mut self.data {
+            Data::Inline { .. } => {
+                let mut kept = 0;
+                for i in 0..self.len() {
+                    if f(&mut self[i]) {
+                        self.swap(kept, i);
+                        kept += 1;
+                    }
+                }
+                self.truncate(kept);
+            }
+            Data::Heap(vec) => vec.retain_mut(f),
+        }
+    }
+
+    /// Resizes the vector to `new_len`, truncating it or filling the new slots with clones of
+    /// `value`.
+    pub fn resize(&mut self, new_len: usize, value: T)
+    where
+        T: Clone,
+    {
+        match new_len.checked_sub(self.len()) {
+            Some(additional) => self.extend(std::iter::repeat_n(value, additional)),
+            None => self.truncate(new_len),
+        }
+    }
+
+    /// Resizes the vector to `new_len`, truncating it or filling the new slots with what `f`
+    /// returns, called once per slot in order.
+    pub fn resize_with<F: FnMut() -> T>(&mut self, new_len: usize, f: F) {
+        match new_len.checked_sub(self.len()) {
+            Some(additional) => self.extend(std::iter::repeat_with(f).take(additional)),
+            None => self.truncate(new_len),
+        }
+    }
+
+    /// Clones every element of `other` onto the end, reserving room for all of them up front.
+    pub fn extend_from_slice(&mut self, other: &[T])
+    where
+        T: Clone,
+    {
+        self.reserve(other.len());
+        for elem in other {
+            self.push(elem.clone());
+        }
+    }
+
+    /// Clones the elements in `range` onto the end, reserving room for all of them up front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or ends past the end of the vector.
+    pub fn extend_from_within<R: RangeBounds<usize>>(&mut self, range: R)
+    where
+        T: Clone,
+    {
+        let (start, end) = bounds(range, self.len());
+        self.reserve(end - start);
+        for i in start..end {
+            let elem = self[i].clone();
+            self.push(elem);
+        }
+    }
+
+    /// Moves every element of `other` onto the end of `self`, leaving `other` empty.
+    pub fn append<const M: usize>(&mut self, other: &mut TinyVec<T, M>) {
+        let count = other.len();
+        self.reserve(count);
+        unsafe {
+            ptr::copy_nonoverlapping(other.as_ptr(), self.as_mut_ptr().add(self.len()), count);
+            other.set_len(0);
+            self.set_len(self.len() + count);
+        }
+    }
+
+    /// Splits the vector in two at `at`, returning a new vector with the elements from `at` on.
+    /// `self` keeps the elements before `at`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `at` is greater than the length.
+    pub fn split_off(&mut self, at: usize) -> TinyVec<T, N> {
+        assert!(at <= self.len(), "split_off index out of bounds");
+        let count = self.len() - at;
+        let mut other = TinyVec::new();
+        other.reserve_exact(count);
+        unsafe {
+            ptr::copy_nonoverlapping(self.as_ptr().add(at), other.as_mut_ptr(), count);
+            self.set_len(at);
+            other.set_len(count);
+        }
+        other
+    }
+
+    /// Removes the elements in `range` and returns an iterator over them, as `MyVec::drain`
+    /// does. Inline elements move to the heap first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or ends past the end of the vector.
+    pub fn drain<R: RangeBounds<usize>>(&mut self, range: R) -> MyVecDrain<'_, T> {
+        self.heap().drain(range)
+    }
+
+    /// Replaces the elements in `range` with the items of `replace_with`, as `MyVec::splice`
+    /// does. Inline elements move to the heap first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range starts after it ends, or ends past the end of the vector.
+    pub fn splice<R, I>(&mut self, range: R, replace_with: I) -> MyVecSplice<'_, I::IntoIter>
+    where
+        R: RangeBounds<usize>,
+        I: IntoIterator<Item = T>,
+    {
+        self.heap().splice(range, replace_with)
+    }
+
+    /// Returns an iterator that removes and yields the elements for which `f` returns `true`, as
+    /// `MyVec::extract_if` does. Inline elements move to the heap first.
+    pub fn extract_if<F: FnMut(&mut T) -> bool>(&mut self, f: F) -> MyVecExtractIf<'_, T, F> {
+        self.heap().extract_if(f)
+    }
+
+    /// Removes runs of equal elements, keeping the first of each run.
+    pub fn dedup(&mut self)
+    where
+        T: PartialEq,
+    {
+        self.dedup_by(|a, b| a == b);
+    }
+
+    /// Removes runs of elements that map to the same key, keeping the first of each run.
+    pub fn dedup_by_key<K: PartialEq, F: FnMut(&mut T) -> K>(&mut self, mut key: F) {
+        self.dedup_by(|a, b| key(a) == key(b));
+    }
+
+    /// Removes runs of elements that `same_bucket` says belong together, keeping the first of
+    /// each run. `same_bucket(a, b)` is called with each element `a` and the last element kept
+    /// before it, `b`, and removes `a` if it returns `true`.
+    pub fn dedup_by<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut same_bucket: F) {
+        match &mut self.data {
+            Data::Inline { len, .. } => {
+                let len = *len;
+                if len <= 1 {
+                    return;
+                }
+                // Like `retain`, swap the kept elements down so a panic leaves every element
+                // in place.
+                let mut kept = 1;
+                for i in 1..len {
+                    let (before, rest) = self.split_at_mut(i);
+                    if !same_bucket(&mut rest[0], &mut before[kept - 1]) {
+                        self.swap(kept, i);
+                        kept += 1;
+                    }
+                }
+                self.truncate(kept);
+            }
+            Data::Heap(vec) => vec.dedup_by(same_bucket),
+        }
+    }
+
+    /// Frees any spare heap capacity, moving the elements back inline if they fit.
+    pub fn shrink_to_fit(&mut self) {
+        let Data::Heap(vec) = &mut self.data else {
+            return;
+        };
+        if vec.len() > N {
+            vec.shrink_to_fit();
+            return;
+        }
+        let mut buf = [const { MaybeUninit::uninit() }; N];
+        let len = vec.len();
+        unsafe {
+            ptr::copy_nonoverlapping(vec.ptr(), buf.as_mut_ptr() as *mut T, len);
+            vec.len = 0;
+        }
+        // Dropping the emptied MyVec only frees its buffer.
+        self.data = Data::Inline { len, buf };
+    }
+
+    // Moves the elements to the heap if they're still inline, and returns the MyVec holding them.
+    fn heap(&mut self) -> &mut MyVec<T> {
+        let len = self.len();
+        self.spill(len);
+        match &mut self.data {
+            Data::Heap(vec) => vec,
+            Data::Inline { .. } => unreachable!(),
+        }
+    }
+
+    /// Converts into a MyVec, which only allocates if the elements were still inline.
+    pub fn into_vec(mut self) -> MyVec<T> {
+        let len = self.len();
+        self.spill(len);
+        let this = ManuallyDrop::new(self);
+        match &this.data {
+            Data::Heap(vec) => unsafe { ptr::read(vec) },
+            Data::Inline { .. } => unreachable!(),
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for TinyVec<T, N> {
+    fn drop(&mut self) {
+        // The inline buffer never drops anything itself. A heap MyVec frees its buffer once this
+        // is done.
+        self.truncate(0);
+    }
+}
+
+impl<T, const N: usize> Deref for TinyVec<T, N> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        match &self.data {
+            Data::Inline { len, buf } => unsafe {
+                slice::from_raw_parts(buf.as_ptr() as *const T, *len)
+            },
+            Data::Heap(vec) => vec,
+        }
+    }
+}
+
+impl<T, const N: usize> DerefMut for TinyVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        match &mut self.data {
+            Data::Inline { len, buf } => unsafe {
+                slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, *len)
+            },
+            Data::Heap(vec) => vec,
+        }
+    }
+}
+
+impl<T, const N: usize> Extend<T> for TinyVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for TinyVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = TinyVec::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T, const N: usize> IntoIterator for TinyVec<T, N> {
+    type Item = T;
+    type IntoIter = TinyVecIntoIter<T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let this = ManuallyDrop::new(self);
+        let inner = match unsafe { ptr::read(&this.data) } {
+            Data::Inline { len, buf } => IntoIterInner::Inline {
+                buf,
+                start: 0,
+                end: len,
+            },
+            Data::Heap(vec) => IntoIterInner::Heap(vec.into_iter()),
+        };
+        TinyVecIntoIter { inner }
+    }
+}
+
+pub struct TinyVecIntoIter<T, const N: usize> {
+    inner: IntoIterInner<T, N>,
+}
+
+enum IntoIterInner<T, const N: usize> {
+    // The slots in `start..end` are still to be yielded.
+    Inline {
+        buf: [MaybeUninit<T>; N],
+        start: usize,
+        end: usize,
+    },
+    Heap(MyVecIntoIter<T>),
+}
+
+impl<T, const N: usize> Iterator for TinyVecIntoIter<T, N> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline { buf, start, end } => {
+                if start == end {
+                    None
+                } else {
+                    *start += 1;
+                    Some(unsafe { buf[*start - 1].assume_init_read() })
+                }
+            }
+            IntoIterInner::Heap(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            IntoIterInner::Inline { start, end, .. } => (end - start, Some(end - start)),
+            IntoIterInner::Heap(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for TinyVecIntoIter<T, N> {
+    fn next_back(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline { buf, start, end } => {
+                if start == end {
+                    None
+                } else {
+                    *end -= 1;
+                    Some(unsafe { buf[*end].assume_init_read() })
+                }
+            }
+            IntoIterInner::Heap(iter) => iter.next_back(),
+        }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for TinyVecIntoIter<T, N> {}
+
+impl<T, const N: usize> FusedIterator for TinyVecIntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for TinyVecIntoIter<T, N> {
+    fn drop(&mut self) {
+        if let IntoIterInner::Inline { .. } = self.inner {
+            for _ in &mut *self {}
+        }
+    }
+}
+
+impl<T, const N: usize> Default for TinyVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for TinyVec<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for TinyVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq<U>, U, const N: usize, const M: usize> PartialEq<TinyVec<U, M>>
+    for TinyVec<T, N>
+{
+    fn eq(&self, other: &TinyVec<U, M>) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<T: PartialEq<U>, U, const N: usize> PartialEq<[U]> for TinyVec<T, N> {
+    fn eq(&self, other: &[U]) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<T: PartialEq<U>, U, const N: usize, const M: usize> PartialEq<[U; M]> for TinyVec<T, N> {
+    fn eq(&self, other: &[U; M]) -> bool {
+        self[..] == other[..]
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for TinyVec<T, N> {}
+
+impl<T: PartialOrd, const N: usize> PartialOrd for TinyVec<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord, const N: usize> Ord for TinyVec<T, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash, const N: usize> Hash for TinyVec<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: arbitrary::Arbitrary<'a>, const N: usize> arbitrary::Arbitrary<'a> for TinyVec<T, N> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TinyVec;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_spill() {
+        let mut vec = TinyVec::<String, 4>::new();
+        for i in 0..4 {
+            vec.push(i.to_string());
+        }
+        assert!(!vec.spilled());
+        assert_eq!(vec.capacity(), 4);
+        vec.insert(1, "a".to_string());
+        assert!(vec.spilled());
+        assert_eq!(vec, ["0", "a", "1", "2", "3"]);
+        assert_eq!(vec.remove(0), "0");
+        assert_eq!(vec.swap_remove(0), "a");
+        assert_eq!(vec, ["3", "1", "2"]);
+
+        vec.shrink_to_fit();
+        assert!(!vec.spilled());
+        assert_eq!(vec, ["3", "1", "2"]);
+        vec.retain(|s| s != "1");
+        assert_eq!(vec.pop().as_deref(), Some("2"));
+        assert_eq!(vec.clone(), ["3"]);
+        assert_eq!(vec.into_vec(), ["3"]);
+
+        let mut units: TinyVec<(), 0> = [(), (), ()].into_iter().collect();
+        assert!(units.spilled());
+        units.truncate(1);
+        assert_eq!(units.len(), 1);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        thread_local!(static DROPS: Cell<usize> = const { Cell::new(0) });
+        struct Counted(u32);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                DROPS.with(|drops| drops.set(drops.get() + 1));
+            }
+        }
+
+        for n in [3, 8] {
+            DROPS.with(|drops| drops.set(0));
+            let vec: TinyVec<Counted, 4> = (0..n).map(Counted).collect();
+            assert_eq!(vec.spilled(), n > 4);
+            let mut iter = vec.into_iter();
+            assert_eq!(iter.len(), n as usize);
+            assert_eq!(iter.next().map(|c| c.0), Some(0));
+            assert_eq!(iter.next_back().map(|c| c.0), Some(n - 1));
+            drop(iter);
+            assert_eq!(DROPS.with(Cell::get), n as usize);
+        }
+
+        DROPS.with(|drops| drops.set(0));
+        let vec: TinyVec<Counted, 4> = (0..3).map(Counted).collect();
+        drop(vec);
+        assert_eq!(DROPS.with(Cell::get), 3);
+    }
+
+    #[test]
+    fn test_vec_api() {
+        let mut vec = TinyVec::<u32, 8>::new();
+        vec.extend_from_slice(&[1, 1, 2, 3, 3, 3]);
+        vec.dedup();
+        assert!(!vec.spilled());
+        assert_eq!(vec, [1, 2, 3]);
+        vec.extend_from_within(1..);
+        vec.resize(6, 7);
+        assert_eq!(vec, [1, 2, 3, 2, 3, 7]);
+        vec.dedup_by_key(|x| *x / 2);
+        assert_eq!(vec, [1, 2, 7]);
+
+        let mut tail = vec.split_off(1);
+        assert_eq!(vec, [1]);
+        assert_eq!(tail, [2, 7]);
+        let mut next = 10;
+        tail.resize_with(10, || {
+            next += 1;
+            next
+        });
+        assert!(tail.spilled());
+        vec.append(&mut tail);
+        assert!(tail.is_empty());
+        assert_eq!(vec, [1, 2, 7, 11, 12, 13, 14, 15, 16, 17, 18]);
+        vec.resize(2, 0);
+        assert_eq!(vec, [1, 2]);
+
+        let mut vec = TinyVec::<u32, 8>::from_iter(0..6);
+        assert_eq!(vec.drain(1..3).collect::<Vec<_>>(), [1, 2]);
+        assert!(vec.spilled());
+        vec.splice(..1, [8, 9]);
+        assert_eq!(vec, [8, 9, 3, 4, 5]);
+        assert_eq!(
+            vec.extract_if(|x| *x % 2 == 1).collect::<Vec<_>>(),
+            [9, 3, 5]
+        );
+        assert_eq!(vec, [8, 4]);
+
+        let mut vec = TinyVec::<u32, 4>::new();
+        vec.spare_capacity_mut()[0].write(5);
+        unsafe { vec.set_len(1) };
+        vec.reserve_exact(5);
+        assert_eq!(vec.capacity(), 6);
+        assert!(vec.try_reserve(usize::MAX).is_err());
+        assert_eq!(vec, [5]);
+        assert!(TinyVec::<u32, 4>::new().try_reserve(8).is_ok());
+    }
+}
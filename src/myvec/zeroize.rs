@@ -0,0 +1,46 @@
+use std::{
+    alloc::Layout,
+    ptr::{self, NonNull},
+    sync::atomic::{self, Ordering},
+};
+
+use super::{AllocError, Allocator, Global};
+
+/// An allocator that wipes every block with zeroes before handing it back to `A`, for vectors
+/// holding key material or passwords.
+///
+/// Growing or shrinking always moves to a fresh block and wipes the old one, rather than letting
+/// `A` resize in place, since a resize that ends up moving would leave a copy behind that nobody
+/// wipes. Give it to a MyVec with `MyVec::new_in(Zeroizing::default())`, and the buffer is wiped
+/// when the vector is dropped, and every time it reallocates on the way.
+///
+/// This only covers the vector's own buffer. Anything the elements point to, and copies that
+/// have already been moved out, are beyond its reach.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Zeroizing<A = Global>(pub A);
+
+unsafe impl<A: Allocator> Allocator for Zeroizing<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        self.0.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe {
+            wipe(ptr.as_ptr(), layout.size());
+            self.0.deallocate(ptr, layout);
+        }
+    }
+
+    // `grow` and `shrink` are left as the trait's defaults, which copy into a new block and then
+    // free the old one through `deallocate` above.
+}
+
+// Overwrites `len` bytes at `ptr` with zeroes in a way the compiler won't optimize out, even
+// though the memory is about to be freed and never read again.
+pub(super) unsafe fn wipe(ptr: *mut u8, len: usize) {
+    for i in 0..len {
+        unsafe { ptr::write_volatile(ptr.add(i), 0) };
+    }
+    // Keep later frees from being reordered before the writes.
+    atomic::compiler_fence(Ordering::SeqCst);
+}
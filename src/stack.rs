@@ -0,0 +1,234 @@
+/// A stack kept as a singly-linked list: each element sits in its own heap node, pointing at the
+/// one pushed before it.
+///
+/// This is about the simplest collection there is, and the one to read first. Everything is
+/// safe code on `Option<Box<_>>`; the only subtlety is `Drop`, which has to unlink the nodes one
+/// at a time, because letting each Box drop the next would recurse once per element and
+/// overflow the call stack on a long enough list.
+pub struct Stack<T> {
+    head: Link<T>,
+    len: usize,
+}
+
+type Link<T> = Option<Box<Node<T>>>;
+
+struct Node<T> {
+    elem: T,
+    next: Link<T>,
+}
+
+impl<T> Stack<T> {
+    pub fn new() -> Self {
+        Stack { head: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    pub fn push(&mut self, elem: T) {
+        let next = self.head.take();
+        self.head = Some(Box::new(Node { elem, next }));
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.head.take().map(|node| {
+            self.head = node.next;
+            self.len -= 1;
+            node.elem
+        })
+    }
+
+    /// Returns the element on top, the one `pop` would return.
+    pub fn peek(&self) -> Option<&T> {
+        self.head.as_ref().map(|node| &node.elem)
+    }
+
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        self.head.as_mut().map(|node| &mut node.elem)
+    }
+
+    /// Returns an iterator over the elements, from the top down.
+    pub fn iter(&self) -> StackIter<'_, T> {
+        StackIter {
+            next: self.head.as_deref(),
+            len: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> StackIterMut<'_, T> {
+        StackIterMut {
+            next: self.head.as_deref_mut(),
+            len: self.len,
+        }
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        let mut link = self.head.take();
+        while let Some(mut node) = link {
+            // Taking `next` out first means dropping `node` frees just this one node.
+            link = node.next.take();
+        }
+    }
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Stack::new()
+    }
+}
+
+/// Pushes the items in order, so the last one ends up on top.
+impl<T> Extend<T> for Stack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|elem| self.push(elem));
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a, T: arbitrary::Arbitrary<'a>> arbitrary::Arbitrary<'a> for Stack<T> {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+impl<T> FromIterator<T> for Stack<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut stack = Stack::new();
+        stack.extend(iter);
+        stack
+    }
+}
+
+impl<T: std::fmt::Debug> std::fmt::Debug for Stack<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// An iterator over a Stack's elements, from the top down.
+pub struct StackIter<'a, T> {
+    next: Option<&'a Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for StackIter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        self.next.map(|node| {
+            self.next = node.next.as_deref();
+            self.len -= 1;
+            &node.elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for StackIter<'_, T> {}
+
+/// An iterator over mutable references to a Stack's elements, from the top down.
+pub struct StackIterMut<'a, T> {
+    next: Option<&'a mut Node<T>>,
+    len: usize,
+}
+
+impl<'a, T> Iterator for StackIterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        self.next.take().map(|node| {
+            self.next = node.next.as_deref_mut();
+            self.len -= 1;
+            &mut node.elem
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for StackIterMut<'_, T> {}
+
+/// An iterator that pops the elements off a Stack, made by `into_iter`.
+pub struct StackIntoIter<T>(Stack<T>);
+
+impl<T> Iterator for StackIntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.0.pop()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl<T> ExactSizeIterator for StackIntoIter<T> {}
+
+impl<T> IntoIterator for Stack<T> {
+    type Item = T;
+    type IntoIter = StackIntoIter<T>;
+    fn into_iter(self) -> StackIntoIter<T> {
+        StackIntoIter(self)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Stack<T> {
+    type Item = &'a T;
+    type IntoIter = StackIter<'a, T>;
+    fn into_iter(self) -> StackIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut Stack<T> {
+    type Item = &'a mut T;
+    type IntoIter = StackIterMut<'a, T>;
+    fn into_iter(self) -> StackIterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Stack;
+
+    #[test]
+    fn test_basics() {
+        let mut stack = Stack::new();
+        assert_eq!(stack.pop(), None);
+        stack.push(1);
+        stack.push(2);
+        stack.push(3);
+        assert_eq!(stack.peek(), Some(&3));
+        *stack.peek_mut().unwrap() *= 10;
+        assert_eq!(stack.pop(), Some(30));
+        stack.extend([4, 5]);
+        stack.iter_mut().for_each(|x| *x += 1);
+        assert_eq!(stack.iter().copied().collect::<Vec<_>>(), [6, 5, 3, 2]);
+        assert_eq!(format!("{stack:?}"), "[6, 5, 3, 2]");
+        assert_eq!(stack.len(), 4);
+        assert_eq!(stack.into_iter().collect::<Vec<_>>(), [6, 5, 3, 2]);
+    }
+
+    #[test]
+    fn test_long_drop() {
+        // Dropping this recursively would overflow the stack.
+        let stack: Stack<u32> = (0..1_000_000).collect();
+        drop(stack);
+    }
+}
@@ -0,0 +1,15 @@
+/// A small linear congruential generator, so the randomised tests are the same every run without
+/// needing a crate for it.
+pub struct Lcg(u32);
+
+impl Lcg {
+    pub fn new(seed: u32) -> Lcg {
+        Lcg(seed)
+    }
+
+    /// Returns a number below `n`, from the high bits, which are the less predictable ones.
+    pub fn below(&mut self, n: u32) -> u32 {
+        self.0 = self.0.wrapping_mul(1103515245).wrapping_add(12345);
+        (self.0 >> 16) % n
+    }
+}